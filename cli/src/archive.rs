@@ -0,0 +1,149 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use std::path::{Path, PathBuf};
+
+use crate::config::AppConfig;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+pub fn archive_root(ssd_data_root: &Path) -> PathBuf {
+    AppConfig::ssd_syncer_dir(ssd_data_root).join("archive")
+}
+
+/// Moves SSD files aside into `.ssd-syncer/archive/<date>/<relpath>` before they are
+/// overwritten or deleted.
+/// 同一天内同一文件被多次覆盖时，只保留当天最后一次被替换前的版本。
+#[derive(Debug, Clone)]
+pub struct Archive {
+    ssd_root: PathBuf,
+    day_dir: PathBuf,
+}
+
+impl Archive {
+    pub fn new(ssd_data_root: &Path, ssd_root: &Path) -> Self {
+        let today = chrono::Local::now().format(DATE_FORMAT).to_string();
+        Self {
+            ssd_root: ssd_root.to_path_buf(),
+            day_dir: archive_root(ssd_data_root).join(today),
+        }
+    }
+
+    /// Move `path` into today's archive folder if it is an existing file on the SSD side.
+    /// Returns false when the path is not archived (local side or missing).
+    pub fn preserve(&self, path: &Path) -> Result<bool> {
+        let Ok(rel) = path.strip_prefix(&self.ssd_root) else {
+            return Ok(false);
+        };
+        if !path.is_file() {
+            return Ok(false);
+        }
+        let target = self.day_dir.join(rel);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create dir: {}", parent.display()))?;
+        }
+        if target.exists() {
+            std::fs::remove_file(&target)?;
+        }
+        std::fs::rename(path, &target).with_context(|| {
+            format!("Failed to archive {} -> {}", path.display(), target.display())
+        })?;
+        log::debug!("Archived {} -> {}", path.display(), target.display());
+        Ok(true)
+    }
+}
+
+/// Dated archive folders, oldest first.
+fn archive_days(archive_root: &Path) -> Vec<(NaiveDate, PathBuf)> {
+    let Ok(entries) = std::fs::read_dir(archive_root) else {
+        return vec![];
+    };
+    let mut days: Vec<(NaiveDate, PathBuf)> = entries
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            NaiveDate::parse_from_str(&name, DATE_FORMAT)
+                .ok()
+                .map(|d| (d, e.path()))
+        })
+        .collect();
+    days.sort();
+    days
+}
+
+/// Delete archive folders older than `retention_days` (0 = keep forever).
+pub fn prune(archive_root: &Path, retention_days: u32, today: NaiveDate) -> Result<usize> {
+    if retention_days == 0 {
+        return Ok(0);
+    }
+    let cutoff = today - chrono::Duration::days(retention_days as i64);
+    let mut removed = 0;
+    for (day, dir) in archive_days(archive_root) {
+        if day < cutoff {
+            std::fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to remove archive: {}", dir.display()))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Archived versions of `rel_path`, oldest first.
+pub fn versions(archive_root: &Path, rel_path: &str) -> Vec<(NaiveDate, PathBuf)> {
+    archive_days(archive_root)
+        .into_iter()
+        .map(|(day, dir)| (day, dir.join(rel_path)))
+        .filter(|(_, path)| path.is_file())
+        .collect()
+}
+
+pub fn parse_day(s: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(s.trim(), DATE_FORMAT)
+        .with_context(|| format!("Invalid date '{}'. Expected YYYY-MM-DD", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_preserve_only_ssd_side() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ssd = tmp.path().join("ssd");
+        let local = tmp.path().join("local");
+        write(&ssd.join("docs/a.txt"), "old");
+        write(&local.join("docs/a.txt"), "local");
+
+        let archive = Archive::new(&ssd, &ssd);
+        assert!(archive.preserve(&ssd.join("docs/a.txt")).unwrap());
+        assert!(!archive.preserve(&local.join("docs/a.txt")).unwrap());
+        assert!(!archive.preserve(&ssd.join("missing.txt")).unwrap());
+
+        assert!(!ssd.join("docs/a.txt").exists());
+        let found = versions(&archive_root(&ssd), "docs/a.txt");
+        assert_eq!(found.len(), 1);
+        assert_eq!(std::fs::read_to_string(&found[0].1).unwrap(), "old");
+    }
+
+    #[test]
+    fn test_prune_retention() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        write(&root.join("2024-01-01/a.txt"), "1");
+        write(&root.join("2024-01-20/a.txt"), "2");
+        write(&root.join("not-a-date/a.txt"), "3");
+
+        let today = parse_day("2024-01-31").unwrap();
+        assert_eq!(prune(root, 0, today).unwrap(), 0);
+        assert_eq!(prune(root, 14, today).unwrap(), 1);
+        assert!(!root.join("2024-01-01").exists());
+        assert!(root.join("2024-01-20").exists());
+        assert!(root.join("not-a-date").exists());
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::AppConfig;
+use crate::snapshot::Snapshot;
+
+/// Characters NTFS, exFAT and FAT reject in file names (besides control characters).
+const ILLEGAL_CHARS: [char; 8] = ['<', '>', ':', '"', '|', '?', '*', '\\'];
+
+/// Filesystem types that apply the Windows file name rules.
+const RESTRICTIVE_FS_TYPES: [&str; 8] = ["vfat", "msdos", "exfat", "ntfs", "ntfs3", "fuseblk", "fat32", "smbfs"];
+
+/// First character in `rel_path` (components separated by '/') that the restrictive
+/// filesystems reject.
+pub fn illegal_char(rel_path: &str) -> Option<char> {
+    rel_path
+        .chars()
+        .find(|c| ILLEGAL_CHARS.contains(c) || c.is_control())
+}
+
+/// Replace illegal characters with their fullwidth look-alikes (`:` → `：`), keeping the name
+/// readable. Control characters become `_`.
+pub fn sanitize(rel_path: &str) -> String {
+    rel_path
+        .chars()
+        .map(|c| {
+            if ILLEGAL_CHARS.contains(&c) {
+                // 全角字符与 ASCII 可见字符的码位相差 0xFEE0
+                char::from_u32(c as u32 + 0xFEE0).unwrap_or('_')
+            } else if c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Whether `path` lives on a filesystem that rejects the characters in [`ILLEGAL_CHARS`].
+pub fn is_restrictive_fs(path: &Path) -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    filesystem_type(path).is_some_and(|fs| RESTRICTIVE_FS_TYPES.contains(&fs.as_str()))
+}
+
+/// Filesystem type of the mount holding `path` (longest matching mount point).
+fn filesystem_type(path: &Path) -> Option<String> {
+    let path = path.canonicalize().ok()?;
+    let mounts: Vec<(PathBuf, String)> = if cfg!(target_os = "linux") {
+        // /proc/self/mounts: "<device> <mount point> <fs type> ..."，空格转义为 \040
+        std::fs::read_to_string("/proc/self/mounts")
+            .ok()?
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let mount_point = fields.nth(1)?.replace("\\040", " ");
+                Some((PathBuf::from(mount_point), fields.next()?.to_string()))
+            })
+            .collect()
+    } else if cfg!(target_os = "macos") {
+        // `mount` 输出："<device> on <mount point> (<fs type>, ...)"
+        let output = std::process::Command::new("mount").output().ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let (_, rest) = line.split_once(" on ")?;
+                let (mount_point, options) = rest.rsplit_once(" (")?;
+                let fs_type = options.split([',', ')']).next()?.trim();
+                Some((PathBuf::from(mount_point), fs_type.to_string()))
+            })
+            .collect()
+    } else {
+        return None;
+    };
+    mounts
+        .into_iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.as_os_str().len())
+        .map(|(_, fs_type)| fs_type.to_lowercase())
+}
+
+/// Names sanitized for the SSD, stored on the SSD so every machine maps them back.
+/// 记录的是 SSD 上的名字 → 原始名字；祖先目录也一并记录，便于按前缀还原整棵子树。
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct NameMap {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    dirty: bool,
+    /// SSD name → original name
+    names: BTreeMap<String, String>,
+    #[serde(skip)]
+    originals: BTreeMap<String, String>,
+}
+
+impl NameMap {
+    pub fn file_path(ssd_data_root: &Path, ssd_rel: &str) -> PathBuf {
+        AppConfig::ssd_syncer_dir(ssd_data_root)
+            .join("names")
+            .join(Snapshot::snapshot_filename(ssd_rel))
+    }
+
+    pub fn load(ssd_data_root: &Path, ssd_rel: &str) -> Result<Self> {
+        let path = Self::file_path(ssd_data_root, ssd_rel);
+        let mut map: NameMap = if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", path.display()))?
+        } else {
+            NameMap::default()
+        };
+        map.path = path;
+        map.originals = map.names.iter().map(|(s, o)| (o.clone(), s.clone())).collect();
+        Ok(map)
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&self.path, content)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Remember that `original` is stored on the SSD as `sanitized`.
+    pub fn record(&mut self, original: &str, sanitized: &str) {
+        let pairs = original.split('/').zip(sanitized.split('/'));
+        let mut orig_prefix = String::new();
+        let mut ssd_prefix = String::new();
+        for (o, s) in pairs {
+            if !orig_prefix.is_empty() {
+                orig_prefix.push('/');
+                ssd_prefix.push('/');
+            }
+            orig_prefix.push_str(o);
+            ssd_prefix.push_str(s);
+            if o != s && !self.names.contains_key(&ssd_prefix) {
+                self.names.insert(ssd_prefix.clone(), orig_prefix.clone());
+                self.originals.insert(orig_prefix.clone(), ssd_prefix.clone());
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Original name of an SSD path.
+    pub fn decode(&self, ssd_path: &str) -> String {
+        Self::replace_prefix(&self.names, ssd_path)
+    }
+
+    /// SSD spelling of an original path, as far as it has been recorded.
+    pub fn encode(&self, original: &str) -> String {
+        Self::replace_prefix(&self.originals, original)
+    }
+
+    pub fn decode_snapshot(&self, snapshot: &mut Snapshot) {
+        if self.names.is_empty() {
+            return;
+        }
+        let files = std::mem::take(&mut snapshot.files);
+        snapshot.files = files.into_iter().map(|(p, e)| (self.decode(&p), e)).collect();
+        let in_flux = std::mem::take(&mut snapshot.in_flux);
+        snapshot.in_flux = in_flux.iter().map(|p| self.decode(p)).collect();
+        let oversized = std::mem::take(&mut snapshot.oversized);
+        snapshot.oversized = oversized.into_iter().map(|(p, size)| (self.decode(&p), size)).collect();
+    }
+
+    fn replace_prefix(map: &BTreeMap<String, String>, path: &str) -> String {
+        let mut end = path.len();
+        loop {
+            if let Some(replacement) = map.get(&path[..end]) {
+                return format!("{}{}", replacement, &path[end..]);
+            }
+            match path[..end].rfind('/') {
+                Some(pos) => end = pos,
+                None => return path.to_string(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_and_illegal_char() {
+        assert_eq!(illegal_char("docs/a:b.txt"), Some(':'));
+        assert_eq!(illegal_char("docs/报告：终稿?.txt"), Some('?'));
+        assert_eq!(illegal_char("docs/plain.txt"), None);
+        assert_eq!(sanitize("a:b/c*d?.txt"), "a：b/c＊d？.txt");
+        assert_eq!(illegal_char(&sanitize("x<y>|\"\\z\u{7}")), None);
+    }
+
+    #[test]
+    fn test_name_map_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut names = NameMap::load(tmp.path(), "work").unwrap();
+        names.record("a:b/c?.txt", &sanitize("a:b/c?.txt"));
+        names.save().unwrap();
+
+        let names = NameMap::load(tmp.path(), "work").unwrap();
+        assert_eq!(names.decode("a：b/c？.txt"), "a:b/c?.txt");
+        // 目录已记录：其下的新文件也能还原
+        assert_eq!(names.decode("a：b/new.txt"), "a:b/new.txt");
+        assert_eq!(names.encode("a:b/new.txt"), "a：b/new.txt");
+        // 本来就含全角字符的名字不受影响
+        assert_eq!(names.decode("报告：终稿.txt"), "报告：终稿.txt");
+    }
+}
@@ -1,16 +1,81 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Management directory (config at home, snapshots/logs on the SSD).
+/// 扫描时始终跳过该目录，不依赖忽略规则。
+pub const SYNCER_DIR_NAME: &str = ".ssd-syncer";
+
+/// Machine name set with `--machine` for this run only (never written to the config).
+static MACHINE_OVERRIDE: OnceLock<String> = OnceLock::new();
+
+/// Use `name` instead of `[machine] name` for the rest of the run.
+pub fn set_machine_override(name: &str) {
+    let _ = MACHINE_OVERRIDE.set(name.to_string());
+}
+
+/// Config file given with `--config` for this run.
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use the config at `path` instead of `~/.ssd-syncer/config.toml` for the rest of the run.
+/// 本机状态（state.toml、哈希缓存）也放在它旁边，便于隔离的测试环境或按项目区分的配置。
+pub fn set_config_path(path: &Path) {
+    let _ = CONFIG_PATH_OVERRIDE.set(path.to_path_buf());
+}
+
+/// SSD profile chosen with `--ssd-profile` for this run only.
+static SSD_PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Profile name for the mount in `[machine] ssd_mount`.
+pub const DEFAULT_SSD_PROFILE: &str = "default";
+
+/// Use the mount of SSD profile `name` for the rest of the run.
+pub fn set_ssd_profile(name: &str) {
+    let _ = SSD_PROFILE.set(name.to_string());
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub machine: MachineConfig,
     #[serde(default)]
     pub sync: Vec<SyncMapping>,
+    /// Other SSDs (`[ssd.<profile>]`); `[machine] ssd_mount` is the "default" profile
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ssd: BTreeMap<String, SsdProfile>,
     #[serde(default)]
     pub ignore: IgnoreConfig,
     #[serde(default)]
     pub conflict: ConflictConfig,
+    #[serde(default)]
+    pub sync_settings: SyncSettings,
+    #[serde(default)]
+    pub performance: PerformanceConfig,
+    #[serde(default)]
+    pub safety: SafetyConfig,
+    #[serde(default)]
+    pub filters: FiltersConfig,
+    #[serde(default)]
+    pub compat: CompatConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub io: IoConfig,
+    #[serde(default)]
+    pub scan: ScanConfig,
+    #[serde(default)]
+    pub watch: WatchConfig,
+    /// Mount the mapping paths were moved to by `--ssd-profile` (never saved)
+    #[serde(skip)]
+    profile_mount: Option<String>,
+    /// SSD profile selected by `--ssd-profile` (never saved)
+    #[serde(skip)]
+    active_profile: Option<String>,
+    /// SSD paths of the mappings `--ssd-profile` moved to its mount; only these are moved back
+    /// on save (never saved)
+    #[serde(skip)]
+    rerooted: BTreeSet<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,24 +85,88 @@ pub struct MachineConfig {
     pub ssd_mount: Option<String>,
 }
 
+/// A named SSD (`[ssd.backup] mount = "/Volumes/BACKUP"`).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SsdProfile {
+    pub mount: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncMapping {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     pub local: String,
     pub ssd: String,
+    /// Only sync files with these extensions (case-insensitive, leading dot optional)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    /// Record this mapping's syncs in the shared `sync.log` (false for e.g. test mappings)
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub log: bool,
+    /// Abort the scan when a side has more files than this (guards against a broken ignore list)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<usize>,
+    /// Conflict strategy of this mapping, overriding `[conflict] strategy`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<ConflictStrategy>,
+    /// ID written into the SSD folder on the first sync; a sync against a folder with another
+    /// ID (a different drive mounted at the same path) is refused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drive_id: Option<String>,
+    /// Drive IDs of the folder on the other SSD profiles, by profile name
+    /// (`[sync.drive_ids] backup = "..."`); `drive_id` is the default SSD's
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub drive_ids: BTreeMap<String, String>,
+}
+
+impl SyncMapping {
+    /// Drive ID recorded for this mapping's folder on SSD profile `profile`.
+    pub fn drive_id_on(&self, profile: &str) -> Option<&str> {
+        if profile == DEFAULT_SSD_PROFILE {
+            self.drive_id.as_deref()
+        } else {
+            self.drive_ids.get(profile).map(String::as_str)
+        }
+    }
+
+    pub fn set_drive_id_on(&mut self, profile: &str, id: String) {
+        if profile == DEFAULT_SSD_PROFILE {
+            self.drive_id = Some(id);
+        } else {
+            self.drive_ids.insert(profile.to_string(), id);
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn is_true(b: &bool) -> bool {
+    *b
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IgnoreConfig {
     #[serde(default = "default_ignore_patterns")]
     pub patterns: Vec<String>,
+    /// Match patterns ignoring case. Unset = detect from the local folder's filesystem
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub case_insensitive: Option<bool>,
+}
+
+impl IgnoreConfig {
+    /// Configured patterns plus one-off `--exclude` patterns for this run
+    pub fn patterns_with(&self, exclude: &[String]) -> Vec<String> {
+        self.patterns.iter().chain(exclude).cloned().collect()
+    }
 }
 
 impl Default for IgnoreConfig {
     fn default() -> Self {
         Self {
             patterns: default_ignore_patterns(),
+            case_insensitive: None,
         }
     }
 }
@@ -48,7 +177,7 @@ fn default_ignore_patterns() -> Vec<String> {
         ".DS_Store".to_string(),
         "Thumbs.db".to_string(),
         "desktop.ini".to_string(),
-        ".ssd-syncer".to_string(),
+        SYNCER_DIR_NAME.to_string(),
         // 版本控制
         ".git".to_string(),
         ".svn".to_string(),
@@ -97,12 +226,20 @@ fn default_ignore_patterns() -> Vec<String> {
 pub struct ConflictConfig {
     #[serde(default = "default_conflict_strategy")]
     pub strategy: ConflictStrategy,
+    /// Middle part of conflict copy names: `<stem>.<marker>.<machine>.<time><.ext>`
+    #[serde(default = "default_conflict_marker")]
+    pub marker: String,
+    /// Move files deleted by a sync to the system trash instead of deleting them permanently
+    #[serde(default)]
+    pub trash: bool,
 }
 
 impl Default for ConflictConfig {
     fn default() -> Self {
         Self {
             strategy: default_conflict_strategy(),
+            marker: default_conflict_marker(),
+            trash: false,
         }
     }
 }
@@ -111,6 +248,10 @@ fn default_conflict_strategy() -> ConflictStrategy {
     ConflictStrategy::Both
 }
 
+fn default_conflict_marker() -> String {
+    "conflict".to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub enum ConflictStrategy {
@@ -119,27 +260,416 @@ pub enum ConflictStrategy {
     SsdWins,
     NewerWins,
     Ask,
+    /// Abort the sync without changing anything if any conflict is found (unattended runs)
+    Fail,
+}
+
+/// `[sync_settings]`（`[[sync]]` 已用于映射列表，所以全局同步选项放在单独的表中）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncSettings {
+    #[serde(default = "default_change_detection")]
+    pub change_detection: ChangeDetection,
+    /// Track a per-file version counter on the SSD; `newer-wins` then prefers the side that
+    /// advanced further since the last sync over the newer mtime
+    #[serde(default)]
+    pub version_counters: bool,
+    /// When `sync` shows the planned changes and asks before carrying them out
+    #[serde(default)]
+    pub confirm: ConfirmPolicy,
+    /// Also log every sync with the changed paths to `.ssd-syncer/sync.jsonl` on the SSD
+    #[serde(default)]
+    pub json_log: bool,
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        Self {
+            change_detection: default_change_detection(),
+            version_counters: false,
+            confirm: ConfirmPolicy::Never,
+            json_log: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfirmPolicy {
+    /// Run the plan without asking
+    #[default]
+    Never,
+    /// Ask when the plan deletes something on either side
+    OnDelete,
+    /// Ask before every sync that changes anything
+    Always,
+}
+
+fn default_change_detection() -> ChangeDetection {
+    ChangeDetection::Hash
+}
+
+/// How `compute_changes` decides that a file present in both base and current scan was modified.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeDetection {
+    /// Content hash differs (default, exact)
+    Hash,
+    /// Size or mtime differs. Faster to reason about but can miss same-size edits
+    /// that restore the old mtime, and flags touched-but-unchanged files.
+    SizeMtime,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerformanceConfig {
+    /// Files copied in parallel within one mapping (1 = sequential, in plan order)
+    #[serde(default = "default_copy_concurrency")]
+    pub copy_concurrency: usize,
+    /// Keep file hashes in `~/.ssd-syncer/hash-cache.tsv` so they survive snapshot resets
+    #[serde(default)]
+    pub hash_cache: bool,
+    /// Size cap for the hash cache file; older entries are dropped beyond it
+    #[serde(default = "default_hash_cache_max_mb")]
+    pub hash_cache_max_mb: u64,
+}
+
+impl Default for PerformanceConfig {
+    fn default() -> Self {
+        Self {
+            copy_concurrency: default_copy_concurrency(),
+            hash_cache: false,
+            hash_cache_max_mb: default_hash_cache_max_mb(),
+        }
+    }
+}
+
+fn default_copy_concurrency() -> usize {
+    1
+}
+
+fn default_hash_cache_max_mb() -> u64 {
+    256
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafetyConfig {
+    /// Move SSD files into `.ssd-syncer/archive/<date>/` instead of overwriting/deleting them
+    #[serde(default)]
+    pub archive: bool,
+    /// Days to keep archived versions (0 = keep forever)
+    #[serde(default = "default_archive_retention_days")]
+    pub archive_retention_days: u32,
+    /// Hold each deletion back for one sync: it is staged first and only carried out by the
+    /// next sync if the file is still gone
+    #[serde(default)]
+    pub deferred_delete: bool,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        Self {
+            archive: false,
+            archive_retention_days: default_archive_retention_days(),
+            deferred_delete: false,
+        }
+    }
+}
+
+fn default_archive_retention_days() -> u32 {
+    30
+}
+
+/// Cross-filesystem compatibility settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompatConfig {
+    #[serde(default)]
+    pub illegal_char_policy: IllegalCharPolicy,
+}
+
+/// What to do with names the SSD's filesystem rejects (e.g. `a:b.txt` on exFAT/NTFS).
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IllegalCharPolicy {
+    /// Skip the file and report which character is not allowed
+    #[default]
+    Error,
+    /// Store it under a name with fullwidth look-alikes (`a：b.txt`), mapped back when read
+    Sanitize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutputConfig {
+    /// Replace emoji, arrows and box-drawing characters with ASCII (same as `--ascii`)
+    #[serde(default)]
+    pub ascii: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanConfig {
+    /// Sync what symlinks point to as if it were there. Off = sync the links themselves
+    /// (recreated on the other side; the SSD's filesystem must support symlinks)
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Leave out files larger than this (e.g. "500M", "1G"); they are reported as skipped.
+    /// Unset = no limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<String>,
+    /// Leave out files larger than this (e.g. "4G"), silently, like an ignore pattern
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignore_larger_than: Option<String>,
+    /// Leave out files smaller than this (e.g. "1" skips empty files)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignore_smaller_than: Option<String>,
+    /// Content hash used to detect changes
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
+}
+
+/// `[scan] hash_algorithm`. Hashes are recorded with the algorithm as prefix (`blake3:…`,
+/// `xxh3:…`), so snapshots written with either one can still be read after switching.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HashAlgorithm {
+    /// Cryptographic (default)
+    #[default]
+    Blake3,
+    /// Several times faster, not cryptographic; enough to tell edits apart
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    /// The algorithm a recorded content hash was made with. None for other entries
+    /// (folders, symlinks).
+    pub fn of(hash: &str) -> Option<Self> {
+        let (prefix, _) = hash.split_once(':')?;
+        [HashAlgorithm::Blake3, HashAlgorithm::Xxh3].into_iter().find(|a| a.prefix() == prefix)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IoConfig {
+    /// How long to wait for the SSD to come back when it disappears mid-sync (e.g. "30s").
+    /// Unset = abort on the first failed action as before
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_for_ssd: Option<String>,
+    /// Appended to a file's name while it is being copied; renamed away once complete.
+    /// 扫描时总是跳过以此结尾的文件，未完成的副本不会进入快照
+    #[serde(default = "default_temp_suffix")]
+    pub temp_suffix: String,
+    /// Retry a copy, rename or delete this many times when it fails with an error that is
+    /// usually transient on USB drives (I/O error, timeout, interrupted). 0 = fail immediately
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Wait before the first retry; doubled for each further one
+    #[serde(default = "default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+}
+
+impl Default for IoConfig {
+    fn default() -> Self {
+        Self {
+            wait_for_ssd: None,
+            temp_suffix: default_temp_suffix(),
+            retries: default_retries(),
+            retry_delay_ms: default_retry_delay_ms(),
+        }
+    }
+}
+
+fn default_retries() -> u32 {
+    2
+}
+
+fn default_retry_delay_ms() -> u64 {
+    500
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchConfig {
+    /// `watch` syncs once no watched folder has changed for this long (`--delay` overrides it)
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self { debounce_ms: default_debounce_ms() }
+    }
+}
+
+fn default_debounce_ms() -> u64 {
+    2000
+}
+
+pub const DEFAULT_TEMP_SUFFIX: &str = ".ssd-syncer.tmp";
+
+fn default_temp_suffix() -> String {
+    DEFAULT_TEMP_SUFFIX.to_string()
+}
+
+/// Age limits applied to files while scanning (durations like "10m", "90d").
+/// 超出范围的文件不会被同步；之前已同步过的文件保持不变，不会被当成删除。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FiltersConfig {
+    /// Skip files modified more recently than this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_age: Option<String>,
+    /// Skip files not modified within this period
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<String>,
+    /// Which side(s) empty directories are created on
+    #[serde(default)]
+    pub empty_dirs: EmptyDirPolicy,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmptyDirPolicy {
+    /// Create and delete empty directories on both sides
+    #[default]
+    Both,
+    /// Keep empty directories local: never create them on the SSD or delete them locally
+    LocalOnly,
+    /// Keep empty directories on the SSD: never create them locally or delete them from the SSD
+    SsdOnly,
+    /// Don't sync empty directories at all
+    None,
+}
+
+/// The part of an `export-config` file that `import-config` merges: mappings and ignore patterns.
+#[derive(Debug, Default, Deserialize)]
+pub struct PortableConfig {
+    #[serde(default)]
+    pub sync: Vec<SyncMapping>,
+    #[serde(default)]
+    pub ignore: PortableIgnore,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PortableIgnore {
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+/// What `import-config` changed.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// Names (or SSD paths) of the mappings added
+    pub added: Vec<String>,
+    /// Mappings left out, with the reason
+    pub skipped: Vec<String>,
+    pub patterns: Vec<String>,
+}
+
+/// New values for a mapping, given to `edit`; None keeps the current value.
+#[derive(Debug, Default)]
+pub struct MappingEdit {
+    pub name: Option<String>,
+    pub local: Option<String>,
+    pub ssd: Option<String>,
 }
 
 impl AppConfig {
     pub fn config_dir() -> Result<PathBuf> {
+        if let Some(path) = CONFIG_PATH_OVERRIDE.get() {
+            return Ok(path.parent().map(Path::to_path_buf).unwrap_or_default());
+        }
         let home = dirs::home_dir().context("Cannot determine home directory")?;
-        Ok(home.join(".ssd-syncer"))
+        Ok(home.join(SYNCER_DIR_NAME))
     }
 
     pub fn config_path() -> Result<PathBuf> {
+        if let Some(path) = CONFIG_PATH_OVERRIDE.get() {
+            return Ok(path.clone());
+        }
         Ok(Self::config_dir()?.join("config.toml"))
     }
 
     pub fn load() -> Result<Self> {
-        let path = Self::config_path()?;
+        let mut config = Self::load_file()?;
+        if let Some(name) = MACHINE_OVERRIDE.get() {
+            config.machine.name = name.clone();
+        }
+        if let Some(profile) = SSD_PROFILE.get() {
+            config.use_ssd_profile(profile)?;
+        }
+        Ok(config)
+    }
+
+    /// Mount path of SSD profile `name` ("default" is `[machine] ssd_mount`).
+    pub fn ssd_profile_mount(&self, name: &str) -> Option<&str> {
+        if name == DEFAULT_SSD_PROFILE {
+            return self.machine.ssd_mount.as_deref();
+        }
+        self.ssd.get(name).map(|p| p.mount.as_str())
+    }
+
+    /// Point mappings on the default SSD at the same folders on SSD profile `name`.
+    /// 映射里保存的是默认 SSD 上的绝对路径：换一块硬盘时只替换挂载点前缀。
+    pub fn use_ssd_profile(&mut self, name: &str) -> Result<()> {
+        let Some(mount) = self.ssd_profile_mount(name).map(str::to_string) else {
+            let known: Vec<&str> = self.ssd.keys().map(String::as_str).collect();
+            anyhow::bail!(
+                "Unknown SSD profile '{}' (configured: {}). Add it with `ssd-syncer set-ssd --profile {} <path>`",
+                name,
+                if known.is_empty() { "none".to_string() } else { known.join(", ") },
+                name
+            );
+        };
+        let Some(default) = self.machine.ssd_mount.clone() else {
+            anyhow::bail!(
+                "SSD profiles need the default mount in [machine] ssd_mount: mapping paths are moved from it to the profile's mount"
+            );
+        };
+        for path in self.reroot_mappings(&default, &mount) {
+            log::warn!("{} is not on the default SSD ({}); it keeps its configured path", path, default);
+        }
+        self.machine.ssd_mount = Some(mount.clone());
+        self.profile_mount = Some(mount);
+        self.active_profile = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Name of the SSD profile of this run ("default" without `--ssd-profile`).
+    pub fn ssd_profile(&self) -> &str {
+        self.active_profile.as_deref().unwrap_or(DEFAULT_SSD_PROFILE)
+    }
+
+    /// Replace the `from` prefix of mapping SSD paths with `to`, remembering the new paths in
+    /// `rerooted`. Returns the paths of mappings not under `from`, which are left as they are.
+    fn reroot_mappings(&mut self, from: &str, to: &str) -> Vec<String> {
+        let mut elsewhere = vec![];
+        for mapping in &mut self.sync {
+            match Path::new(&mapping.ssd).strip_prefix(from) {
+                Ok(rest) => {
+                    mapping.ssd = Path::new(to).join(rest).to_string_lossy().to_string();
+                    self.rerooted.insert(mapping.ssd.clone());
+                }
+                Err(_) => elsewhere.push(mapping.ssd.clone()),
+            }
+        }
+        elsewhere
+    }
+
+    fn load_file() -> Result<Self> {
+        Self::load_from(&Self::config_path()?)
+    }
+
+    /// Parse the config file at `path` as it is on disk (no `--machine` / `--ssd-profile`).
+    pub fn load_from(path: &Path) -> Result<Self> {
         if !path.exists() {
             anyhow::bail!(
                 "Config not found at {}. Run `ssd-syncer init` first.",
                 path.display()
             );
         }
-        let content = std::fs::read_to_string(&path)
+        let content = std::fs::read_to_string(path)
             .with_context(|| format!("Failed to read config: {}", path.display()))?;
         let config: AppConfig =
             toml::from_str(&content).with_context(|| "Failed to parse config")?;
@@ -151,11 +681,80 @@ impl AppConfig {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
-        let content = toml::to_string_pretty(self)?;
+        let on_disk = if MACHINE_OVERRIDE.get().is_some() || self.profile_mount.is_some() {
+            Self::load_file().ok()
+        } else {
+            None
+        };
+        let config = self.for_saving(on_disk.as_ref());
+        let content = toml::to_string_pretty(&config)?;
         std::fs::write(&path, content)?;
         Ok(())
     }
 
+    /// The config as it is written back: without the changes `--machine` and `--ssd-profile`
+    /// make for one run, taking the original values from `on_disk`.
+    fn for_saving(&self, on_disk: Option<&Self>) -> Self {
+        let mut config = self.clone();
+        let Some(on_disk) = on_disk else {
+            return config;
+        };
+        // `--machine` 只对本次运行生效：保存时保留配置文件中原来的名字
+        if MACHINE_OVERRIDE.get().is_some() {
+            config.machine.name = on_disk.machine.name.clone();
+        }
+        // `--ssd-profile` 同理：加载时移到该配置挂载点的映射换回默认 SSD 的挂载点，
+        // 本来就在其他路径（包括该配置的硬盘上）的映射保持原样
+        if let Some(mount) = &self.profile_mount {
+            if let Some(default) = &on_disk.machine.ssd_mount {
+                for mapping in config.sync.iter_mut().filter(|m| self.rerooted.contains(&m.ssd)) {
+                    if let Ok(rest) = Path::new(&mapping.ssd).strip_prefix(mount) {
+                        mapping.ssd = Path::new(default).join(rest).to_string_lossy().to_string();
+                    }
+                }
+            }
+            if config.machine.ssd_mount.as_ref() == Some(mount) {
+                config.machine.ssd_mount = on_disk.machine.ssd_mount.clone();
+            }
+        }
+        config
+    }
+
+    /// The config without what belongs to this machine (`[machine]` and the `[ssd.*]` mounts),
+    /// as TOML for `export-config`.
+    pub fn to_portable(&self) -> Result<String> {
+        let mut value = toml::Value::try_from(self)?;
+        if let Some(table) = value.as_table_mut() {
+            table.remove("machine");
+            table.remove("ssd");
+        }
+        Ok(toml::to_string_pretty(&value)?)
+    }
+
+    /// Add the mappings and ignore patterns of an exported config. Mappings whose SSD folder
+    /// or name is already configured are skipped; the machine identity is never touched.
+    pub fn import(&mut self, portable: PortableConfig) -> ImportReport {
+        let mut report = ImportReport::default();
+        for mapping in portable.sync {
+            let label = mapping.name.clone().unwrap_or_else(|| mapping.ssd.clone());
+            if self.sync.iter().any(|m| m.ssd == mapping.ssd) {
+                report.skipped.push(format!("{} (SSD folder already mapped: {})", label, mapping.ssd));
+            } else if mapping.name.as_deref().is_some_and(|n| self.find_mapping_by_name(n).is_some()) {
+                report.skipped.push(format!("{} (name already used)", label));
+            } else {
+                self.sync.push(mapping);
+                report.added.push(label);
+            }
+        }
+        for pattern in portable.ignore.patterns {
+            if !self.ignore.patterns.contains(&pattern) {
+                self.ignore.patterns.push(pattern.clone());
+                report.patterns.push(pattern);
+            }
+        }
+        report
+    }
+
     pub fn create_new(machine_name: &str) -> Result<Self> {
         let config = AppConfig {
             machine: MachineConfig {
@@ -163,8 +762,21 @@ impl AppConfig {
                 ssd_mount: None,
             },
             sync: vec![],
+            ssd: BTreeMap::new(),
             ignore: IgnoreConfig::default(),
             conflict: ConflictConfig::default(),
+            sync_settings: SyncSettings::default(),
+            performance: PerformanceConfig::default(),
+            safety: SafetyConfig::default(),
+            filters: FiltersConfig::default(),
+            compat: CompatConfig::default(),
+            output: OutputConfig::default(),
+            io: IoConfig::default(),
+            scan: ScanConfig::default(),
+            watch: WatchConfig::default(),
+            profile_mount: None,
+            active_profile: None,
+            rerooted: BTreeSet::new(),
         };
         config.save()?;
         Ok(config)
@@ -174,8 +786,79 @@ impl AppConfig {
         self.sync.iter().find(|m| m.name.as_deref() == Some(name))
     }
 
+    /// Change the name, local folder or SSD folder of the mapping named `target` (or whose SSD
+    /// folder is `target`). Returns the mapping before and after the change.
+    pub fn edit_mapping(&mut self, target: &str, edit: MappingEdit) -> Result<(SyncMapping, SyncMapping)> {
+        let index = self
+            .sync
+            .iter()
+            .position(|m| m.name.as_deref() == Some(target))
+            .or_else(|| self.sync.iter().position(|m| m.ssd == target))
+            .with_context(|| format!("No mapping found with name or SSD folder '{}'", target))?;
+        let mut others = self.sync.iter().enumerate().filter(|(i, _)| *i != index).map(|(_, m)| m);
+
+        if let Some(name) = &edit.name {
+            if others.clone().any(|m| m.name.as_ref() == Some(name)) {
+                anyhow::bail!("Mapping with name '{}' already exists", name);
+            }
+        }
+        if let Some(local) = &edit.local {
+            if !Path::new(local).exists() {
+                anyhow::bail!("Local path does not exist: {}", local);
+            }
+        }
+        if let Some(ssd) = &edit.ssd {
+            if !Path::new(ssd).is_absolute() {
+                anyhow::bail!("SSD path must be an absolute path, got: '{}'", ssd);
+            }
+            if let Some(other) = others.find(|m| m.ssd == *ssd) {
+                anyhow::bail!(
+                    "SSD folder {} is already used by mapping '{}'",
+                    ssd,
+                    other.name.as_deref().unwrap_or(&other.ssd)
+                );
+            }
+        }
+
+        let before = self.sync[index].clone();
+        let mapping = &mut self.sync[index];
+        if let Some(name) = edit.name {
+            mapping.name = Some(name);
+        }
+        if let Some(local) = edit.local {
+            mapping.local = local;
+        }
+        if let Some(ssd) = edit.ssd {
+            // 新的 SSD 文件夹有自己的硬盘 ID（或还没有），下次同步时重新记录
+            if ssd != mapping.ssd {
+                mapping.drive_id = None;
+                mapping.drive_ids.clear();
+            }
+            mapping.ssd = ssd;
+        }
+        Ok((before, mapping.clone()))
+    }
+
+    /// Mappings whose name matches any of `patterns` (exact names or globs such as `proj-*`),
+    /// in config order.
+    pub fn filter_mappings(&self, patterns: &[String]) -> Vec<&SyncMapping> {
+        self.sync
+            .iter()
+            .filter(|m| {
+                m.name.as_deref().is_some_and(|name| {
+                    patterns.iter().any(|p| name == p || crate::ignore::IgnoreMatcher::glob_match(name, p))
+                })
+            })
+            .collect()
+    }
+
+    /// The mapping's own `conflict` strategy, or `[conflict] strategy`.
+    pub fn conflict_strategy_for(&self, mapping: &SyncMapping) -> ConflictStrategy {
+        mapping.conflict.clone().unwrap_or_else(|| self.conflict.strategy.clone())
+    }
+
     pub fn ssd_syncer_dir(ssd_mount: &Path) -> PathBuf {
-        ssd_mount.join(".ssd-syncer")
+        ssd_mount.join(SYNCER_DIR_NAME)
     }
 
     pub fn ssd_snapshots_dir(ssd_mount: &Path, machine_name: &str) -> PathBuf {
@@ -184,3 +867,269 @@ impl AppConfig {
             .join(machine_name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ssd_profiles() {
+        // 旧配置：没有 [ssd.*] 表，ssd_mount 即 default 配置
+        let old = r#"
+            [machine]
+            name = "mac"
+            ssd_mount = "/Volumes/WORK"
+
+            [[sync]]
+            name = "DOCS"
+            local = "/Users/me/docs"
+            ssd = "/Volumes/WORK/DOCS"
+        "#;
+        let mut config: AppConfig = toml::from_str(old).unwrap();
+        assert!(config.ssd.is_empty());
+        assert_eq!(config.ssd_profile_mount(DEFAULT_SSD_PROFILE), Some("/Volumes/WORK"));
+        assert!(config.use_ssd_profile("backup").is_err());
+        config.use_ssd_profile(DEFAULT_SSD_PROFILE).unwrap();
+        assert_eq!(config.sync[0].ssd, "/Volumes/WORK/DOCS");
+        assert!(!toml::to_string(&config).unwrap().contains("[ssd"));
+
+        let new = format!("{}\n[ssd.backup]\nmount = \"/Volumes/BACKUP\"\n\n[[sync]]\nlocal = \"/x\"\nssd = \"/mnt/other/X\"\n", old);
+        let mut config: AppConfig = toml::from_str(&new).unwrap();
+        assert_eq!(config.ssd_profile_mount("backup"), Some("/Volumes/BACKUP"));
+        config.use_ssd_profile("backup").unwrap();
+        assert_eq!(config.sync[0].ssd, "/Volumes/BACKUP/DOCS");
+        assert_eq!(config.sync[1].ssd, "/mnt/other/X");
+        assert_eq!(config.machine.ssd_mount.as_deref(), Some("/Volumes/BACKUP"));
+    }
+
+    #[test]
+    fn test_save_under_profile_keeps_mappings_on_profile_mount() {
+        let text = r#"
+            [machine]
+            name = "mac"
+            ssd_mount = "/Volumes/WORK"
+
+            [ssd.backup]
+            mount = "/Volumes/BACKUP"
+
+            [[sync]]
+            name = "DOCS"
+            local = "/Users/me/docs"
+            ssd = "/Volumes/WORK/DOCS"
+
+            [[sync]]
+            name = "ARCHIVE"
+            local = "/Users/me/archive"
+            ssd = "/Volumes/BACKUP/ARCHIVE"
+        "#;
+        let on_disk: AppConfig = toml::from_str(text).unwrap();
+        let mut config = on_disk.clone();
+        config.use_ssd_profile("backup").unwrap();
+        assert_eq!(config.sync[0].ssd, "/Volumes/BACKUP/DOCS");
+        assert_eq!(config.sync[1].ssd, "/Volumes/BACKUP/ARCHIVE");
+
+        // 只有加载时移过去的 DOCS 换回默认挂载点；本来就在备用 SSD 上的 ARCHIVE 不变
+        let saved = config.for_saving(Some(&on_disk));
+        assert_eq!(saved.sync[0].ssd, "/Volumes/WORK/DOCS");
+        assert_eq!(saved.sync[1].ssd, "/Volumes/BACKUP/ARCHIVE");
+        assert_eq!(saved.machine.ssd_mount.as_deref(), Some("/Volumes/WORK"));
+    }
+
+    #[test]
+    fn test_load_from_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("work.toml");
+        let err = AppConfig::load_from(&path).unwrap_err();
+        assert!(err.to_string().contains("work.toml"));
+
+        std::fs::write(&path, "[machine]\nname = \"ci\"\n").unwrap();
+        let config = AppConfig::load_from(&path).unwrap();
+        assert_eq!(config.machine.name, "ci");
+        assert!(config.sync.is_empty());
+    }
+
+    #[test]
+    fn test_mapping_conflict_strategy() {
+        let config: AppConfig = toml::from_str(
+            r#"
+            [machine]
+            name = "mac"
+
+            [conflict]
+            strategy = "both"
+
+            [[sync]]
+            name = "DOCS"
+            local = "/Users/me/docs"
+            ssd = "/Volumes/WORK/DOCS"
+            conflict = "local-wins"
+
+            [[sync]]
+            name = "CODE"
+            local = "/Users/me/code"
+            ssd = "/Volumes/WORK/CODE"
+            "#,
+        )
+        .unwrap();
+        let docs = config.find_mapping_by_name("DOCS").unwrap();
+        let code = config.find_mapping_by_name("CODE").unwrap();
+        assert_eq!(config.conflict_strategy_for(docs), ConflictStrategy::LocalWins);
+        assert_eq!(config.conflict_strategy_for(code), ConflictStrategy::Both);
+        // 没有覆盖的映射保存时不写出 conflict
+        let saved = toml::to_string(&config).unwrap();
+        assert_eq!(saved.matches("conflict = ").count(), 1, "{}", saved);
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let mut exported: AppConfig = toml::from_str(
+            r#"
+            [machine]
+            name = "mac"
+            ssd_mount = "/Volumes/WORK"
+
+            [ssd.backup]
+            mount = "/Volumes/BACKUP"
+
+            [[sync]]
+            name = "DOCS"
+            local = "/Users/me/docs"
+            ssd = "/Volumes/WORK/DOCS"
+            conflict = "newer-wins"
+
+            [[sync]]
+            name = "CODE"
+            local = "/Users/me/code"
+            ssd = "/Volumes/WORK/CODE"
+            "#,
+        )
+        .unwrap();
+        exported.ignore.patterns = vec![".DS_Store".to_string(), "*.psd".to_string()];
+        let text = exported.to_portable().unwrap();
+        assert!(!text.contains("[machine]") && !text.contains("mac\"") && !text.contains("BACKUP"), "{}", text);
+
+        let mut config: AppConfig =
+            toml::from_str("[machine]\nname = \"pc\"\nssd_mount = \"/media/pc/WORK\"\n").unwrap();
+        config.sync.push(SyncMapping {
+            name: Some("CODE-PC".to_string()),
+            local: "/home/pc/code".to_string(),
+            ssd: "/Volumes/WORK/CODE".to_string(),
+            extensions: vec![],
+            log: true,
+            max_files: None,
+            conflict: None,
+            drive_id: None,
+            drive_ids: BTreeMap::new(),
+        });
+        let report = config.import(toml::from_str(&text).unwrap());
+
+        assert_eq!(report.added, vec!["DOCS"]);
+        assert_eq!(report.skipped.len(), 1);
+        assert!(report.skipped[0].starts_with("CODE (SSD folder already mapped"), "{:?}", report.skipped);
+        assert_eq!(report.patterns, vec!["*.psd"]);
+        assert_eq!(config.machine.name, "pc");
+        assert_eq!(config.machine.ssd_mount.as_deref(), Some("/media/pc/WORK"));
+        let docs = config.find_mapping_by_name("DOCS").unwrap();
+        assert_eq!(config.conflict_strategy_for(docs), ConflictStrategy::NewerWins);
+
+        // 再导入一次不会有任何变化
+        let again = config.import(toml::from_str(&text).unwrap());
+        assert!(again.added.is_empty() && again.patterns.is_empty());
+        assert_eq!(again.skipped.len(), 2);
+    }
+
+    fn config_with_mappings(tmp: &Path, names: &[&str]) -> AppConfig {
+        let mut config: AppConfig = toml::from_str("[machine]\nname = \"mac\"\n").unwrap();
+        for name in names {
+            config.sync.push(SyncMapping {
+                name: Some(name.to_string()),
+                local: tmp.join(name).to_string_lossy().to_string(),
+                ssd: format!("/Volumes/WORK/{}", name),
+                extensions: vec![],
+                log: true,
+                max_files: None,
+                conflict: None,
+                drive_id: Some(format!("id-{}", name)),
+                drive_ids: BTreeMap::new(),
+            });
+        }
+        config
+    }
+
+    #[test]
+    fn test_edit_mapping() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tmp.path().join("moved")).unwrap();
+        let mut config = config_with_mappings(tmp.path(), &["DOCS", "CODE"]);
+
+        let rename = MappingEdit { name: Some("NOTES".to_string()), ..Default::default() };
+        let (before, after) = config.edit_mapping("DOCS", rename).unwrap();
+        assert_eq!(before.name.as_deref(), Some("DOCS"));
+        assert_eq!(after.name.as_deref(), Some("NOTES"));
+        assert!(config.find_mapping_by_name("DOCS").is_none());
+        assert_eq!(config.sync[0].drive_id.as_deref(), Some("id-DOCS"));
+
+        // 也可以按 SSD 路径定位；换 SSD 文件夹后清除硬盘 ID
+        let moved = tmp.path().join("moved").to_string_lossy().to_string();
+        let edit = MappingEdit {
+            local: Some(moved.clone()),
+            ssd: Some("/Volumes/NEW/NOTES".to_string()),
+            ..Default::default()
+        };
+        let (_, after) = config.edit_mapping("/Volumes/WORK/DOCS", edit).unwrap();
+        assert_eq!((after.local.as_str(), after.ssd.as_str()), (moved.as_str(), "/Volumes/NEW/NOTES"));
+        assert_eq!(after.drive_id, None);
+        assert_eq!(config.sync[0].ssd, "/Volumes/NEW/NOTES");
+    }
+
+    #[test]
+    fn test_edit_mapping_rejects_collisions() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut config = config_with_mappings(tmp.path(), &["DOCS", "CODE"]);
+        let edit = |name: Option<&str>, local: Option<&str>, ssd: Option<&str>| MappingEdit {
+            name: name.map(str::to_string),
+            local: local.map(str::to_string),
+            ssd: ssd.map(str::to_string),
+        };
+
+        let err = config.edit_mapping("DOCS", edit(Some("CODE"), None, None)).unwrap_err();
+        assert_eq!(err.to_string(), "Mapping with name 'CODE' already exists");
+        let err = config.edit_mapping("DOCS", edit(None, None, Some("/Volumes/WORK/CODE"))).unwrap_err();
+        assert_eq!(err.to_string(), "SSD folder /Volumes/WORK/CODE is already used by mapping 'CODE'");
+        assert!(config.edit_mapping("DOCS", edit(None, None, Some("relative/DOCS"))).is_err());
+        assert!(config.edit_mapping("DOCS", edit(None, Some("/no/such/folder"), None)).is_err());
+        assert!(config.edit_mapping("MISSING", edit(Some("X"), None, None)).is_err());
+        // 改成自己现有的名字或路径不算冲突
+        config.edit_mapping("DOCS", edit(Some("DOCS"), None, Some("/Volumes/WORK/DOCS"))).unwrap();
+        assert_eq!(config.sync[0].drive_id.as_deref(), Some("id-DOCS"));
+        assert_eq!(config.sync[1].name.as_deref(), Some("CODE"));
+    }
+
+    #[test]
+    fn test_filter_mappings() {
+        let mut config: AppConfig = toml::from_str("[machine]\nname = \"mac\"\n").unwrap();
+        for name in ["proj-web", "DOCS", "proj-api", "project"] {
+            config.sync.push(SyncMapping {
+                name: Some(name.to_string()),
+                local: format!("/Users/me/{}", name),
+                ssd: format!("/Volumes/WORK/{}", name),
+                extensions: vec![],
+                log: true,
+                max_files: None,
+                conflict: None,
+                drive_id: None,
+                drive_ids: BTreeMap::new(),
+            });
+        }
+        let names = |patterns: &[&str]| -> Vec<String> {
+            let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+            config.filter_mappings(&patterns).iter().map(|m| m.name.clone().unwrap()).collect()
+        };
+
+        assert_eq!(names(&["proj-*"]), vec!["proj-web", "proj-api"]);
+        assert_eq!(names(&["DOCS"]), vec!["DOCS"]);
+        assert_eq!(names(&["proj"]), Vec::<String>::new());
+        // 多个模式：任一匹配即选中，按配置顺序且不重复
+        assert_eq!(names(&["DOCS", "proj-?eb", "*"]), vec!["proj-web", "DOCS", "proj-api", "project"]);
+    }
+}
@@ -1,5 +1,7 @@
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 
+use crate::config::{ChangeDetection, EmptyDirPolicy, HashAlgorithm};
 use crate::snapshot::{FileEntry, Snapshot};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -9,6 +11,16 @@ pub enum ChangeType {
     Deleted,
 }
 
+impl ChangeType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeType::Added => "added",
+            ChangeType::Modified => "modified",
+            ChangeType::Deleted => "deleted",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FileChange {
     pub path: String,
@@ -23,13 +35,143 @@ pub enum SyncAction {
     CopyToLocal,
     DeleteFromSsd,
     DeleteFromLocal,
+    /// Directory or file renamed locally: rename `from` → entry path on the SSD
+    RenameOnSsd { from: String },
+    /// Directory or file renamed on the SSD: rename `from` → entry path locally
+    RenameOnLocal { from: String },
     Conflict(ConflictInfo),
 }
 
+impl SyncAction {
+    /// Stable name of the action in machine-readable output (`plan --format json`, `sync.jsonl`).
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SyncAction::CopyToSsd => "copy-to-ssd",
+            SyncAction::CopyToLocal => "copy-to-local",
+            SyncAction::DeleteFromSsd => "delete-from-ssd",
+            SyncAction::DeleteFromLocal => "delete-from-local",
+            SyncAction::RenameOnSsd { .. } => "rename-on-ssd",
+            SyncAction::RenameOnLocal { .. } => "rename-on-local",
+            SyncAction::Conflict(_) => "conflict",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ConflictInfo {
     pub local_change: ChangeType,
     pub ssd_change: ChangeType,
+    /// (local, SSD) version counters when `[sync_settings] version_counters` is on
+    pub versions: Option<(u64, u64)>,
+    /// Entry at the last sync (the common ancestor) and on each side now; `None` = absent
+    pub base: Option<FileEntry>,
+    pub local: Option<FileEntry>,
+    pub ssd: Option<FileEntry>,
+}
+
+impl ConflictInfo {
+    /// What changed locally since the last sync, e.g. "1.2 KiB (3f2a9c1e) → 4.0 KiB (77b01d2e)".
+    pub fn local_delta(&self) -> String {
+        delta(self.base.as_ref(), self.local.as_ref())
+    }
+
+    /// What changed on the SSD since the last sync.
+    pub fn ssd_delta(&self) -> String {
+        delta(self.base.as_ref(), self.ssd.as_ref())
+    }
+}
+
+fn describe(e: &FileEntry) -> String {
+    if e.is_dir {
+        "folder".to_string()
+    } else if let Some(target) = e.symlink_target() {
+        format!("link to {}", target)
+    } else {
+        // 哈希带算法前缀（"blake3:..."），只显示摘要的前 8 位
+        let digest = e.hash.rsplit(':').next().unwrap_or(&e.hash);
+        format!("{} ({})", crate::units::format_bytes(e.size), &digest[..digest.len().min(8)])
+    }
+}
+
+/// Size and modification time (local time) as `diff` shows them, e.g. "1.2 KiB, 2026-10-17 08:03".
+fn size_and_mtime(e: &FileEntry) -> String {
+    let mtime = chrono::DateTime::from_timestamp(e.mtime_secs, 0)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "?".to_string());
+    format!("{}, {}", crate::units::format_bytes(e.size), mtime)
+}
+
+fn delta(base: Option<&FileEntry>, now: Option<&FileEntry>) -> String {
+    match (base, now) {
+        (None, None) => "absent".to_string(),
+        (None, Some(now)) => format!("added {}", describe(now)),
+        (Some(base), None) => format!("deleted (was {})", describe(base)),
+        (Some(base), Some(now)) => format!("{} → {}", describe(base), describe(now)),
+    }
+}
+
+/// "added …", "deleted (was …)" or "modified, … → …".
+fn describe_change(change: &ChangeType, base: Option<&FileEntry>, now: Option<&FileEntry>) -> String {
+    match change {
+        ChangeType::Modified => format!("modified, {}", delta(base, now)),
+        ChangeType::Added | ChangeType::Deleted => delta(base, now),
+    }
+}
+
+/// Why each entry of `plan` got its action (`sync --dry-run --explain`): the change seen on
+/// each side since the last sync, with the entries that were compared. One list per entry.
+pub fn explain_plan(
+    plan: &SyncPlan,
+    base: &Snapshot,
+    ssd_cache: &Snapshot,
+    local: &Snapshot,
+    ssd: &Snapshot,
+    detection: ChangeDetection,
+) -> Vec<Vec<String>> {
+    // 与 plan_changes 相同：size-mtime 模式下 SSD 侧与换上 SSD 缓存 size/mtime 的基准比较
+    let ssd_base = match detection {
+        ChangeDetection::Hash => base.clone(),
+        ChangeDetection::SizeMtime => rebase_stats(base, ssd_cache),
+    };
+    let side = |label: &str, base: &Snapshot, now: &Snapshot, path: &str| {
+        let (before, after) = (base.files.get(path), now.files.get(path));
+        let change = match (before, after) {
+            (None, None) => return format!("{} absent", label),
+            (None, Some(_)) => ChangeType::Added,
+            (Some(_), None) => ChangeType::Deleted,
+            (Some(b), Some(a)) if is_modified(b, a, detection) => ChangeType::Modified,
+            (Some(b), Some(_)) => return format!("{} unchanged, {}", label, describe(b)),
+        };
+        format!("{} {}", label, describe_change(&change, before, after))
+    };
+
+    plan.actions
+        .iter()
+        .map(|entry| match &entry.action {
+            SyncAction::Conflict(info) => {
+                let mut lines = vec![
+                    format!("local: {}", describe_change(&info.local_change, info.base.as_ref(), info.local.as_ref())),
+                    format!("SSD:   {}", describe_change(&info.ssd_change, info.base.as_ref(), info.ssd.as_ref())),
+                ];
+                if let Some((local_version, ssd_version)) = info.versions {
+                    lines.push(format!("versions: local v{}, SSD v{}", local_version, ssd_version));
+                }
+                lines
+            }
+            SyncAction::RenameOnSsd { .. } => vec![
+                format!("local: renamed {}, same content", entry.display_path()),
+                "SSD:   unchanged".to_string(),
+            ],
+            SyncAction::RenameOnLocal { .. } => vec![
+                "local: unchanged".to_string(),
+                format!("SSD:   renamed {}, same content", entry.display_path()),
+            ],
+            _ => vec![
+                side("local:", base, local, &entry.path),
+                side("SSD:  ", &ssd_base, ssd, &entry.path),
+            ],
+        })
+        .collect()
 }
 
 #[derive(Debug, Clone)]
@@ -42,10 +184,117 @@ pub struct SyncPlanEntry {
     pub path: String,
     pub action: SyncAction,
     pub is_dir: bool,
+    pub entry: Option<FileEntry>, // Source-side entry for copies (None for deletes/conflicts)
+}
+
+impl SyncPlanEntry {
+    /// Path for display; renames show `from → to`.
+    pub fn display_path(&self) -> String {
+        match &self.action {
+            SyncAction::RenameOnSsd { from } | SyncAction::RenameOnLocal { from } if self.is_dir => {
+                format!("{}/ → {}/", from, self.path)
+            }
+            SyncAction::RenameOnSsd { from } | SyncAction::RenameOnLocal { from } => {
+                format!("{} → {}", from, self.path)
+            }
+            _ => self.path.clone(),
+        }
+    }
+
+    /// Size and mtime of the file an action is about, from the scanned snapshots: the source
+    /// of a copy or rename, the file to delete, or both sides of a conflict. None for folders.
+    pub fn file_details(&self, local: &Snapshot, ssd: &Snapshot) -> Option<String> {
+        if self.is_dir {
+            return None;
+        }
+        let local = local.files.get(&self.path).filter(|e| !e.is_dir);
+        let ssd = ssd.files.get(&self.path).filter(|e| !e.is_dir);
+        let entry = match &self.action {
+            SyncAction::Conflict(_) => {
+                let describe = |e: Option<&FileEntry>| e.map_or("deleted".to_string(), size_and_mtime);
+                return Some(format!("local {} | SSD {}", describe(local), describe(ssd)));
+            }
+            SyncAction::CopyToSsd | SyncAction::DeleteFromLocal | SyncAction::RenameOnSsd { .. } => local,
+            SyncAction::CopyToLocal | SyncAction::DeleteFromSsd | SyncAction::RenameOnLocal { .. } => ssd,
+        };
+        entry.map(size_and_mtime)
+    }
+
+    /// One line of `diff --output`: `<action>\t<path>` (folders end in `/`), then
+    /// `from=<path>` for renames or `local=<change>\tssd=<change>` for conflicts.
+    /// 路径中的制表符、换行和反斜杠会被转义，每行都能按制表符拆回字段。
+    pub fn to_line(&self) -> String {
+        let path = |p: &str| {
+            let escaped = p.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n");
+            if self.is_dir { format!("{}/", escaped) } else { escaped }
+        };
+        let mut line = format!("{}\t{}", self.action.kind(), path(&self.path));
+        match &self.action {
+            SyncAction::RenameOnSsd { from } | SyncAction::RenameOnLocal { from } => {
+                line.push_str(&format!("\tfrom={}", path(from)));
+            }
+            SyncAction::Conflict(info) => {
+                line.push_str(&format!(
+                    "\tlocal={}\tssd={}",
+                    info.local_change.as_str(),
+                    info.ssd_change.as_str()
+                ));
+            }
+            _ => {}
+        }
+        line
+    }
+}
+
+/// Number of planned actions of each kind (`status`).
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanCounts {
+    pub copy_to_ssd: usize,
+    pub copy_to_local: usize,
+    pub del_ssd: usize,
+    pub del_local: usize,
+    pub renames: usize,
+    pub conflicts: usize,
 }
 
 #[allow(dead_code)]
 impl SyncPlan {
+    pub fn counts(&self) -> PlanCounts {
+        let mut counts = PlanCounts::default();
+        for entry in &self.actions {
+            let counter = match &entry.action {
+                SyncAction::CopyToSsd => &mut counts.copy_to_ssd,
+                SyncAction::CopyToLocal => &mut counts.copy_to_local,
+                SyncAction::DeleteFromSsd => &mut counts.del_ssd,
+                SyncAction::DeleteFromLocal => &mut counts.del_local,
+                SyncAction::RenameOnSsd { .. } | SyncAction::RenameOnLocal { .. } => &mut counts.renames,
+                SyncAction::Conflict(_) => &mut counts.conflicts,
+            };
+            *counter += 1;
+        }
+        counts
+    }
+
+    /// Total size of the files the plan copies (either way), from their snapshot entries.
+    /// Conflicts are left out: how much they copy depends on the strategy.
+    pub fn copy_bytes(&self) -> u64 {
+        self.bytes_copied_if(|a| matches!(a, SyncAction::CopyToSsd | SyncAction::CopyToLocal))
+    }
+
+    /// Like [`SyncPlan::copy_bytes`], only the copies to the SSD.
+    pub fn ssd_copy_bytes(&self) -> u64 {
+        self.bytes_copied_if(|a| matches!(a, SyncAction::CopyToSsd))
+    }
+
+    fn bytes_copied_if(&self, copy: impl Fn(&SyncAction) -> bool) -> u64 {
+        self.actions
+            .iter()
+            .filter(|a| !a.is_dir && copy(&a.action))
+            .filter_map(|a| a.entry.as_ref())
+            .map(|e| e.size)
+            .sum()
+    }
+
     pub fn has_conflicts(&self) -> bool {
         self.actions
             .iter()
@@ -62,12 +311,76 @@ impl SyncPlan {
     pub fn non_conflict_count(&self) -> usize {
         self.actions.len() - self.conflict_count()
     }
+
+    /// The actions in the order they are carried out: folders are created first (parents before
+    /// children), then renames and conflicts, file copies, file deletes, and last folder deletes
+    /// (children before parents), so a folder exists before anything goes into it and is only
+    /// removed once it has been emptied. Actions of the same kind keep their path order.
+    pub fn ordered(&self) -> Vec<&SyncPlanEntry> {
+        let mut actions: Vec<&SyncPlanEntry> = self.actions.iter().collect();
+        actions.sort_by_key(|entry| execution_rank(entry));
+        actions
+    }
+
+    /// The whole plan for `diff --output`, one `SyncPlanEntry::to_line` per action.
+    pub fn to_text(&self) -> String {
+        self.actions.iter().map(|entry| entry.to_line() + "\n").collect()
+    }
+
+    /// Drop the entries `keep` rejects and return the paths they would have touched
+    /// (rename sources included), so the caller can leave those paths unsynced.
+    pub fn defer_unless(&mut self, keep: impl Fn(&SyncPlanEntry) -> bool) -> Vec<String> {
+        let mut deferred = vec![];
+        self.actions.retain(|entry| {
+            if keep(entry) {
+                return true;
+            }
+            if let SyncAction::RenameOnSsd { from } | SyncAction::RenameOnLocal { from } = &entry.action {
+                deferred.push(from.clone());
+            }
+            deferred.push(entry.path.clone());
+            false
+        });
+        deferred
+    }
+}
+
+/// Sort key of [`SyncPlan::ordered`].
+fn execution_rank(entry: &SyncPlanEntry) -> (u8, isize) {
+    let depth = entry.path.matches('/').count() as isize;
+    match (&entry.action, entry.is_dir) {
+        (SyncAction::CopyToSsd | SyncAction::CopyToLocal, true) => (0, depth),
+        (SyncAction::RenameOnSsd { .. } | SyncAction::RenameOnLocal { .. } | SyncAction::Conflict(_), _) => (1, 0),
+        (SyncAction::CopyToSsd | SyncAction::CopyToLocal, false) => (2, 0),
+        (SyncAction::DeleteFromSsd | SyncAction::DeleteFromLocal, false) => (3, 0),
+        (SyncAction::DeleteFromSsd | SyncAction::DeleteFromLocal, true) => (4, -depth),
+    }
+}
+
+/// Whether `[filters] empty_dirs` lets `entry` run. Only empty-directory entries are affected
+/// (folder renames always run); a rejected entry is left out and the base keeps its old state,
+/// so the same action is dropped again next time instead of being undone.
+pub fn empty_dir_allowed(policy: EmptyDirPolicy, entry: &SyncPlanEntry) -> bool {
+    if !entry.is_dir {
+        return true;
+    }
+    match &entry.action {
+        SyncAction::RenameOnSsd { .. } | SyncAction::RenameOnLocal { .. } | SyncAction::Conflict(_) => true,
+        // local-only：不往 SSD 建空目录，也不因 SSD 上删除而删掉本地的空目录；ssd-only 反之
+        SyncAction::CopyToSsd | SyncAction::DeleteFromLocal => {
+            matches!(policy, EmptyDirPolicy::Both | EmptyDirPolicy::SsdOnly)
+        }
+        SyncAction::CopyToLocal | SyncAction::DeleteFromSsd => {
+            matches!(policy, EmptyDirPolicy::Both | EmptyDirPolicy::LocalOnly)
+        }
+    }
 }
 
 /// Compute changeset between a base snapshot and a current snapshot.
 pub fn compute_changes(
     base: &Snapshot,
     current: &Snapshot,
+    detection: ChangeDetection,
 ) -> Vec<FileChange> {
     let mut changes = Vec::new();
 
@@ -99,7 +412,7 @@ pub fn compute_changes(
                 });
             }
             (Some(base_entry), Some(cur_entry)) => {
-                if base_entry.hash != cur_entry.hash {
+                if is_modified(base_entry, cur_entry, detection) {
                     changes.push(FileChange {
                         path: path.clone(),
                         change_type: ChangeType::Modified,
@@ -115,6 +428,322 @@ pub fn compute_changes(
     changes
 }
 
+/// Files that look unchanged on both sides only because the base was hashed with another
+/// algorithm, yet differ between the sides, as a modification on each side.
+/// 无法判断是哪一侧改的（保留 mtime 的编辑）：按冲突处理，否则新基准记下本地哈希后，
+/// 下次同步会用 SSD 上的旧内容覆盖本地的修改。
+fn hidden_by_algorithm_switch(
+    base: &Snapshot,
+    local: &Snapshot,
+    ssd: &Snapshot,
+    local_changes: &[FileChange],
+    ssd_changes: &[FileChange],
+) -> Vec<(FileChange, FileChange)> {
+    let changed: BTreeSet<&str> = local_changes.iter().chain(ssd_changes).map(|c| c.path.as_str()).collect();
+    let modified = |path: &str, entry: &FileEntry| FileChange {
+        path: path.to_string(),
+        change_type: ChangeType::Modified,
+        entry: Some(entry.clone()),
+        is_dir: false,
+    };
+    base.files
+        .iter()
+        .filter(|(path, _)| !changed.contains(path.as_str()))
+        .filter_map(|(path, base_entry)| {
+            let (l, s) = (local.files.get(path)?, ssd.files.get(path)?);
+            let hidden = !l.is_dir
+                && mixed_algorithms(&base_entry.hash, &l.hash)
+                && HashAlgorithm::of(&l.hash) == HashAlgorithm::of(&s.hash)
+                && l.hash != s.hash;
+            hidden.then(|| (modified(path, l), modified(path, s)))
+        })
+        .collect()
+}
+
+fn mixed_algorithms(a: &str, b: &str) -> bool {
+    matches!((HashAlgorithm::of(a), HashAlgorithm::of(b)), (Some(x), Some(y)) if x != y)
+}
+
+fn is_modified(base: &FileEntry, current: &FileEntry, detection: ChangeDetection) -> bool {
+    match detection {
+        // 切换 [scan] hash_algorithm 后的第一次同步：不同算法的哈希无法比较，改看 size/mtime
+        ChangeDetection::Hash if mixed_algorithms(&base.hash, &current.hash) => {
+            base.size != current.size || base.mtime_secs != current.mtime_secs
+        }
+        ChangeDetection::Hash => base.hash != current.hash,
+        ChangeDetection::SizeMtime => {
+            base.size != current.size || base.mtime_secs != current.mtime_secs
+        }
+    }
+}
+
+/// Replace base size/mtime with the values recorded in `cache` where the content matches.
+/// 基准快照保存的是本地 mtime，SSD 副本的 mtime 与之不同；
+/// size-mtime 模式下与 SSD 扫描比较前需要换成 SSD 缓存中的值。
+pub fn rebase_stats(base: &Snapshot, cache: &Snapshot) -> Snapshot {
+    let mut rebased = base.clone();
+    for (path, entry) in rebased.files.iter_mut() {
+        if let Some(cached) = cache.files.get(path) {
+            if cached.hash == entry.hash {
+                entry.size = cached.size;
+                entry.mtime_secs = cached.mtime_secs;
+            }
+        }
+    }
+    rebased
+}
+
+/// Compute the sync plan for one mapping from its base snapshot, SSD cache and fresh scans.
+pub fn plan_changes(
+    base: &Snapshot,
+    ssd_cache: &Snapshot,
+    local: &Snapshot,
+    ssd: &Snapshot,
+    detection: ChangeDetection,
+) -> (SyncPlan, usize, usize) {
+    let mut local_changes = compute_changes(base, local, detection);
+    let mut ssd_changes = match detection {
+        ChangeDetection::Hash => compute_changes(base, ssd, detection),
+        ChangeDetection::SizeMtime => compute_changes(&rebase_stats(base, ssd_cache), ssd, detection),
+    };
+    if detection == ChangeDetection::Hash {
+        for (local_change, ssd_change) in hidden_by_algorithm_switch(base, local, ssd, &local_changes, &ssd_changes) {
+            local_changes.push(local_change);
+            ssd_changes.push(ssd_change);
+        }
+    }
+    let mut plan = coalesce_file_renames(
+        coalesce_dir_renames(build_sync_plan(&local_changes, &ssd_changes), base), base);
+    // 冲突附带上次同步时的条目（共同祖先），便于对比两侧各自改了什么
+    for entry in &mut plan.actions {
+        if let SyncAction::Conflict(info) = &mut entry.action {
+            info.base = base.files.get(&entry.path).cloned();
+        }
+    }
+    (plan, local_changes.len(), ssd_changes.len())
+}
+
+/// All ancestor directories of `path` ("a/b/c.txt" → "a", "a/b").
+fn ancestors(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('/').map(move |(i, _)| &path[..i])
+}
+
+/// Entries under `dir/`, keyed by their path relative to `dir`.
+fn subtree<'a, I>(dir: &str, entries: I) -> BTreeMap<String, String>
+where
+    I: Iterator<Item = (&'a String, &'a FileEntry)>,
+{
+    let prefix = format!("{}/", dir);
+    entries
+        .filter_map(|(path, entry)| {
+            path.strip_prefix(&prefix)
+                .map(|rest| (rest.to_string(), entry.hash.clone()))
+        })
+        .collect()
+}
+
+/// Replace "delete every file under `old/`" + "copy identical files under `new/`" with a single
+/// directory rename, so a renamed folder is moved instead of re-copied.
+/// 只有整个子树（所有文件及哈希）完全对应时才合并；部分移动仍按单文件处理。
+pub fn coalesce_dir_renames(plan: SyncPlan, base: &Snapshot) -> SyncPlan {
+    let mut actions = plan.actions;
+    for (delete, copy, to_ssd) in [
+        (SyncAction::DeleteFromSsd, SyncAction::CopyToSsd, true),
+        (SyncAction::DeleteFromLocal, SyncAction::CopyToLocal, false),
+    ] {
+        let deleted: BTreeSet<&str> = actions
+            .iter()
+            .filter(|a| a.action == delete)
+            .map(|a| a.path.as_str())
+            .collect();
+        let copied: BTreeMap<&String, &FileEntry> = actions
+            .iter()
+            .filter(|a| a.action == copy)
+            .filter_map(|a| a.entry.as_ref().map(|e| (&a.path, e)))
+            .collect();
+        if deleted.is_empty() || copied.is_empty() {
+            continue;
+        }
+
+        // 新目录：基准中不存在，且其下所有计划条目都是复制
+        let other_under = |dir: &str| {
+            let prefix = format!("{}/", dir);
+            actions
+                .iter()
+                .any(|a| a.path.starts_with(&prefix) && a.action != copy)
+        };
+        let mut new_dirs: BTreeMap<BTreeMap<String, String>, &str> = BTreeMap::new();
+        let new_candidates: BTreeSet<&str> = copied.keys().flat_map(|p| ancestors(p)).collect();
+        for dir in new_candidates {
+            if base.files.contains_key(dir) || has_entries_under(dir, &base.files) || other_under(dir) {
+                continue;
+            }
+            let files = subtree(dir, copied.iter().map(|(p, e)| (*p, *e)));
+            if !files.is_empty() {
+                new_dirs.entry(files).or_insert(dir);
+            }
+        }
+
+        // 旧目录：基准中该子树的所有条目都被删除
+        let old_candidates: BTreeSet<&str> = deleted.iter().flat_map(|p| ancestors(p)).collect();
+        let mut renames: Vec<(String, String)> = Vec::new();
+        // 按深度从浅到深处理，外层目录匹配后跳过其内部目录
+        let mut old_sorted: Vec<&str> = old_candidates.into_iter().collect();
+        old_sorted.sort_by_key(|d| d.matches('/').count());
+        for old in old_sorted {
+            if renames.iter().any(|(o, _)| old.starts_with(&format!("{}/", o))) {
+                continue;
+            }
+            let files = subtree(old, base.files.iter());
+            if files.is_empty() || !files.keys().all(|rel| deleted.contains(format!("{}/{}", old, rel).as_str())) {
+                continue;
+            }
+            if let Some(new) = new_dirs.remove(&files) {
+                let nested = |a: &str, b: &str| a.starts_with(&format!("{}/", b));
+                if nested(new, old) || nested(old, new) || renames.iter().any(|(_, n)| nested(new, n)) {
+                    continue;
+                }
+                renames.push((old.to_string(), new.to_string()));
+            }
+        }
+
+        for (old, new) in renames {
+            let (old_prefix, new_prefix) = (format!("{}/", old), format!("{}/", new));
+            actions.retain(|a| {
+                !((a.action == delete && a.path.starts_with(&old_prefix))
+                    || (a.action == copy && a.path.starts_with(&new_prefix)))
+            });
+            let action = if to_ssd {
+                SyncAction::RenameOnSsd { from: old }
+            } else {
+                SyncAction::RenameOnLocal { from: old }
+            };
+            actions.push(SyncPlanEntry {
+                path: new,
+                action,
+                is_dir: true,
+                entry: None,
+            });
+        }
+    }
+    actions.sort_by(|a, b| a.path.cmp(&b.path));
+    SyncPlan { actions }
+}
+
+/// Turn a file deleted on one side plus a new file with the same content (hash and size)
+/// into a rename on the other side, so the content is not copied again. Runs after
+/// `coalesce_dir_renames`; files moved with their whole folder are already covered.
+pub fn coalesce_file_renames(plan: SyncPlan, base: &Snapshot) -> SyncPlan {
+    let mut actions = plan.actions;
+    for (delete, copy, to_ssd) in [
+        (SyncAction::DeleteFromSsd, SyncAction::CopyToSsd, true),
+        (SyncAction::DeleteFromLocal, SyncAction::CopyToLocal, false),
+    ] {
+        let mut deleted: BTreeMap<(&str, u64), Vec<&str>> = BTreeMap::new();
+        let mut added: BTreeMap<(&str, u64), Vec<&str>> = BTreeMap::new();
+        for a in actions.iter().filter(|a| !a.is_dir) {
+            if a.action == delete {
+                if let Some(old) = base.files.get(&a.path) {
+                    deleted.entry((&old.hash, old.size)).or_default().push(&a.path);
+                }
+            } else if a.action == copy && !base.files.contains_key(&a.path) {
+                if let Some(new) = &a.entry {
+                    added.entry((&new.hash, new.size)).or_default().push(&a.path);
+                }
+            }
+        }
+        // 内容相同的多个文件按路径顺序两两配对，任意配对都能省去复制
+        let renames: Vec<(String, String)> = deleted
+            .iter()
+            .filter_map(|(key, olds)| added.get(key).map(|news| (olds, news)))
+            .flat_map(|(olds, news)| olds.iter().zip(news.iter()))
+            .map(|(old, new)| (old.to_string(), new.to_string()))
+            .collect();
+        if renames.is_empty() {
+            continue;
+        }
+
+        let olds: BTreeSet<&str> = renames.iter().map(|(old, _)| old.as_str()).collect();
+        let news: BTreeSet<&str> = renames.iter().map(|(_, new)| new.as_str()).collect();
+        actions.retain(|a| {
+            !((a.action == delete && olds.contains(a.path.as_str()))
+                || (a.action == copy && news.contains(a.path.as_str())))
+        });
+        for (old, new) in renames {
+            let action = if to_ssd {
+                SyncAction::RenameOnSsd { from: old }
+            } else {
+                SyncAction::RenameOnLocal { from: old }
+            };
+            actions.push(SyncPlanEntry {
+                path: new,
+                action,
+                is_dir: false,
+                entry: None,
+            });
+        }
+    }
+    actions.sort_by(|a, b| a.path.cmp(&b.path));
+    SyncPlan { actions }
+}
+
+/// Whether `files` has anything below `dir`.
+fn has_entries_under(dir: &str, files: &BTreeMap<String, FileEntry>) -> bool {
+    let prefix = format!("{}/", dir);
+    files
+        .range(prefix.clone()..)
+        .next()
+        .is_some_and(|(p, _)| p.starts_with(&prefix))
+}
+
+/// How much two never-synced folders have in common.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overlap {
+    pub local_files: usize,
+    pub ssd_files: usize,
+    /// Files on the smaller side that also exist on the other side (same path or same content)
+    pub matching: usize,
+}
+
+impl Overlap {
+    pub fn percent(&self) -> f64 {
+        let smaller = self.local_files.min(self.ssd_files);
+        if smaller == 0 {
+            100.0
+        } else {
+            self.matching as f64 * 100.0 / smaller as f64
+        }
+    }
+}
+
+/// Compare the two sides of a first sync. 以文件较少的一侧为基准统计重合度，
+/// 这样 SSD 上只有部分文件（正常情况）时不会被误判为不相关的数据。
+pub fn first_sync_overlap(local: &Snapshot, ssd: &Snapshot) -> Overlap {
+    let files = |snap: &'_ Snapshot| -> BTreeMap<String, String> {
+        snap.files
+            .iter()
+            .filter(|(_, e)| !e.is_dir)
+            .map(|(p, e)| (p.clone(), e.hash.clone()))
+            .collect()
+    };
+    let (local_files, ssd_files) = (files(local), files(ssd));
+    let (smaller, larger) = if local_files.len() <= ssd_files.len() {
+        (&local_files, &ssd_files)
+    } else {
+        (&ssd_files, &local_files)
+    };
+    let larger_hashes: BTreeSet<&String> = larger.values().collect();
+    let matching = smaller
+        .iter()
+        .filter(|(path, hash)| larger.contains_key(*path) || larger_hashes.contains(hash))
+        .count();
+    Overlap {
+        local_files: local_files.len(),
+        ssd_files: ssd_files.len(),
+        matching,
+    }
+}
+
 /// Build a sync plan by merging local and SSD changesets.
 pub fn build_sync_plan(
     local_changes: &[FileChange],
@@ -169,12 +798,20 @@ pub fn build_sync_plan(
                         _ => SyncAction::Conflict(ConflictInfo {
                             local_change: lc.change_type.clone(),
                             ssd_change: sc.change_type.clone(),
+                            versions: None,
+                            base: None,
+                            local: lc.entry.clone(),
+                            ssd: sc.entry.clone(),
                         }),
                     }
                 } else {
                     SyncAction::Conflict(ConflictInfo {
                         local_change: lc.change_type.clone(),
                         ssd_change: sc.change_type.clone(),
+                        versions: None,
+                        base: None,
+                        local: lc.entry.clone(),
+                        ssd: sc.entry.clone(),
                     })
                 }
             }
@@ -187,10 +824,17 @@ pub fn build_sync_plan(
             .or_else(|| ssd_change.map(|c| c.is_dir))
             .unwrap_or(false);
 
+        let entry = match &action {
+            SyncAction::CopyToSsd => local_change.and_then(|c| c.entry.clone()),
+            SyncAction::CopyToLocal => ssd_change.and_then(|c| c.entry.clone()),
+            _ => None,
+        };
+
         actions.push(SyncPlanEntry {
             path: path.to_string(),
             action,
             is_dir,
+            entry,
         });
     }
 
@@ -208,6 +852,8 @@ mod tests {
             mtime_secs: 1000,
             hash: hash.to_string(),
             is_dir: false,
+            version: 0,
+            is_symlink: false,
         }
     }
 
@@ -219,11 +865,46 @@ mod tests {
             .files
             .insert("new.txt".to_string(), make_entry("hash1"));
 
-        let changes = compute_changes(&base, &current);
+        let changes = compute_changes(&base, &current, ChangeDetection::Hash);
         assert_eq!(changes.len(), 1);
         assert_eq!(changes[0].change_type, ChangeType::Added);
     }
 
+    #[test]
+    fn test_mixed_hash_algorithms_compare_stats() {
+        let mut base = Snapshot::new("test", "mac");
+        base.files.insert("same.txt".to_string(), make_entry("blake3:aaaa"));
+        base.files.insert("edited.txt".to_string(), make_entry("blake3:bbbb"));
+        base.files.insert("link".to_string(), make_entry("blake3:cccc"));
+        let mut current = Snapshot::new("test", "mac");
+        current.files.insert("same.txt".to_string(), make_entry("xxh3:1111"));
+        current.files.insert("edited.txt".to_string(), FileEntry { size: 101, ..make_entry("xxh3:2222") });
+        current.files.insert("link".to_string(), make_entry("symlink:target"));
+
+        // 换算法后哈希必然不同：只在 size/mtime 也变了时才算修改
+        let changes = compute_changes(&base, &current, ChangeDetection::Hash);
+        let paths: Vec<&str> = changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec!["edited.txt", "link"]);
+    }
+
+    #[test]
+    fn test_algorithm_switch_keeps_hidden_edits_as_conflicts() {
+        let mut base = Snapshot::new("test", "mac");
+        base.files.insert("same.txt".to_string(), make_entry("blake3:aaaa"));
+        base.files.insert("edited.txt".to_string(), make_entry("blake3:bbbb"));
+        let (mut local, mut ssd) = (Snapshot::new("test", "mac"), Snapshot::new("test", "mac"));
+        local.files.insert("same.txt".to_string(), make_entry("xxh3:1111"));
+        ssd.files.insert("same.txt".to_string(), make_entry("xxh3:1111"));
+        // 保留 size/mtime 的编辑：按 size/mtime 两侧都"未变"，但新哈希不同
+        local.files.insert("edited.txt".to_string(), make_entry("xxh3:2222"));
+        ssd.files.insert("edited.txt".to_string(), make_entry("xxh3:3333"));
+
+        let (plan, _, _) = plan_changes(&base, &ssd, &local, &ssd, ChangeDetection::Hash);
+        assert_eq!(plan.actions.len(), 1, "{:?}", plan.actions);
+        assert_eq!(plan.actions[0].path, "edited.txt");
+        assert!(matches!(plan.actions[0].action, SyncAction::Conflict(_)));
+    }
+
     #[test]
     fn test_compute_changes_deleted() {
         let mut base = Snapshot::new("test", "mac");
@@ -231,7 +912,7 @@ mod tests {
             .insert("old.txt".to_string(), make_entry("hash1"));
         let current = Snapshot::new("test", "mac");
 
-        let changes = compute_changes(&base, &current);
+        let changes = compute_changes(&base, &current, ChangeDetection::Hash);
         assert_eq!(changes.len(), 1);
         assert_eq!(changes[0].change_type, ChangeType::Deleted);
     }
@@ -246,11 +927,109 @@ mod tests {
             .files
             .insert("file.txt".to_string(), make_entry("hash2"));
 
-        let changes = compute_changes(&base, &current);
+        let changes = compute_changes(&base, &current, ChangeDetection::Hash);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change_type, ChangeType::Modified);
+    }
+
+    #[test]
+    fn test_compute_changes_size_mtime() {
+        let mut base = Snapshot::new("test", "mac");
+        base.files.insert("same.txt".to_string(), make_entry("hash1"));
+        base.files.insert("touched.txt".to_string(), make_entry("hash1"));
+        let mut current = Snapshot::new("test", "mac");
+        // 内容不同但 size/mtime 相同：size-mtime 模式下视为未修改
+        current.files.insert("same.txt".to_string(), make_entry("hash2"));
+        let mut touched = make_entry("hash1");
+        touched.mtime_secs = 2000;
+        current.files.insert("touched.txt".to_string(), touched);
+
+        let changes = compute_changes(&base, &current, ChangeDetection::SizeMtime);
         assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "touched.txt");
         assert_eq!(changes[0].change_type, ChangeType::Modified);
     }
 
+    #[test]
+    fn test_plan_changes_size_mtime_uses_ssd_stats() {
+        let mut base = Snapshot::new("test", "mac");
+        base.files.insert("file.txt".to_string(), make_entry("hash1"));
+        // SSD 副本内容相同但 mtime 不同
+        let mut ssd_entry = make_entry("hash1");
+        ssd_entry.mtime_secs = 5000;
+        let mut ssd = Snapshot::new("test", "mac");
+        ssd.files.insert("file.txt".to_string(), ssd_entry);
+        let ssd_cache = ssd.clone();
+
+        let (plan, _, ssd_count) =
+            plan_changes(&base, &ssd_cache, &base, &ssd, ChangeDetection::SizeMtime);
+        assert_eq!(ssd_count, 0);
+        assert!(plan.actions.is_empty());
+    }
+
+    fn snapshot(files: &[(&str, &str)]) -> Snapshot {
+        let mut snap = Snapshot::new("test", "mac");
+        for (path, hash) in files {
+            snap.files.insert(path.to_string(), make_entry(hash));
+        }
+        snap
+    }
+
+    #[test]
+    fn test_dir_rename_coalesced() {
+        let base = snapshot(&[("old/a.txt", "h1"), ("old/sub/b.txt", "h2"), ("keep.txt", "h3")]);
+        let local = snapshot(&[("new/a.txt", "h1"), ("new/sub/b.txt", "h2"), ("keep.txt", "h3")]);
+
+        let (plan, _, _) = plan_changes(&base, &base, &local, &base, ChangeDetection::Hash);
+        assert_eq!(plan.actions.len(), 1);
+        assert_eq!(plan.actions[0].path, "new");
+        assert_eq!(
+            plan.actions[0].action,
+            SyncAction::RenameOnSsd { from: "old".to_string() }
+        );
+
+        // SSD 侧重命名 → 本地执行重命名
+        let (plan, _, _) = plan_changes(&base, &base, &base, &local, ChangeDetection::Hash);
+        assert_eq!(
+            plan.actions[0].action,
+            SyncAction::RenameOnLocal { from: "old".to_string() }
+        );
+    }
+
+    #[test]
+    fn test_file_rename_coalesced() {
+        let base = snapshot(&[("foo.txt", "h1"), ("keep.txt", "h2"), ("old.txt", "h3")]);
+        let local = snapshot(&[("bar.txt", "h1"), ("keep.txt", "h2"), ("new.txt", "h4")]);
+
+        let (plan, _, _) = plan_changes(&base, &base, &local, &base, ChangeDetection::Hash);
+        let paths: Vec<String> = plan.actions.iter().map(|e| e.display_path()).collect();
+        // 内容不同的新文件仍按删除 + 复制处理
+        assert_eq!(paths, vec!["foo.txt → bar.txt", "new.txt", "old.txt"]);
+        assert_eq!(plan.actions[0].action, SyncAction::RenameOnSsd { from: "foo.txt".to_string() });
+        assert!(!plan.actions[0].is_dir);
+
+        // SSD 上改名 → 本地改名
+        let (plan, _, _) = plan_changes(&base, &local, &base, &local, ChangeDetection::Hash);
+        assert_eq!(plan.actions[0].action, SyncAction::RenameOnLocal { from: "foo.txt".to_string() });
+    }
+
+    #[test]
+    fn test_partial_move_not_coalesced() {
+        // 只移动了部分文件：old/ 下仍有文件保留
+        let base = snapshot(&[("old/a.txt", "h1"), ("old/b.txt", "h2")]);
+        let local = snapshot(&[("new/a.txt", "h1"), ("old/b.txt", "h2")]);
+        // 不合并为目录改名，移动的单个文件按文件改名处理
+        let (plan, _, _) = plan_changes(&base, &base, &local, &base, ChangeDetection::Hash);
+        let paths: Vec<String> = plan.actions.iter().map(|e| e.display_path()).collect();
+        assert_eq!(paths, vec!["old/a.txt → new/a.txt"]);
+
+        // 内容有变化：目录不合并，b.txt 仍是删除 + 复制
+        let local = snapshot(&[("new/a.txt", "h1"), ("new/b.txt", "changed")]);
+        let (plan, _, _) = plan_changes(&base, &base, &local, &base, ChangeDetection::Hash);
+        let paths: Vec<String> = plan.actions.iter().map(|e| e.display_path()).collect();
+        assert_eq!(paths, vec!["old/a.txt → new/a.txt", "new/b.txt", "old/b.txt"]);
+    }
+
     #[test]
     fn test_sync_plan_local_add() {
         let local_changes = vec![FileChange {
@@ -282,4 +1061,220 @@ mod tests {
         assert_eq!(plan.actions.len(), 1);
         assert!(matches!(plan.actions[0].action, SyncAction::Conflict(_)));
     }
+
+    #[test]
+    fn test_plan_counts_json() {
+        let base = snapshot(&[("kept.txt", "h1"), ("gone.txt", "h2"), ("both.txt", "h3")]);
+        let local = snapshot(&[("kept.txt", "h1"), ("new.txt", "h4"), ("both.txt", "h3-local")]);
+        let ssd = snapshot(&[("kept.txt", "h1"), ("gone.txt", "h2"), ("both.txt", "h3-ssd")]);
+        let (plan, _, _) = plan_changes(&base, &ssd, &local, &ssd, ChangeDetection::Hash);
+
+        let json = serde_json::to_string(&plan.counts()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["copy_to_ssd"], 1);
+        assert_eq!(value["del_ssd"], 1);
+        assert_eq!(value["conflicts"], 1);
+        let parsed: PlanCounts = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, PlanCounts { copy_to_ssd: 1, del_ssd: 1, conflicts: 1, ..Default::default() });
+    }
+
+    #[test]
+    fn test_plan_copy_bytes() {
+        let base = snapshot(&[("kept.txt", "h1"), ("gone.txt", "h2"), ("both.txt", "h3")]);
+        let mut local = snapshot(&[("kept.txt", "h1"), ("new.txt", "h4"), ("both.txt", "h3-local")]);
+        local.files.insert("assets".to_string(), FileEntry { size: 0, is_dir: true, ..make_entry("") });
+        let mut ssd = snapshot(&[("kept.txt", "h1"), ("gone.txt", "h2"), ("both.txt", "h3-ssd")]);
+        ssd.files.insert("video.mov".to_string(), FileEntry { size: 5_000_000_000, ..make_entry("h5") });
+        let (plan, _, _) = plan_changes(&base, &ssd, &local, &ssd, ChangeDetection::Hash);
+
+        // new.txt → SSD，video.mov → 本地；删除、目录和冲突不计入
+        let copies: u64 = plan
+            .actions
+            .iter()
+            .filter(|a| matches!(a.action, SyncAction::CopyToSsd | SyncAction::CopyToLocal) && !a.is_dir)
+            .map(|a| a.entry.as_ref().unwrap().size)
+            .sum();
+        assert_eq!(copies, 5_000_000_100);
+        assert_eq!(plan.copy_bytes(), copies);
+        assert_eq!(plan.ssd_copy_bytes(), 100);
+        assert_eq!(SyncPlan { actions: vec![] }.copy_bytes(), 0);
+    }
+
+    #[test]
+    fn test_plan_ordered() {
+        let entry = |path: &str, action: SyncAction, is_dir: bool| SyncPlanEntry {
+            path: path.to_string(),
+            action,
+            is_dir,
+            entry: None,
+        };
+        let plan = SyncPlan {
+            actions: vec![
+                entry("a", SyncAction::DeleteFromSsd, true),
+                entry("a/b", SyncAction::DeleteFromSsd, true),
+                entry("a/x.txt", SyncAction::DeleteFromSsd, false),
+                entry("docs", SyncAction::CopyToLocal, true),
+                entry("docs/img", SyncAction::CopyToLocal, true),
+                entry("docs/img/p.png", SyncAction::CopyToLocal, false),
+                entry("new", SyncAction::RenameOnSsd { from: "old".to_string() }, true),
+                entry("out", SyncAction::CopyToSsd, true),
+                entry("out/r.txt", SyncAction::CopyToSsd, false),
+                entry("z.txt", SyncAction::DeleteFromLocal, false),
+            ],
+        };
+        let order: Vec<&str> = plan.ordered().iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            order,
+            vec![
+                // 先建目录（由浅到深），再改名、复制文件、删除文件，最后删除目录（由深到浅）
+                "docs", "out", "docs/img",
+                "new",
+                "docs/img/p.png", "out/r.txt",
+                "a/x.txt", "z.txt",
+                "a/b", "a",
+            ]
+        );
+        // 计划本身仍按路径排序（diff 等的显示顺序不变）
+        assert_eq!(plan.actions[0].path, "a");
+    }
+
+    #[test]
+    fn test_diff_file_details() {
+        let base = snapshot(&[("report.doc", "h1"), ("notes.txt", "h2")]);
+        let mut local = snapshot(&[("report.doc", "h1-local"), ("notes.txt", "h2-local")]);
+        local.files.get_mut("report.doc").unwrap().size = 2048;
+        let ssd = snapshot(&[("report.doc", "h1"), ("notes.txt", "h2-ssd")]);
+        let (plan, _, _) = plan_changes(&base, &ssd, &local, &ssd, ChangeDetection::Hash);
+        let details = |path: &str| {
+            let entry = plan.actions.iter().find(|a| a.path == path).unwrap();
+            entry.file_details(&local, &ssd).unwrap()
+        };
+        let mtime = chrono::DateTime::from_timestamp(1000, 0)
+            .unwrap()
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string();
+
+        // 本地修改的文件：显示要复制过去的本地文件
+        assert_eq!(details("report.doc"), format!("2.0 KiB, {}", mtime));
+        // 冲突：两侧并列
+        assert_eq!(details("notes.txt"), format!("local 100 B, {} | SSD 100 B, {}", mtime, mtime));
+    }
+
+    #[test]
+    fn test_plan_text_export() {
+        let base = snapshot(&[("kept.txt", "h1"), ("gone.txt", "h2"), ("both.txt", "h3"), ("old/x.txt", "h5")]);
+        let local = snapshot(&[("kept.txt", "h1"), ("tab\there.txt", "h4"), ("both.txt", "h3-local"), ("new/x.txt", "h5")]);
+        let ssd = snapshot(&[("kept.txt", "h1"), ("gone.txt", "h2"), ("both.txt", "h3-ssd"), ("old/x.txt", "h5")]);
+        let (plan, _, _) = plan_changes(&base, &ssd, &local, &ssd, ChangeDetection::Hash);
+
+        let text = plan.to_text();
+        let lines: Vec<Vec<&str>> = text.lines().map(|l| l.split('\t').collect()).collect();
+        assert_eq!(lines.len(), plan.actions.len());
+        for (fields, entry) in lines.iter().zip(&plan.actions) {
+            assert_eq!(fields[0], entry.action.kind());
+            let path = if entry.is_dir { format!("{}/", entry.path) } else { entry.path.clone() };
+            assert_eq!(fields[1].replace("\\t", "\t"), path);
+        }
+        assert!(text.contains("conflict\tboth.txt\tlocal=modified\tssd=modified\n"), "{}", text);
+        assert!(text.contains("rename-on-ssd\tnew/\tfrom=old/\n"), "{}", text);
+        assert!(text.contains("delete-from-ssd\tgone.txt\n"), "{}", text);
+        assert!(text.contains("copy-to-ssd\ttab\\there.txt\n"), "{}", text);
+        // 同样的输入得到同样的文本，可以直接 diff 两次的结果
+        assert_eq!(plan_changes(&base, &ssd, &local, &ssd, ChangeDetection::Hash).0.to_text(), text);
+    }
+
+    #[test]
+    fn test_conflict_carries_common_ancestor() {
+        let base = snapshot(&[("file.txt", "base1234xyz")]);
+        let local = snapshot(&[("file.txt", "local567xyz")]);
+        let ssd = snapshot(&[]);
+
+        let (plan, _, _) = plan_changes(&base, &ssd, &local, &ssd, ChangeDetection::Hash);
+        let SyncAction::Conflict(info) = &plan.actions[0].action else {
+            panic!("expected a conflict: {:?}", plan.actions[0].action);
+        };
+        assert_eq!(info.base.as_ref().map(|e| e.hash.as_str()), Some("base1234xyz"));
+        assert_eq!(info.local_delta(), "100 B (base1234) → 100 B (local567)");
+        assert_eq!(info.ssd_delta(), "deleted (was 100 B (base1234))");
+    }
+
+    #[test]
+    fn test_first_sync_overlap() {
+        let local = snapshot(&[("a.txt", "h1"), ("b.txt", "h2"), ("c.txt", "h3"), ("d.txt", "h4")]);
+
+        // SSD 只有本地文件的子集（同路径或同内容）：视为完全重合
+        let subset = snapshot(&[("a.txt", "other"), ("moved/b.txt", "h2")]);
+        assert_eq!(first_sync_overlap(&local, &subset).percent(), 100.0);
+
+        let unrelated = snapshot(&[("x.txt", "h8"), ("y.txt", "h9"), ("c.txt", "h3")]);
+        let overlap = first_sync_overlap(&local, &unrelated);
+        assert_eq!(overlap, Overlap { local_files: 4, ssd_files: 3, matching: 1 });
+        assert!(overlap.percent() < 34.0);
+
+        let empty = snapshot(&[]);
+        assert_eq!(first_sync_overlap(&local, &empty).percent(), 100.0);
+    }
+
+    #[test]
+    fn test_defer_unless_restores_previous_state() {
+        let base = snapshot(&[("old/a.txt", "h1"), ("b.txt", "h2"), ("c.txt", "h3")]);
+        let local = snapshot(&[("new/a.txt", "h1"), ("b.txt", "h2-local"), ("c.txt", "h3")]);
+        let ssd = snapshot(&[("old/a.txt", "h1"), ("b.txt", "h2-ssd"), ("c.txt", "h3-ssd")]);
+
+        let (mut plan, _, _) = plan_changes(&base, &ssd, &local, &ssd, ChangeDetection::Hash);
+        assert_eq!(plan.actions.len(), 3);
+        let deferred = plan.defer_unless(|e| matches!(e.action, SyncAction::Conflict(_)));
+        assert_eq!(plan.actions.len(), 1);
+        assert_eq!(plan.actions[0].path, "b.txt");
+        assert_eq!(deferred, vec!["c.txt", "old", "new"]);
+
+        // 延后的路径恢复为同步前的基准状态，下次同步会再次提出同样的变更
+        let mut new_base = snapshot(&[("new/a.txt", "h1"), ("b.txt", "h2-ssd"), ("c.txt", "h3-ssd")]);
+        new_base.restore_paths(&base, &deferred);
+        let local_after = snapshot(&[("new/a.txt", "h1"), ("b.txt", "h2-ssd"), ("c.txt", "h3")]);
+        let (next, _, _) = plan_changes(&new_base, &ssd, &local_after, &ssd, ChangeDetection::Hash);
+        let paths: Vec<String> = next.actions.iter().map(|e| e.display_path()).collect();
+        assert_eq!(paths, vec!["c.txt", "old/ → new/"]);
+    }
+
+    #[test]
+    fn test_explain_plan() {
+        let base = snapshot(&[("a.txt", "h1"), ("b.txt", "h2")]);
+        let local = snapshot(&[("a.txt", "h1-local"), ("b.txt", "h2")]);
+        let ssd = snapshot(&[("a.txt", "h1"), ("b.txt", "h2-ssd"), ("c.txt", "h3")]);
+
+        let (plan, _, _) = plan_changes(&base, &ssd, &local, &ssd, ChangeDetection::Hash);
+        let why = explain_plan(&plan, &base, &ssd, &local, &ssd, ChangeDetection::Hash);
+        let paths: Vec<&str> = plan.actions.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "b.txt", "c.txt"]);
+        assert!(why[0][0].starts_with("local: modified, 100 B (h1) → 100 B (h1-local)"), "{}", why[0][0]);
+        assert_eq!(why[0][1], "SSD:   unchanged, 100 B (h1)");
+        assert!(why[1][1].starts_with("SSD:   modified"));
+        assert_eq!(why[2], vec!["local: absent", "SSD:   added 100 B (h3)"]);
+    }
+
+    #[test]
+    fn test_empty_dir_policy() {
+        let entry = |action: SyncAction, is_dir: bool| SyncPlanEntry {
+            path: "empty".to_string(),
+            action,
+            is_dir,
+            entry: None,
+        };
+        let to_ssd = entry(SyncAction::CopyToSsd, true);
+        let to_local = entry(SyncAction::CopyToLocal, true);
+        let delete_local = entry(SyncAction::DeleteFromLocal, true);
+        let file = entry(SyncAction::CopyToSsd, false);
+
+        assert!(empty_dir_allowed(EmptyDirPolicy::Both, &to_ssd));
+        assert!(!empty_dir_allowed(EmptyDirPolicy::LocalOnly, &to_ssd));
+        assert!(empty_dir_allowed(EmptyDirPolicy::LocalOnly, &to_local));
+        // ssd-only：空目录不会建到本地，但 SSD 上删除的空目录仍会从本地删除
+        assert!(!empty_dir_allowed(EmptyDirPolicy::SsdOnly, &to_local));
+        assert!(empty_dir_allowed(EmptyDirPolicy::SsdOnly, &delete_local));
+        assert!(!empty_dir_allowed(EmptyDirPolicy::None, &to_local));
+        // 文件不受影响
+        assert!(empty_dir_allowed(EmptyDirPolicy::None, &file));
+    }
 }
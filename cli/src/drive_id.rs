@@ -0,0 +1,106 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::config::AppConfig;
+use crate::output::outln;
+
+/// File in the SSD folder's `.ssd-syncer` directory holding the folder's drive ID.
+pub fn file_path(ssd_data_root: &Path) -> PathBuf {
+    AppConfig::ssd_syncer_dir(ssd_data_root).join("drive-id")
+}
+
+/// The drive ID written into the SSD folder, if any.
+pub fn read(ssd_data_root: &Path) -> Result<Option<String>> {
+    let path = file_path(ssd_data_root);
+    match std::fs::read_to_string(&path) {
+        Ok(content) => Ok(Some(content.trim().to_string()).filter(|id| !id.is_empty())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Write `id` into the SSD folder unless it already carries it.
+pub fn write(ssd_data_root: &Path, id: &str) -> Result<()> {
+    if read(ssd_data_root)?.as_deref() == Some(id) {
+        return Ok(());
+    }
+    let path = file_path(ssd_data_root);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, format!("{}\n", id)).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Check that the SSD folder is the one the mapping was synced with (`expected` is the ID
+/// recorded for the mapping on the SSD profile of this run). 另一块 SSD 挂载在同一路径时，按不相干的快照同步可能删除文件，
+/// 因此 ID 不符时中止，除非 `force`。
+///
+/// Returns the ID to remember for the mapping: the folder's ID when the mapping has none yet,
+/// a new one when neither has one, or the folder's ID accepted with `force`.
+pub fn verify(ssd_data_root: &Path, expected: Option<&str>, force: bool) -> Result<Option<String>> {
+    let found = read(ssd_data_root)?;
+    match (expected, found) {
+        (Some(expected), Some(found)) if expected == found => Ok(None),
+        (None, found) => Ok(Some(found.unwrap_or_else(new_id))),
+        (Some(expected), found) => {
+            let found_desc = match &found {
+                Some(id) => format!("drive ID {}", id),
+                None => "no drive ID".to_string(),
+            };
+            if !force {
+                anyhow::bail!(
+                    "{} is not the drive this mapping was synced with: expected drive ID {}, found {}. \
+                     Nothing was synced. Check that the right SSD is connected, or pass --force to sync \
+                     with this drive from now on.",
+                    ssd_data_root.display(),
+                    expected,
+                    found_desc
+                );
+            }
+            outln!(
+                "  ⚠ {} has {} instead of {} (--force: syncing with this drive from now on)",
+                ssd_data_root.display(),
+                found_desc,
+                expected
+            );
+            Ok(Some(found.unwrap_or_else(new_id)))
+        }
+    }
+}
+
+fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mismatched_drive_id() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ssd = tmp.path();
+
+        // 首次同步：生成新 ID，写入后再次校验一致
+        let id = verify(ssd, None, false).unwrap().unwrap();
+        write(ssd, &id).unwrap();
+        assert_eq!(read(ssd).unwrap().as_deref(), Some(id.as_str()));
+        assert_eq!(verify(ssd, Some(&id), false).unwrap(), None);
+
+        // 同一路径上换了一块 SSD
+        let other = tempfile::tempdir().unwrap();
+        write(other.path(), "other-drive").unwrap();
+        let err = verify(other.path(), Some(&id), false).unwrap_err();
+        assert!(err.to_string().contains("expected drive ID"), "{}", err);
+        assert_eq!(verify(other.path(), Some(&id), true).unwrap().as_deref(), Some("other-drive"));
+
+        // ID 文件丢失同样视为不符
+        let empty = tempfile::tempdir().unwrap();
+        assert!(verify(empty.path(), Some(&id), false).is_err());
+        let adopted = verify(empty.path(), Some(&id), true).unwrap().unwrap();
+        assert_ne!(adopted, id);
+
+        // 映射尚未记录 ID（旧版本的配置）：沿用 SSD 上已有的 ID
+        assert_eq!(verify(ssd, None, false).unwrap(), Some(id));
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use std::path::{Component, Path, PathBuf};
+
+use crate::ignore::IgnoreMatcher;
+
+/// Directories matching `pattern` (`add --local '~/clients/*/assets'`). `*` and `?` are
+/// allowed in any path component; a leading `~` is the home directory.
+pub fn expand_dirs(pattern: &str) -> Result<Vec<PathBuf>> {
+    let expanded = match pattern.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let home = dirs::home_dir().context("Cannot determine home directory")?;
+            home.join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(pattern),
+    };
+
+    let mut matches = vec![PathBuf::new()];
+    for component in expanded.components() {
+        let Component::Normal(name) = component else {
+            matches.iter_mut().for_each(|m| m.push(component));
+            continue;
+        };
+        let name = name.to_string_lossy();
+        if !name.contains(['*', '?']) {
+            matches.iter_mut().for_each(|m| m.push(&*name));
+            continue;
+        }
+        // 含通配符的路径段：逐个列出已匹配目录的子目录
+        let mut next = vec![];
+        for dir in &matches {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let entry_name = entry.file_name().to_string_lossy().to_string();
+                if IgnoreMatcher::glob_match(&entry_name, &name) && entry.path().is_dir() {
+                    next.push(entry.path());
+                }
+            }
+        }
+        matches = next;
+    }
+    matches.retain(|m| m.is_dir());
+    matches.sort();
+    Ok(matches)
+}
+
+/// Fill in `{name}`, `{parent}` and `{grandparent}` (components of `path`, counted from
+/// the end) in an `--ssd-template`.
+pub fn render_template(template: &str, path: &Path) -> Result<String> {
+    let names: Vec<String> = path
+        .components()
+        .rev()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .with_context(|| format!("Unclosed '{{' in template '{}'", template))?;
+        let key = &rest[start + 1..start + end];
+        let depth = match key {
+            "name" => 0,
+            "parent" => 1,
+            "grandparent" => 2,
+            _ => anyhow::bail!(
+                "Unknown placeholder '{{{}}}' in template '{}' (use {{name}}, {{parent}} or {{grandparent}})",
+                key,
+                template
+            ),
+        };
+        let value = names
+            .get(depth)
+            .with_context(|| format!("'{}' has no {{{}}} component", path.display(), key))?;
+        out.push_str(value);
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_dirs_and_template() {
+        let tmp = tempfile::tempdir().unwrap();
+        for dir in ["acme/assets", "globex/assets", "initech/docs"] {
+            std::fs::create_dir_all(tmp.path().join("clients").join(dir)).unwrap();
+        }
+        // 同名文件不算匹配
+        std::fs::write(tmp.path().join("clients/readme"), "x").unwrap();
+
+        let pattern = format!("{}/clients/*/assets", tmp.path().display());
+        let dirs = expand_dirs(&pattern).unwrap();
+        assert_eq!(
+            dirs,
+            vec![tmp.path().join("clients/acme/assets"), tmp.path().join("clients/globex/assets")]
+        );
+
+        assert_eq!(render_template("{parent}_ASSETS", &dirs[0]).unwrap(), "acme_ASSETS");
+        assert_eq!(render_template("{grandparent}/{parent}-{name}", &dirs[1]).unwrap(), "clients/globex-assets");
+        assert!(render_template("{client}", &dirs[0]).is_err());
+        assert!(render_template("{parent", &dirs[0]).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::config::AppConfig;
+
+/// Sidecar hash cache keyed by absolute path + size + mtime, kept in the config dir so it
+/// survives snapshot resets. 仅追加写入的文本文件，每行 `size\tmtime\thash\tpath`；
+/// 同一路径以最后一行为准，超过大小上限时在加载时压缩。
+#[derive(Debug)]
+pub struct HashStore {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, (u64, i64, String)>>,
+    writer: Mutex<BufWriter<File>>,
+}
+
+static STORE: OnceLock<HashStore> = OnceLock::new();
+
+pub fn store_path() -> Result<PathBuf> {
+    Ok(AppConfig::config_dir()?.join("hash-cache.tsv"))
+}
+
+/// Enable the process-wide store. Later calls are no-ops.
+pub fn init(path: &Path, max_bytes: u64) -> Result<()> {
+    if STORE.get().is_none() {
+        let _ = STORE.set(HashStore::open(path, max_bytes)?);
+    }
+    Ok(())
+}
+
+/// Cached hash for `abs_path` if its size and mtime are unchanged.
+pub fn lookup(abs_path: &Path, size: u64, mtime_secs: i64) -> Option<String> {
+    STORE.get()?.get(abs_path, size, mtime_secs)
+}
+
+pub fn record(abs_path: &Path, size: u64, mtime_secs: i64, hash: &str) {
+    if let Some(store) = STORE.get() {
+        if let Err(e) = store.insert(abs_path, size, mtime_secs, hash) {
+            log::warn!("Failed to update hash cache: {}", e);
+        }
+    }
+}
+
+pub fn flush() {
+    if let Some(store) = STORE.get() {
+        if let Err(e) = store.writer.lock().unwrap().flush() {
+            log::warn!("Failed to write hash cache {}: {}", store.path.display(), e);
+        }
+    }
+}
+
+impl HashStore {
+    fn open(path: &Path, max_bytes: u64) -> Result<Self> {
+        let mut seen: Vec<String> = vec![];
+        let mut entries = HashMap::new();
+        if path.exists() {
+            let file = File::open(path)
+                .with_context(|| format!("Failed to open hash cache: {}", path.display()))?;
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                let mut parts = line.splitn(4, '\t');
+                let (Some(size), Some(mtime), Some(hash), Some(file_path)) =
+                    (parts.next(), parts.next(), parts.next(), parts.next())
+                else {
+                    continue;
+                };
+                let (Ok(size), Ok(mtime)) = (size.parse::<u64>(), mtime.parse::<i64>()) else {
+                    continue;
+                };
+                entries.insert(file_path.to_string(), (size, mtime, hash.to_string()));
+                seen.push(file_path.to_string());
+            }
+        }
+
+        let file_len = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if file_len > max_bytes {
+            // 按每个路径最后一次写入的先后排序，最久未更新的排在前面
+            let mut order: Vec<String> = vec![];
+            let mut kept = HashSet::new();
+            for p in seen.into_iter().rev() {
+                if kept.insert(p.clone()) {
+                    order.push(p);
+                }
+            }
+            order.reverse();
+            Self::compact(path, &mut entries, &order, max_bytes)?;
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open hash cache: {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    /// Rewrite the file with one line per path, dropping the oldest paths until it fits
+    /// in half the cap (so it doesn't need compacting again right away).
+    fn compact(
+        path: &Path,
+        entries: &mut HashMap<String, (u64, i64, String)>,
+        order: &[String],
+        max_bytes: u64,
+    ) -> Result<()> {
+        let lines: Vec<String> = order
+            .iter()
+            .filter_map(|p| entries.get(p).map(|e| format_line(p, e.0, e.1, &e.2)))
+            .collect();
+        let mut total: u64 = lines.iter().map(|l| l.len() as u64).sum();
+        let mut skip = 0;
+        while total > max_bytes / 2 && skip < lines.len() {
+            total -= lines[skip].len() as u64;
+            entries.remove(&order[skip]);
+            skip += 1;
+        }
+        let tmp = path.with_extension("tsv.tmp");
+        std::fs::write(&tmp, lines[skip..].concat())
+            .with_context(|| format!("Failed to write hash cache: {}", tmp.display()))?;
+        std::fs::rename(&tmp, path)?;
+        log::debug!("Compacted hash cache: dropped {} old entries", skip);
+        Ok(())
+    }
+
+    fn get(&self, abs_path: &Path, size: u64, mtime_secs: i64) -> Option<String> {
+        let key = abs_path.to_string_lossy();
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(key.as_ref())
+            .filter(|(s, m, _)| *s == size && *m == mtime_secs)
+            .map(|(_, _, hash)| hash.clone())
+    }
+
+    fn insert(&self, abs_path: &Path, size: u64, mtime_secs: i64, hash: &str) -> Result<()> {
+        let key = abs_path.to_string_lossy().to_string();
+        // 含换行的路径无法按行存储，直接跳过
+        if key.contains('\n') {
+            return Ok(());
+        }
+        let line = format_line(&key, size, mtime_secs, hash);
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, (size, mtime_secs, hash.to_string()));
+        self.writer.lock().unwrap().write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn format_line(path: &str, size: u64, mtime_secs: i64, hash: &str) -> String {
+    format!("{}\t{}\t{}\t{}\n", size, mtime_secs, hash, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_roundtrip_and_compaction() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("hash-cache.tsv");
+        let store = HashStore::open(&path, 1 << 20).unwrap();
+        store.insert(Path::new("/data/a.txt"), 10, 100, "blake3:a").unwrap();
+        store.insert(Path::new("/data/b.txt"), 20, 200, "blake3:b").unwrap();
+        store.insert(Path::new("/data/a.txt"), 11, 101, "blake3:a2").unwrap();
+        store.writer.lock().unwrap().flush().unwrap();
+
+        let reopened = HashStore::open(&path, 1 << 20).unwrap();
+        assert_eq!(reopened.get(Path::new("/data/a.txt"), 11, 101).as_deref(), Some("blake3:a2"));
+        // size/mtime 变化后不能使用旧哈希
+        assert_eq!(reopened.get(Path::new("/data/a.txt"), 10, 100), None);
+        assert_eq!(reopened.get(Path::new("/data/b.txt"), 20, 200).as_deref(), Some("blake3:b"));
+
+        // 超过上限：去重后仍超出一半上限的最旧条目被丢弃
+        let line_len = format_line("/data/b.txt", 20, 200, "blake3:b").len() as u64;
+        let compacted = HashStore::open(&path, line_len * 3).unwrap();
+        assert_eq!(compacted.get(Path::new("/data/b.txt"), 20, 200), None);
+        assert!(compacted.get(Path::new("/data/a.txt"), 11, 101).is_some());
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+    }
+}
@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use crate::config::{AppConfig, SyncMapping};
+use crate::snapshot::Snapshot;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Status {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Status::Pass => "✓",
+            Status::Warn => "⚠",
+            Status::Fail => "✕",
+        }
+    }
+}
+
+/// One line of the `doctor` health report.
+#[derive(Debug)]
+pub struct Check {
+    pub status: Status,
+    pub message: String,
+}
+
+impl Check {
+    fn new(status: Status, message: impl Into<String>) -> Self {
+        Check { status, message: message.into() }
+    }
+}
+
+/// Check that a mapping can be synced: the local folder and SSD mount exist, the SSD is
+/// writable, and the `.ssd-syncer` directory and its snapshots are intact.
+pub fn check_mapping(mapping: &SyncMapping) -> Vec<Check> {
+    let mut checks = vec![];
+
+    let local = Path::new(&mapping.local);
+    checks.push(if local.is_dir() {
+        Check::new(Status::Pass, format!("Local folder exists: {}", mapping.local))
+    } else if local.exists() {
+        Check::new(Status::Fail, format!("Local path is not a folder: {}", mapping.local))
+    } else {
+        Check::new(Status::Fail, format!("Local folder does not exist: {}", mapping.local))
+    });
+
+    let ssd = Path::new(&mapping.ssd);
+    if !ssd.is_dir() {
+        checks.push(Check::new(
+            Status::Fail,
+            format!("SSD mount point does not exist (is the SSD connected?): {}", mapping.ssd),
+        ));
+        return checks;
+    }
+    checks.push(Check::new(Status::Pass, format!("SSD mount point exists: {}", mapping.ssd)));
+
+    // 写入并删除一个探测文件，确认 SSD 可写（只读挂载、权限不足等）
+    let probe = ssd.join(format!(".ssd-syncer-doctor-{}", std::process::id()));
+    match std::fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            checks.push(Check::new(Status::Pass, "SSD is writable"));
+        }
+        Err(e) => checks.push(Check::new(Status::Fail, format!("SSD is not writable: {}", e))),
+    }
+
+    let syncer_dir = AppConfig::ssd_syncer_dir(ssd);
+    if !syncer_dir.exists() {
+        checks.push(Check::new(
+            Status::Warn,
+            format!("{} does not exist yet (never synced)", syncer_dir.display()),
+        ));
+        return checks;
+    }
+    if !syncer_dir.is_dir() {
+        checks.push(Check::new(
+            Status::Fail,
+            format!("{} is not a directory", syncer_dir.display()),
+        ));
+        return checks;
+    }
+    let snapshots_root = syncer_dir.join("snapshots");
+    if snapshots_root.exists() && !snapshots_root.is_dir() {
+        checks.push(Check::new(
+            Status::Fail,
+            format!("{} is not a directory", snapshots_root.display()),
+        ));
+        return checks;
+    }
+    checks.push(Check::new(Status::Pass, format!("{} is intact", syncer_dir.display())));
+
+    // 所有机器的快照都要能解析，否则下次同步会失败
+    let mut files: Vec<std::path::PathBuf> = std::fs::read_dir(&snapshots_root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .flat_map(|machine| std::fs::read_dir(machine.path()).into_iter().flatten().flatten())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    let mut valid = 0;
+    for path in &files {
+        match Snapshot::load(path) {
+            Ok(_) => valid += 1,
+            Err(e) => checks.push(Check::new(
+                Status::Fail,
+                format!("Snapshot is corrupt: {} ({:#})", path.display(), e),
+            )),
+        }
+    }
+    if valid > 0 {
+        checks.push(Check::new(Status::Pass, format!("{} snapshot(s) are valid", valid)));
+    }
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(local: &Path, ssd: &Path) -> SyncMapping {
+        SyncMapping {
+            name: Some("work".to_string()),
+            local: local.to_string_lossy().to_string(),
+            ssd: ssd.to_string_lossy().to_string(),
+            extensions: vec![],
+            log: true,
+            max_files: None,
+            conflict: None,
+            drive_id: None,
+            drive_ids: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_healthy_mapping() {
+        let tmp = tempfile::tempdir().unwrap();
+        let local = tmp.path().join("local");
+        let ssd = tmp.path().join("ssd");
+        std::fs::create_dir_all(&local).unwrap();
+        let snapshot = AppConfig::ssd_snapshots_dir(&ssd, "mac").join(Snapshot::snapshot_filename("work"));
+        Snapshot::new("work", "mac").save(&snapshot).unwrap();
+
+        let checks = check_mapping(&mapping(&local, &ssd));
+        assert!(checks.iter().all(|c| c.status == Status::Pass), "{:?}", checks);
+        assert!(checks.last().unwrap().message.starts_with("1 snapshot(s)"));
+
+        // 损坏的快照判为失败
+        std::fs::write(&snapshot, "{\"files\":").unwrap();
+        let checks = check_mapping(&mapping(&local, &ssd));
+        assert!(checks.iter().any(|c| c.status == Status::Fail && c.message.contains("corrupt")));
+    }
+
+    #[test]
+    fn test_missing_local_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ssd = tmp.path().join("ssd");
+        std::fs::create_dir_all(&ssd).unwrap();
+
+        let checks = check_mapping(&mapping(&tmp.path().join("gone"), &ssd));
+        assert_eq!(checks[0].status, Status::Fail);
+        assert!(checks[0].message.starts_with("Local folder does not exist"));
+        // SSD 尚未同步过：只是警告
+        assert!(checks.iter().any(|c| c.status == Status::Warn));
+        assert!(!ssd.join(".ssd-syncer").exists());
+    }
+}
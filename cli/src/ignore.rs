@@ -1,44 +1,219 @@
+use std::collections::BTreeMap;
 use std::path::Path;
 
+/// Per-folder ignore file at the root of a mapping's local folder, one pattern per line.
+pub const IGNORE_FILE_NAME: &str = ".ssdignore";
+
+#[derive(Debug, Clone)]
 pub struct IgnoreMatcher {
     patterns: Vec<String>,
+    case_insensitive: bool,
+}
+
+/// Guess whether the filesystem holding `path` treats names case-insensitively,
+/// by checking whether a case-flipped spelling of the path resolves to the same entry.
+/// 路径中没有可翻转大小写的字母时，按平台默认值处理（macOS/Windows 不区分大小写）。
+pub fn detect_case_insensitive(path: &Path) -> bool {
+    let platform_default = cfg!(any(target_os = "macos", target_os = "windows"));
+    let Ok(original) = std::fs::metadata(path) else {
+        return platform_default;
+    };
+    for ancestor in path.ancestors() {
+        let Some(name) = ancestor.file_name().map(|n| n.to_string_lossy()) else {
+            continue;
+        };
+        let flipped: String = name
+            .chars()
+            .map(|c| {
+                if c.is_lowercase() {
+                    c.to_uppercase().next().unwrap_or(c)
+                } else {
+                    c.to_lowercase().next().unwrap_or(c)
+                }
+            })
+            .collect();
+        if flipped == name {
+            continue;
+        }
+        let rest = path.strip_prefix(ancestor).unwrap_or(Path::new(""));
+        let probe = ancestor.with_file_name(flipped).join(rest);
+        return match std::fs::metadata(&probe) {
+            Ok(meta) => same_entry(&original, &meta),
+            Err(_) => false,
+        };
+    }
+    platform_default
+}
+
+#[cfg(unix)]
+fn same_entry(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_entry(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    a.is_dir() == b.is_dir() && a.len() == b.len() && a.modified().ok() == b.modified().ok()
+}
+
+/// Check that `pattern` can actually match something with this matcher.
+/// 返回说明原因的错误信息，而不是静默接受永远不会命中的规则。
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    // `!pattern` 取消之前规则的忽略，其余部分按普通规则检查
+    let pattern = match pattern.strip_prefix('!') {
+        Some("") => return Err("'!' must be followed by a pattern to re-include".to_string()),
+        Some(rest) => rest,
+        None => pattern,
+    };
+    if pattern.trim().is_empty() {
+        return Err("pattern is empty".to_string());
+    }
+    if pattern.trim() != pattern {
+        return Err("pattern has leading or trailing whitespace".to_string());
+    }
+    if pattern.contains('\\') {
+        return Err("use '/' as the path separator, not '\\'".to_string());
+    }
+    if pattern.starts_with('/') || pattern.ends_with('/') {
+        return Err("remove the leading/trailing '/'; paths are relative to the sync folder root".to_string());
+    }
+    if pattern.contains("//") {
+        return Err("pattern contains an empty path component ('//')".to_string());
+    }
+    if pattern.contains('?') && !pattern.contains('*') {
+        return Err("'?' is only treated as a wildcard in patterns that also contain '*'".to_string());
+    }
+    // 方括号必须成对出现且不能嵌套
+    let mut in_brackets = false;
+    for c in pattern.chars() {
+        match c {
+            '[' if in_brackets => return Err("nested '[' in pattern".to_string()),
+            ']' if !in_brackets => return Err("unbalanced ']' in pattern".to_string()),
+            '[' | ']' => in_brackets = !in_brackets,
+            _ => {}
+        }
+    }
+    if in_brackets {
+        return Err("unbalanced '[' in pattern".to_string());
+    }
+    Ok(())
 }
 
 impl IgnoreMatcher {
     pub fn new(patterns: &[String]) -> Self {
         Self {
             patterns: patterns.to_vec(),
+            case_insensitive: false,
         }
     }
 
+    /// Add the patterns of the `.ssdignore` file in `root`, if there is one. They come after
+    /// the configured patterns, so a `!pattern` in the file can re-include a path.
+    pub fn with_ignore_file(mut self, root: &Path) -> anyhow::Result<Self> {
+        let path = root.join(IGNORE_FILE_NAME);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(self),
+            Err(e) => return Err(anyhow::Error::new(e).context(format!("Failed to read {}", path.display()))),
+        };
+        // 与 .gitignore 相同：空行和 # 开头的行是注释
+        self.patterns.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string),
+        );
+        Ok(self)
+    }
+
+    /// Compare names and patterns ignoring case (for case-insensitive filesystems).
+    pub fn with_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
     pub fn is_ignored(&self, rel_path: &str) -> bool {
-        // 统一使用正斜杠
-        let normalized = rel_path.replace('\\', "/");
-        let path = Path::new(&normalized);
-
-        for pattern in &self.patterns {
-            if pattern.contains('/') {
-                // 路径模式：匹配完整相对路径或其前缀
-                // 例如 "projects/temp" 匹配 "projects/temp" 及 "projects/temp/foo.txt"
-                if Self::matches_pattern(&normalized, pattern) {
-                    return true;
-                }
-                // 也检查路径是否以 pattern/ 开头
-                if normalized.starts_with(&format!("{}/", pattern)) {
-                    return true;
-                }
+        self.matched_pattern(rel_path).is_some()
+    }
+
+    /// Return the pattern that ignores `rel_path`, if any. Patterns are checked in order and
+    /// the last one that matches decides, as in `.gitignore`: a matching `!pattern` re-includes
+    /// the path. A file inside an ignored directory cannot be re-included, since the scan
+    /// never descends into that directory.
+    pub fn matched_pattern(&self, rel_path: &str) -> Option<&str> {
+        // 统一使用正斜杠；不区分大小写时两侧都转为小写再比较
+        let mut normalized = rel_path.replace('\\', "/");
+        if self.case_insensitive {
+            normalized = normalized.to_lowercase();
+        }
+
+        let mut decision = None;
+        for original in &self.patterns {
+            let (negated, pattern) = match original.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, original.as_str()),
+            };
+            let lowered;
+            let pattern = if self.case_insensitive {
+                lowered = pattern.to_lowercase();
+                &lowered
             } else {
-                // 名称模式：匹配路径中任意一个组件
-                for component in path.components() {
-                    let name = component.as_os_str().to_string_lossy();
-                    if Self::matches_pattern(&name, pattern) {
-                        return true;
-                    }
-                }
+                pattern
+            };
+            if Self::path_matches(&normalized, pattern) {
+                decision = (!negated).then_some(original.as_str());
             }
         }
+        decision
+    }
 
-        false
+    fn path_matches(normalized: &str, pattern: &str) -> bool {
+        if pattern.contains('/') {
+            // 路径模式：匹配完整相对路径或其前缀
+            // 例如 "projects/temp" 匹配 "projects/temp" 及 "projects/temp/foo.txt"
+            let glob = pattern.contains('*');
+            let pattern: Vec<&str> = pattern.split('/').collect();
+            let path: Vec<&str> = normalized.split('/').collect();
+            (1..=path.len()).any(|end| Self::segments_match(&path[..end], &pattern, glob))
+        } else {
+            // 名称模式：匹配路径中任意一个组件
+            Path::new(normalized)
+                .components()
+                .any(|component| Self::matches_pattern(&component.as_os_str().to_string_lossy(), pattern))
+        }
+    }
+
+    /// Group the ignored ones among `paths` by the pattern that ignores them.
+    pub fn ignored_by_pattern<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a str>,
+    ) -> BTreeMap<String, Vec<String>> {
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for path in paths {
+            if let Some(pattern) = self.matched_pattern(path) {
+                groups.entry(pattern.to_string()).or_default().push(path.to_string());
+            }
+        }
+        groups
+    }
+
+    /// Match path segments against pattern segments: `**` stands for zero or more whole
+    /// segments, while `*` and `?` stay within one segment.
+    fn segments_match(path: &[&str], pattern: &[&str], glob: bool) -> bool {
+        match pattern.split_first() {
+            None => path.is_empty(),
+            Some((&"**", rest)) if glob => {
+                (0..=path.len()).any(|skip| Self::segments_match(&path[skip..], rest, glob))
+            }
+            Some((first, rest)) => match path.split_first() {
+                Some((name, path_rest)) => {
+                    let matched = if glob { Self::glob_match(name, first) } else { name == first };
+                    matched && Self::segments_match(path_rest, rest, glob)
+                }
+                None => false,
+            },
+        }
     }
 
     fn matches_pattern(name: &str, pattern: &str) -> bool {
@@ -50,7 +225,7 @@ impl IgnoreMatcher {
         }
     }
 
-    fn glob_match(text: &str, pattern: &str) -> bool {
+    pub fn glob_match(text: &str, pattern: &str) -> bool {
         // Simple glob: only supports * (match any sequence) and ? (match single char)
         let t_chars: Vec<char> = text.chars().collect();
         let p_chars: Vec<char> = pattern.chars().collect();
@@ -106,6 +281,95 @@ mod tests {
         assert!(!m.is_ignored("my-project/main.rs"));
     }
 
+    #[test]
+    fn test_case_insensitive_mode() {
+        let patterns = vec!["*.tmp".to_string(), "Build".to_string(), "docs/Drafts".to_string()];
+        let sensitive = IgnoreMatcher::new(&patterns);
+        assert!(!sensitive.is_ignored("cache/FILE.TMP"));
+        assert!(!sensitive.is_ignored("build"));
+        assert!(sensitive.is_ignored("Build/out.o"));
+
+        let insensitive = IgnoreMatcher::new(&patterns).with_case_insensitive(true);
+        assert_eq!(insensitive.matched_pattern("cache/FILE.TMP"), Some("*.tmp"));
+        assert_eq!(insensitive.matched_pattern("build/out.o"), Some("Build"));
+        assert_eq!(insensitive.matched_pattern("DOCS/drafts/a.md"), Some("docs/Drafts"));
+        assert!(!insensitive.is_ignored("file.txt"));
+    }
+
+    #[test]
+    fn test_detect_case_insensitive() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("MixedCase");
+        std::fs::create_dir(&dir).unwrap();
+        // 大小写翻转后的路径能否指向同一目录，取决于临时目录所在文件系统
+        let expected = std::fs::metadata(tmp.path().join("mIXEDcASE"))
+            .map(|m| m.is_dir())
+            .unwrap_or(false);
+        assert_eq!(detect_case_insensitive(&dir), expected);
+    }
+
+    #[test]
+    fn test_matched_pattern() {
+        let m = IgnoreMatcher::new(&["*.tmp".to_string(), "build".to_string()]);
+        assert_eq!(m.matched_pattern("build"), Some("build"));
+        assert_eq!(m.matched_pattern("src/a.tmp"), Some("*.tmp"));
+        assert_eq!(m.matched_pattern("src/main.rs"), None);
+    }
+
+    #[test]
+    fn test_ignored_by_pattern() {
+        let m = IgnoreMatcher::new(&["*.log".to_string(), "node_modules".to_string()]);
+        let groups = m.ignored_by_pattern(["a.log", "src/main.rs", "web/node_modules/x.js", "b/c.log"]);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups["*.log"], vec!["a.log", "b/c.log"]);
+        assert_eq!(groups["node_modules"], vec!["web/node_modules/x.js"]);
+    }
+
+    #[test]
+    fn test_validate_pattern() {
+        for ok in ["*.log", "node_modules", "projects/temp", "file?.*", "[draft] notes"] {
+            assert!(validate_pattern(ok).is_ok(), "{}", ok);
+        }
+        for bad in ["", "  ", " tmp", "build/", "/dist", "a\\b", "a//b", "file?.txt", "[abc", "a]b", "[[x]]"] {
+            assert!(validate_pattern(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_negated_pattern() {
+        let m = IgnoreMatcher::new(&["*.log".to_string(), "!important.log".to_string()]);
+        assert!(m.is_ignored("foo.log"));
+        assert!(!m.is_ignored("important.log"));
+        assert!(!m.is_ignored("logs/important.log"));
+        assert_eq!(m.matched_pattern("a/foo.log"), Some("*.log"));
+
+        // 后出现的规则优先：顺序颠倒时 important.log 仍被忽略
+        let reversed = IgnoreMatcher::new(&["!important.log".to_string(), "*.log".to_string()]);
+        assert!(reversed.is_ignored("important.log"));
+        assert!(reversed.is_ignored("foo.log"));
+
+        assert!(validate_pattern("!important.log").is_ok());
+        assert!(validate_pattern("!").is_err());
+        assert!(validate_pattern("!/dist").is_err());
+    }
+
+    #[test]
+    fn test_double_star_pattern() {
+        let m = IgnoreMatcher::new(&["docs/**/*.tmp".to_string(), "src/*.rs".to_string()]);
+        assert!(m.is_ignored("docs/a/b/c.tmp"));
+        assert!(m.is_ignored("docs/c.tmp"));
+        assert!(!m.is_ignored("docs/a/b/c.txt"));
+        assert!(!m.is_ignored("other/docs/c.tmp"));
+        // 单个 * 不跨越目录
+        assert!(m.is_ignored("src/main.rs"));
+        assert!(!m.is_ignored("src/sub/main.rs"));
+
+        let m = IgnoreMatcher::new(&["src/**/target".to_string()]);
+        assert!(m.is_ignored("src/target"));
+        assert!(m.is_ignored("src/crates/core/target/debug/app"));
+        assert!(!m.is_ignored("lib/target"));
+    }
+
     #[test]
     fn test_path_pattern() {
         // 路径模式：只忽略特定路径下的目录
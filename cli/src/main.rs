@@ -1,23 +1,66 @@
+mod archive;
+mod compat;
 mod config;
 mod diff;
+mod drive_id;
+mod glob;
+mod hash_store;
+mod health;
 mod ignore;
+mod output;
+mod prompt;
+mod resume;
+mod state;
 mod scanner;
 mod snapshot;
 mod sync_engine;
-
-use anyhow::Result;
-use clap::{Parser, Subcommand};
-use std::path::Path;
+mod sync_log;
+mod units;
+mod versions;
+mod watch;
+
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 use config::AppConfig;
 use diff::SyncAction;
 use ignore::IgnoreMatcher;
-use snapshot::Snapshot;
+use output::{outln, paint, Tone};
+use scanner::ScanOptions;
+use snapshot::{FileEntry, Snapshot};
 
 #[derive(Parser)]
 #[command(name = "ssd-syncer", version, about = "Sync folders via SSD across machines")]
 struct Cli {
+    /// Plain ASCII output: no emoji, arrows or box-drawing characters
+    #[arg(long, global = true)]
+    ascii: bool,
+
+    /// Only print errors (to stderr); the exit code tells whether the command succeeded
+    #[arg(long, short, global = true)]
+    quiet: bool,
+
+    /// Colour changes by direction: auto = only on a terminal and when NO_COLOR is not set
+    #[arg(long, global = true, value_enum, value_name = "WHEN", default_value_t = output::ColorChoice::Auto)]
+    color: output::ColorChoice,
+
+    /// Use this config file instead of ~/.ssd-syncer/config.toml (state is kept next to it)
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Use this machine name instead of `[machine] name` for this run (separate snapshots)
+    #[arg(long, global = true, value_name = "NAME")]
+    machine: Option<String>,
+
+    /// Use the SSD of this profile ([ssd.<NAME>], or 'default') for mappings on the default SSD
+    #[arg(long, global = true, value_name = "NAME")]
+    ssd_profile: Option<String>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -33,15 +76,26 @@ enum Commands {
 
     /// Add a sync folder mapping
     Add {
-        /// Local folder path
+        /// Local folder path, or a pattern such as '~/clients/*/assets' with --ssd-template
         #[arg(long)]
         local: String,
-        /// SSD target absolute path (e.g. "/Volumes/WORK_SYNC/WORK_SYNC")
-        #[arg(long)]
-        ssd: String,
+        /// SSD target absolute path (e.g. "/Volumes/WORK_SYNC/WORK_SYNC");
+        /// with --ssd-template, the SSD directory that holds the folders
+        #[arg(long, required_unless_present = "ssd_template")]
+        ssd: Option<String>,
         /// Alias name for this mapping (e.g. "WORK")
+        #[arg(long, required_unless_present = "ssd_template", conflicts_with = "ssd_template")]
+        name: Option<String>,
+        /// Add one mapping per folder matching --local; the SSD folder (and mapping name) is
+        /// built from {name}, {parent} and {grandparent} of each match, e.g. '{parent}_ASSETS'
         #[arg(long)]
-        name: String,
+        ssd_template: Option<String>,
+        /// Remember the SSD directory for later `add --ssd-template` runs without --ssd (default)
+        #[arg(long, overrides_with = "no_remember")]
+        remember: bool,
+        /// Don't record the SSD directory as the last used one
+        #[arg(long, overrides_with = "remember")]
+        no_remember: bool,
     },
 
     /// Remove a sync folder mapping
@@ -51,31 +105,159 @@ enum Commands {
         name: String,
     },
 
+    /// Rename a mapping or move its local or SSD folder
+    #[command(visible_alias = "rename-mapping")]
+    Edit {
+        /// Current name or SSD folder of the mapping
+        mapping: String,
+        /// New mapping name
+        #[arg(long)]
+        name: Option<String>,
+        /// New local folder (must exist)
+        #[arg(long)]
+        local: Option<String>,
+        /// New SSD folder (absolute path)
+        #[arg(long)]
+        ssd: Option<String>,
+    },
+
     /// List all configured sync mappings
-    List,
+    List {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
 
-    /// Sync all configured folders with SSD
-    Sync {
-        /// Mapping name (optional if only one mapping exists)
-        name: Option<String>,
-        /// Dry run (preview only, no changes)
+    /// Set the mount path of the default SSD or of a named SSD profile
+    SetSsd {
+        /// Mount path of the SSD (e.g. "/Volumes/BACKUP")
+        path: String,
+        /// Profile name (default: the 'default' profile, [machine] ssd_mount)
+        #[arg(long)]
+        profile: Option<String>,
+    },
+
+    /// Write the mappings, ignore patterns and settings to a file for another machine
+    /// (without this machine's name and SSD mounts)
+    ExportConfig {
+        /// File to write (TOML)
+        #[arg(long)]
+        output: PathBuf,
+    },
+
+    /// Add the mappings and ignore patterns of a file written by `export-config`
+    ImportConfig {
+        /// File written by `export-config`
+        #[arg(long)]
+        input: PathBuf,
+    },
+
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Manage the persistent hash cache ([performance] hash_cache)
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+
+    /// Maintain the per-machine snapshots stored on the SSD
+    Snapshots {
+        #[command(subcommand)]
+        command: SnapshotsCommand,
+    },
+
+    /// Delete this machine's snapshots of mappings no longer configured (and old conflict copies)
+    Prune {
+        /// Also delete conflict copies older than --older-than, on both sides of every mapping
+        #[arg(long, default_value_t = false)]
+        conflicts: bool,
+        /// Minimum age of conflict copies to delete, from the time in their name (s / m / h / d / w)
+        #[arg(long, default_value = "30d", requires = "conflicts")]
+        older_than: String,
+        /// Dry run (list what would be deleted)
         #[arg(long, default_value_t = false)]
         dry_run: bool,
-        /// Verbose mode: show each file operation on a separate line
-        #[arg(long, short, default_value_t = false)]
-        verbose: bool,
+    },
+
+    /// Sync all configured folders with SSD
+    Sync(SyncArgs),
+
+    /// Keep watching the local folders and sync shortly after files change
+    Watch {
+        /// Mapping name (default: all mappings)
+        name: Option<String>,
+        /// Sync once the folders have been quiet this long (e.g. 2s, 1m); overrides [watch] debounce_ms
+        #[arg(long, value_name = "DURATION")]
+        delay: Option<String>,
     },
 
     /// Show sync status (preview changes without applying)
     Status {
-        /// Mapping name (optional if only one mapping exists)
-        name: Option<String>,
+        /// Mapping names or glob patterns such as 'proj-*' (optional if only one mapping exists)
+        names: Vec<String>,
+        /// Same as NAMES (repeatable)
+        #[arg(long = "name", value_name = "NAME")]
+        name: Vec<String>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Also ignore files matching PATTERN for this run only (repeatable, same syntax as ignore-add)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Hash every file, even if size and mtime are unchanged (slower; catches edits that keep the mtime)
+        #[arg(long, default_value_t = false)]
+        checksum: bool,
+        /// Number of files to hash in parallel on each side (default: number of CPUs)
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        threads: Option<u64>,
     },
 
     /// Show detailed diff between local and SSD
     Diff {
         /// Mapping name (optional if only one mapping exists)
         name: Option<String>,
+        /// Also write the plans to FILE, one tab-separated line per action (for diffing two runs)
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+        /// Also ignore files matching PATTERN for this run only (repeatable, same syntax as ignore-add)
+        #[arg(long, value_name = "PATTERN")]
+        exclude: Vec<String>,
+        /// Only print the action and path of each difference, without sizes and modification times
+        #[arg(long, default_value_t = false)]
+        names_only: bool,
+    },
+
+    /// Compute the sync plan without changing anything (not even creating SSD folders)
+    Plan {
+        /// Mapping name (optional if only one mapping exists)
+        #[arg(long)]
+        name: Option<String>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    /// Check config, folders and SSD state, then look for configuration drift between machines
+    Doctor {
+        /// Mapping name (optional if only one mapping exists)
+        name: Option<String>,
+    },
+
+    /// Re-hash the files on the SSD and compare them with the last sync (detects bit rot)
+    Verify {
+        /// Mapping name (optional if only one mapping exists)
+        name: Option<String>,
+    },
+
+    /// Measure scan and hash throughput for a mapping (no sync)
+    #[command(hide = true)]
+    Bench {
+        /// Mapping name (optional if only one mapping exists)
+        name: Option<String>,
     },
 
     /// Show sync history log
@@ -85,6 +267,62 @@ enum Commands {
         /// Number of recent entries to show
         #[arg(long, default_value_t = 20)]
         limit: usize,
+        /// Only show entries at or after this date (YYYY-MM-DD or "YYYY-MM-DD HH:MM:SS", UTC)
+        #[arg(long)]
+        newer_than: Option<String>,
+        /// Only show entries from this long ago until now, e.g. 7d, 24h or 30m
+        #[arg(long, value_name = "DURATION", conflicts_with = "newer_than")]
+        since: Option<String>,
+        /// Only show entries before this date (YYYY-MM-DD or "YYYY-MM-DD HH:MM:SS", UTC)
+        #[arg(long)]
+        older_than: Option<String>,
+    },
+
+    /// Mark a file as in sync after confirming both sides have identical content
+    Touch {
+        /// Mapping name
+        #[arg(long)]
+        name: String,
+        /// File path relative to the sync folder root
+        path: String,
+    },
+
+    /// Make the current state of one side the new base snapshot, discarding pending changes
+    ResetBase {
+        /// Mapping name
+        #[arg(long)]
+        name: String,
+        /// Side whose current state becomes the base
+        #[arg(long, value_enum, default_value_t = BaseSide::Local)]
+        side: BaseSide,
+    },
+
+    /// Pull everything from the SSD into the local folder (never deletes), e.g. on a fresh machine
+    RestoreFromSsd {
+        /// Mapping name
+        #[arg(long)]
+        name: String,
+        /// Dry run (preview only, no changes)
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Also replace local files whose content differs from the SSD
+        #[arg(long, default_value_t = false)]
+        overwrite: bool,
+    },
+
+    /// Restore a file's archived version onto the SSD (see `[safety] archive`)
+    RestoreArchive {
+        /// Mapping name (optional if only one mapping exists)
+        #[arg(long)]
+        name: Option<String>,
+        /// File path relative to the sync folder root
+        path: String,
+        /// Archive date to restore from (YYYY-MM-DD, default: latest)
+        #[arg(long)]
+        date: Option<String>,
+        /// List archived versions instead of restoring
+        #[arg(long, default_value_t = false)]
+        list: bool,
     },
 
     /// Reset ignore patterns to defaults (includes common build/temp directories)
@@ -99,6 +337,9 @@ enum Commands {
         patterns: Vec<String>,
     },
 
+    /// Check all ignore patterns for mistakes that would make them never match
+    IgnoreValidate,
+
     /// Remove one or more ignore patterns
     IgnoreRemove {
         /// Patterns to remove
@@ -107,26 +348,207 @@ enum Commands {
 
 }
 
-fn main() -> Result<()> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
-        .format_timestamp(None)
-        .init();
+#[derive(Args, Default)]
+struct SyncArgs {
+    /// Mapping names or glob patterns such as 'proj-*' (optional if only one mapping exists)
+    names: Vec<String>,
+    /// Same as NAMES (repeatable)
+    #[arg(long = "name", value_name = "NAME")]
+    name: Vec<String>,
+    /// Dry run (preview only, no changes)
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+    /// With --dry-run: show the change on each side that led to every planned action
+    #[arg(long, default_value_t = false, requires = "dry_run")]
+    explain: bool,
+    /// Verbose mode: show each file operation on a separate line
+    #[arg(long, short, default_value_t = false)]
+    verbose: bool,
+    /// Only print the per-mapping summary, no per-file progress
+    #[arg(long, default_value_t = false, conflicts_with = "verbose")]
+    summary_only: bool,
+    /// List the slowest files copied in each mapping (also shown with --verbose)
+    #[arg(long, default_value_t = false)]
+    profile: bool,
+    /// Number of files to copy in parallel (overrides [performance] copy_concurrency)
+    #[arg(long, visible_alias = "jobs", value_parser = clap::value_parser!(u64).range(1..))]
+    concurrency: Option<u64>,
+    /// Merge on a first sync even if the local and SSD folders look unrelated, and sync with an
+    /// SSD folder whose drive ID differs from the one recorded for the mapping on this SSD profile
+    #[arg(long, default_value_t = false)]
+    force: bool,
+    /// Only resolve conflicts; leave all other changes for the next sync
+    #[arg(long, default_value_t = false)]
+    only_conflicts: bool,
+    /// One-way backup: only copy and delete on the SSD; changes made on the SSD wait for a later sync
+    #[arg(long, default_value_t = false, conflicts_with_all = ["pull", "only_conflicts"])]
+    push: bool,
+    /// One-way restore: only copy and delete locally; local changes wait for a later sync
+    #[arg(long, default_value_t = false, conflicts_with = "only_conflicts")]
+    pull: bool,
+    /// Move deleted files to the system trash instead of deleting them (as [conflict] trash)
+    #[arg(long, default_value_t = false)]
+    delete_to_trash: bool,
+    /// Proceed without asking when [sync_settings] confirm would ask
+    #[arg(long, short = 'y', default_value_t = false, conflicts_with = "no")]
+    yes: bool,
+    /// Answer no when [sync_settings] confirm would ask (the mapping is skipped)
+    #[arg(long, default_value_t = false)]
+    no: bool,
+    /// After syncing, scan both sides again and report anything still out of sync
+    #[arg(long, default_value_t = false)]
+    verify_after: bool,
+    /// Only scan and sync this folder of the mapping (relative path, e.g. 'app/src')
+    #[arg(long)]
+    subpath: Option<String>,
+    /// Also ignore files matching PATTERN for this run only (repeatable, same syntax as ignore-add)
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+    /// Hash every file, even if size and mtime are unchanged (slower; catches edits that keep the mtime)
+    #[arg(long, default_value_t = false)]
+    checksum: bool,
+    /// Skip files larger than SIZE (e.g. 500M, 1G) for this run; overrides [scan] max_file_size
+    #[arg(long, value_name = "SIZE")]
+    max_file_size: Option<String>,
+    /// Number of files to hash in parallel on each side while scanning (default: number of CPUs)
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    threads: Option<u64>,
+    /// Remember the SSD directory of this mapping for `add --ssd-template` without --ssd (default)
+    #[arg(long, overrides_with = "no_remember")]
+    remember: bool,
+    /// Don't record the SSD directory as the last used one
+    #[arg(long, overrides_with = "remember")]
+    no_remember: bool,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective configuration, with defaults filled in
+    Show {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Delete the persistent hash cache
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum SnapshotsCommand {
+    /// Delete the snapshots of machines that have not synced for a long time
+    Prune {
+        /// Only prune machines whose last sync is older than this (s / m / h / d / w)
+        #[arg(long, default_value = "180d")]
+        older_than: String,
+        /// Only check the SSD of this mapping (default: all mappings)
+        #[arg(long)]
+        name: Option<String>,
+        /// Dry run (list what would be deleted)
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum BaseSide {
+    Local,
+    Ssd,
+}
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or(if cli.quiet { "warn" } else { "info" }),
+    )
+    .format_timestamp(None)
+    .init();
+    output::set_quiet(cli.quiet);
+    output::set_color(cli.color.enabled(
+        std::io::stdout().is_terminal(),
+        std::env::var("NO_COLOR").ok().as_deref(),
+    ));
+
+    if let Some(path) = &cli.config {
+        config::set_config_path(path);
+    }
+    // 配置尚不存在（如 init 之前）时只看命令行参数
+    output::set_ascii(cli.ascii || AppConfig::load().is_ok_and(|c| c.output.ascii));
+
+    if let Some(machine) = &cli.machine {
+        config::set_machine_override(machine);
+        let warning = format!(
+            "⚠ Running as machine '{}' (--machine): snapshots and conflict copies use this name, \
+             so this run reads and writes a separate snapshot namespace (.ssd-syncer/snapshots/{}/).",
+            machine, machine
+        );
+        eprintln!("{}", output::text(&warning));
+    }
+    if let Some(profile) = &cli.ssd_profile {
+        config::set_ssd_profile(profile);
+    }
+
     match cli.command {
-        None => cmd_sync(None, false, false),
+        None => cmd_sync(&SyncArgs::default()),
         Some(Commands::Init { name }) => cmd_init(&name),
-        Some(Commands::Add { local, ssd, name }) => cmd_add(&local, &ssd, &name),
+        Some(Commands::Add { local, ssd, name, ssd_template, no_remember, .. }) => match ssd_template {
+            Some(template) => cmd_add_glob(&local, ssd.as_deref(), &template, !no_remember),
+            None => cmd_add(&local, &ssd.unwrap_or_default(), &name.unwrap_or_default(), !no_remember),
+        },
         Some(Commands::Remove { name }) => cmd_remove(&name),
-        Some(Commands::List) => cmd_list(),
-        Some(Commands::Sync { name, dry_run, verbose }) => cmd_sync(name.as_deref(), dry_run, verbose),
-        Some(Commands::Status { name }) => cmd_status(name.as_deref()),
-        Some(Commands::Diff { name }) => cmd_diff(name.as_deref()),
-        Some(Commands::Log { name, limit }) => cmd_log(name.as_deref(), limit),
+        Some(Commands::Edit { mapping, name, local, ssd }) => {
+            cmd_edit(&mapping, config::MappingEdit { name, local, ssd })
+        }
+        Some(Commands::List { format }) => cmd_list(format),
+        Some(Commands::SetSsd { path, profile }) => cmd_set_ssd(&path, profile.as_deref()),
+        Some(Commands::ExportConfig { output }) => cmd_export_config(&output),
+        Some(Commands::ImportConfig { input }) => cmd_import_config(&input),
+        Some(Commands::Config { command: ConfigCommand::Show { format } }) => cmd_config_show(format),
+        Some(Commands::Cache { command: CacheCommand::Clear }) => cmd_cache_clear(),
+        Some(Commands::Snapshots { command: SnapshotsCommand::Prune { older_than, name, dry_run } }) => {
+            cmd_snapshots_prune(&older_than, name.as_deref(), dry_run)
+        }
+        Some(Commands::Prune { conflicts, older_than, dry_run }) => {
+            cmd_prune(conflicts.then_some(older_than.as_str()), dry_run)
+        }
+        Some(Commands::Sync(args)) => cmd_sync(&args),
+        Some(Commands::Watch { name, delay }) => cmd_watch(name.as_deref(), delay.as_deref()),
+        Some(Commands::Status { mut names, name, format, exclude, checksum, threads }) => {
+            names.extend(name);
+            cmd_status(&names, format, &exclude, checksum, threads)
+        }
+        Some(Commands::Diff { name, output, exclude, names_only }) => {
+            cmd_diff(name.as_deref(), output.as_deref(), &exclude, names_only)
+        }
+        Some(Commands::Plan { name, format }) => cmd_plan(name.as_deref(), format),
+        Some(Commands::Doctor { name }) => cmd_doctor(name.as_deref()),
+        Some(Commands::Verify { name }) => cmd_verify(name.as_deref()),
+        Some(Commands::Bench { name }) => cmd_bench(name.as_deref()),
+        Some(Commands::Log { name, limit, newer_than, since, older_than }) => {
+            cmd_log(name.as_deref(), limit, newer_than.as_deref(), since.as_deref(), older_than.as_deref())
+        }
+        Some(Commands::Touch { name, path }) => cmd_touch(&name, &path),
+        Some(Commands::ResetBase { name, side }) => cmd_reset_base(&name, side),
+        Some(Commands::RestoreFromSsd { name, dry_run, overwrite }) => {
+            cmd_restore_from_ssd(&name, dry_run, overwrite)
+        }
+        Some(Commands::RestoreArchive { name, path, date, list }) => {
+            cmd_restore_archive(name.as_deref(), &path, date.as_deref(), list)
+        }
         Some(Commands::IgnoreReset) => cmd_ignore_reset(),
         Some(Commands::IgnoreList) => cmd_ignore_list(),
         Some(Commands::IgnoreAdd { patterns }) => cmd_ignore_add(&patterns),
+        Some(Commands::IgnoreValidate) => cmd_ignore_validate(),
         Some(Commands::IgnoreRemove { patterns }) => cmd_ignore_remove(&patterns),
     }
 }
@@ -146,7 +568,7 @@ fn cmd_init(name: &str) -> Result<()> {
     Ok(())
 }
 
-fn cmd_add(local: &str, ssd: &str, name: &str) -> Result<()> {
+fn cmd_add(local: &str, ssd: &str, name: &str, remember: bool) -> Result<()> {
     let mut config = AppConfig::load()?;
 
     // Validate SSD path is absolute
@@ -169,8 +591,17 @@ fn cmd_add(local: &str, ssd: &str, name: &str) -> Result<()> {
         name: Some(name.to_string()),
         local: local.to_string(),
         ssd: ssd.to_string(),
+        extensions: vec![],
+        log: true,
+        max_files: None,
+        conflict: None,
+        drive_id: None,
+        drive_ids: BTreeMap::new(),
     });
     config.save()?;
+    if remember {
+        remember_ssd_mount(Path::new(ssd).parent());
+    }
 
     println!("Added sync mapping:");
     println!("  Name:  {}", name);
@@ -179,6 +610,71 @@ fn cmd_add(local: &str, ssd: &str, name: &str) -> Result<()> {
     Ok(())
 }
 
+/// `add --local <pattern> --ssd-template <template>`：每个匹配的目录添加一个映射，已存在的跳过
+fn cmd_add_glob(pattern: &str, ssd_dir: Option<&str>, template: &str, remember: bool) -> Result<()> {
+    let mut config = AppConfig::load()?;
+    let matches = glob::expand_dirs(pattern)?;
+    if matches.is_empty() {
+        anyhow::bail!("No folders match '{}'", pattern);
+    }
+    // 相对模板的 SSD 目录：--ssd，其次 [machine] ssd_mount，最后是上次同步或添加时用过的目录
+    let relative = Path::new(template).is_relative();
+    let ssd_dir = ssd_dir.map(str::to_string).or_else(|| config.machine.ssd_mount.clone()).or_else(|| {
+        let dir = state::State::load().last_ssd_mount.filter(|_| relative)?;
+        outln!("  Using last used SSD directory {} (pass --ssd to choose another)", dir);
+        Some(dir)
+    });
+
+    let mut created = 0;
+    let mut skipped = 0;
+    for local in &matches {
+        let rendered = glob::render_template(template, local)?;
+        let ssd = if Path::new(&rendered).is_absolute() {
+            PathBuf::from(&rendered)
+        } else {
+            let Some(dir) = &ssd_dir else {
+                anyhow::bail!("'{}' is a relative SSD path; pass --ssd <SSD directory>", rendered);
+            };
+            Path::new(dir).join(&rendered)
+        };
+        if !ssd.is_absolute() {
+            anyhow::bail!("SSD path must be an absolute path, got: '{}'", ssd.display());
+        }
+        let name = ssd
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .with_context(|| format!("Cannot derive a mapping name from '{}'", ssd.display()))?;
+        let local = local.to_string_lossy().to_string();
+
+        if config.find_mapping_by_name(&name).is_some() || config.sync.iter().any(|m| m.local == local) {
+            outln!("  Skipped {}: already configured ({})", name, local);
+            skipped += 1;
+            continue;
+        }
+        outln!("  Added   {}: {} ↔ {}", name, local, ssd.display());
+        config.sync.push(config::SyncMapping {
+            name: Some(name),
+            local,
+            ssd: ssd.to_string_lossy().to_string(),
+            extensions: vec![],
+            log: true,
+            max_files: None,
+            conflict: None,
+            drive_id: None,
+            drive_ids: BTreeMap::new(),
+        });
+        created += 1;
+    }
+    if created > 0 {
+        config.save()?;
+        if remember && relative {
+            remember_ssd_mount(ssd_dir.as_deref().map(Path::new));
+        }
+    }
+    outln!("Created {} mapping(s), skipped {} already configured.", created, skipped);
+    Ok(())
+}
+
 fn cmd_remove(name: &str) -> Result<()> {
     let mut config = AppConfig::load()?;
     let before = config.sync.len();
@@ -193,13 +689,132 @@ fn cmd_remove(name: &str) -> Result<()> {
     Ok(())
 }
 
-fn cmd_list() -> Result<()> {
+fn cmd_edit(target: &str, edit: config::MappingEdit) -> Result<()> {
+    if edit.name.is_none() && edit.local.is_none() && edit.ssd.is_none() {
+        anyhow::bail!("Nothing to change; pass --name, --local or --ssd");
+    }
+    let mut config = AppConfig::load()?;
+    let (before, after) = config.edit_mapping(target, edit)?;
+    config.save()?;
+
+    let show = |old: &str, new: &str| if old == new { new.to_string() } else { format!("{} → {}", old, new) };
+    outln!("Updated sync mapping:");
+    outln!("  Name:  {}", show(before.name.as_deref().unwrap_or("-"), after.name.as_deref().unwrap_or("-")));
+    outln!("  Local: {}", show(&before.local, &after.local));
+    outln!("  SSD:   {}", show(&before.ssd, &after.ssd));
+
+    // 快照按 SSD 路径命名：换了 SSD 文件夹后旧快照不再使用，下次同步按首次同步处理
+    if before.ssd != after.ssd {
+        outln!(
+            "  ⚠ The snapshots of this mapping are named after its old SSD folder ({}) and are no longer \
+             used; the next sync is handled as a first sync.",
+            before.ssd
+        );
+    } else if before.local != after.local {
+        outln!(
+            "  ⚠ The next sync compares {} with the last synced state; files missing there are deleted \
+             from the SSD. Check it with `ssd-syncer status` first.",
+            after.local
+        );
+    }
+    Ok(())
+}
+
+fn cmd_export_config(output: &Path) -> Result<()> {
+    // 导出配置文件中的原样内容（不受 --machine / --ssd-profile 影响）
+    let config = AppConfig::load_from(&AppConfig::config_path()?)?;
+    std::fs::write(output, config.to_portable()?)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    println!(
+        "Exported {} mapping(s) and {} ignore pattern(s) to {}",
+        config.sync.len(),
+        config.ignore.patterns.len(),
+        output.display()
+    );
+    println!("Run `ssd-syncer import-config --input {}` on the other machine.", output.display());
+    Ok(())
+}
+
+fn cmd_import_config(input: &Path) -> Result<()> {
+    let mut config = AppConfig::load()?;
+    let content = std::fs::read_to_string(input)
+        .with_context(|| format!("Failed to read {}", input.display()))?;
+    let portable: config::PortableConfig =
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", input.display()))?;
+    let report = config.import(portable);
+
+    for label in &report.added {
+        println!("  + Mapping {}", label);
+    }
+    for reason in &report.skipped {
+        println!("  - Skipped {}", reason);
+    }
+    for pattern in &report.patterns {
+        println!("  + Ignore pattern {}", pattern);
+    }
+    if report.added.is_empty() && report.patterns.is_empty() {
+        println!("Nothing to import.");
+        return Ok(());
+    }
+    config.save()?;
+    println!(
+        "Imported {} mapping(s) and {} ignore pattern(s).",
+        report.added.len(),
+        report.patterns.len()
+    );
+    Ok(())
+}
+
+/// Machine-readable view of the config for `list --format json`
+#[derive(Serialize)]
+struct ListReport<'a> {
+    machine: &'a str,
+    ssd_mount: Option<&'a str>,
+    ssd_profiles: &'a BTreeMap<String, config::SsdProfile>,
+    conflict_strategy: &'a config::ConflictStrategy,
+    ignore_patterns: &'a [String],
+    mappings: &'a [config::SyncMapping],
+}
+
+/// 输出实际生效的配置：未写入配置文件的选项显示其默认值
+fn cmd_config_show(format: OutputFormat) -> Result<()> {
+    let config = AppConfig::load()?;
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&config)?),
+        OutputFormat::Text => {
+            println!("# Effective configuration (loaded from {})", AppConfig::config_path()?.display());
+            print!("{}", toml::to_string_pretty(&config)?);
+        }
+    }
+    Ok(())
+}
+
+fn cmd_list(format: OutputFormat) -> Result<()> {
     let config = AppConfig::load()?;
 
+    if format == OutputFormat::Json {
+        let report = ListReport {
+            machine: &config.machine.name,
+            ssd_mount: config.machine.ssd_mount.as_deref(),
+            ssd_profiles: &config.ssd,
+            conflict_strategy: &config.conflict.strategy,
+            ignore_patterns: &config.ignore.patterns,
+            mappings: &config.sync,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
     println!("Machine: {}", config.machine.name);
     if let Some(ref ssd) = config.machine.ssd_mount {
         println!("Default SSD mount: {}", ssd);
     }
+    if !config.ssd.is_empty() {
+        println!("SSD profiles (--ssd-profile):");
+        for (name, profile) in &config.ssd {
+            println!("  {}: {}", name, profile.mount);
+        }
+    }
     println!("Conflict strategy: {:?}", config.conflict.strategy);
     println!();
 
@@ -217,6 +832,12 @@ fn cmd_list() -> Result<()> {
         }
         println!("     Local: {}", mapping.local);
         println!("     SSD:   {}", mapping.ssd);
+        if !mapping.extensions.is_empty() {
+            println!("     Extensions: {}", mapping.extensions.join(", "));
+        }
+        if let Some(ref strategy) = mapping.conflict {
+            println!("     Conflict strategy: {:?}", strategy);
+        }
     }
 
     println!();
@@ -224,6 +845,28 @@ fn cmd_list() -> Result<()> {
     Ok(())
 }
 
+/// `set-ssd [--profile NAME] PATH`
+fn cmd_set_ssd(path: &str, profile: Option<&str>) -> Result<()> {
+    let mut config = AppConfig::load()?;
+    if !Path::new(path).is_absolute() {
+        anyhow::bail!("SSD path must be an absolute path, got: '{}'", path);
+    }
+    match profile.unwrap_or(config::DEFAULT_SSD_PROFILE) {
+        config::DEFAULT_SSD_PROFILE => config.machine.ssd_mount = Some(path.to_string()),
+        "" => anyhow::bail!("Profile name must not be empty"),
+        name => {
+            config.ssd.insert(name.to_string(), config::SsdProfile { mount: path.to_string() });
+        }
+    }
+    config.save()?;
+    println!(
+        "SSD profile '{}' set to {}",
+        profile.unwrap_or(config::DEFAULT_SSD_PROFILE),
+        path
+    );
+    Ok(())
+}
+
 /// Resolve mapping(s) by name. If name is given, find that mapping.
 /// If name is None and only one mapping exists, auto-select it.
 /// Returns (ssd_path_string, Vec of matching mappings).
@@ -250,182 +893,779 @@ fn resolve_mappings<'a>(name: Option<&str>, config: &'a AppConfig) -> Result<(St
     }
 }
 
-fn cmd_sync(name: Option<&str>, dry_run: bool, verbose: bool) -> Result<()> {
+/// Like [`resolve_mappings`], for commands that take several names or glob patterns: every
+/// mapping matching any of them is selected. A single plain name behaves as before.
+fn resolve_mapping_patterns<'a>(names: &[String], config: &'a AppConfig) -> Result<(String, Vec<&'a config::SyncMapping>)> {
+    match names {
+        [] => resolve_mappings(None, config),
+        [name] if !name.contains(['*', '?']) => resolve_mappings(Some(name), config),
+        _ => {
+            let mappings = config.filter_mappings(names);
+            let Some(first) = mappings.first() else {
+                anyhow::bail!(
+                    "No mapping matches {}. Use `ssd-syncer list` to see configured mappings.",
+                    names.iter().map(|n| format!("'{}'", n)).collect::<Vec<_>>().join(", ")
+                );
+            };
+            Ok((first.ssd.clone(), mappings))
+        }
+    }
+}
+
+fn cmd_sync(args: &SyncArgs) -> Result<()> {
+    let dry_run = args.dry_run;
     let start_time = Instant::now();
     let config = AppConfig::load()?;
-    let (ssd_mount_str, mappings) = resolve_mappings(name, &config)?;
-    let ssd_path = Path::new(&ssd_mount_str);
+    let names: Vec<String> = args.names.iter().chain(&args.name).cloned().collect();
+    let (_, mappings) = resolve_mapping_patterns(&names, &config)?;
 
-    if !ssd_path.exists() {
-        anyhow::bail!("SSD mount point does not exist: {}", ssd_mount_str);
-    }
+    // 选中多个映射时，每个映射的快照和日志都在它自己的 SSD 文件夹里
+    let mut new_drive_ids = vec![];
+    for mapping in &mappings {
+        let ssd_path = Path::new(&mapping.ssd);
+        if !ssd_path.exists() {
+            anyhow::bail!("SSD mount point does not exist: {}", mapping.ssd);
+        }
+
+        // Ensure .ssd-syncer directory on SSD
+        let syncer_dir = AppConfig::ssd_syncer_dir(ssd_path);
+        if !syncer_dir.exists() {
+            std::fs::create_dir_all(&syncer_dir)?;
+        }
 
-    // Ensure .ssd-syncer directory on SSD
-    let syncer_dir = AppConfig::ssd_syncer_dir(ssd_path);
-    if !syncer_dir.exists() {
-        std::fs::create_dir_all(&syncer_dir)?;
+        // 首次同步（或旧版本的配置）时记下 SSD 文件夹的 ID，之后同一路径换了硬盘就能发现
+        // 每块 SSD（--ssd-profile）分别记录 ID：备用盘上的同名文件夹有自己的 ID
+        if let Some(id) = drive_id::verify(ssd_path, mapping.drive_id_on(config.ssd_profile()), args.force)? {
+            if !dry_run {
+                drive_id::write(ssd_path, &id)?;
+                new_drive_ids.push((mapping.ssd.clone(), id));
+            }
+        }
+    }
+    if !new_drive_ids.is_empty() {
+        let mut updated = config.clone();
+        for (ssd, id) in new_drive_ids {
+            if let Some(mapping) = updated.sync.iter_mut().find(|m| m.ssd == ssd) {
+                mapping.set_drive_id_on(config.ssd_profile(), id);
+            }
+        }
+        updated.save()?;
     }
 
-    let ignore = IgnoreMatcher::new(&config.ignore.patterns);
+    let ignore = IgnoreMatcher::new(&config.ignore.patterns_with(&args.exclude));
+    let max_file_size = args
+        .max_file_size
+        .as_deref()
+        .map(|s| units::parse_size(s).context("Invalid --max-file-size"))
+        .transpose()?;
 
     if dry_run {
-        println!("=== DRY RUN (no changes will be made) ===");
-        println!();
+        outln!("=== DRY RUN (no changes will be made) ===");
+        outln!();
     }
 
     let mut total_actions = 0;
+    let mut total_deferred = 0;
+    let mut declined = 0;
+    let mut conflict_failures = 0;
+    // 所有映射的错误，最后统一汇总输出（映射名 → 错误）
+    let mut all_errors: Vec<(String, Vec<String>)> = vec![];
+    let mut summary: Vec<SummaryRow> = vec![];
+    // 时钟偏差按硬盘只警告一次（同一块 SSD 上的多个映射）
+    let mut drift_warned: BTreeSet<String> = BTreeSet::new();
 
     for mapping in &mappings {
         let label = mapping.name.as_deref().unwrap_or(&mapping.ssd);
-        println!("━━━ Syncing: {} ↔ {} ━━━", mapping.local, label);
+        let ssd_path = Path::new(&mapping.ssd);
+        let drive = drive_key(ssd_path);
+        if !drift_warned.contains(&drive) && warn_clock_drift(ssd_path, &config.conflict_strategy_for(mapping)) {
+            drift_warned.insert(drive);
+        }
+        outln!("━━━ Syncing: {} ↔ {} ━━━", mapping.local, label);
+        let mapping_start = Instant::now();
+        let mut row = SummaryRow { label: label.to_string(), ..Default::default() };
 
         let local_path = Path::new(&mapping.local);
         if !local_path.exists() {
-            println!("  ⚠ Local path does not exist, skipping: {}", mapping.local);
+            outln!("  ⚠ Local path does not exist, skipping: {}", mapping.local);
+            row.outcome = Some("skipped");
+            summary.push(row);
             continue;
         }
 
-        match sync_engine::sync_one_mapping(
+        warn_ignored_roots(mapping, &ignore);
+
+        let mut opts = sync_options(&config, mapping, &ignore, dry_run, args.verbose)?;
+        opts.summary_only = args.summary_only;
+        opts.force = args.force;
+        opts.only_conflicts = args.only_conflicts;
+        opts.direction = if args.push {
+            Some(sync_engine::SyncDirection::Push)
+        } else if args.pull {
+            Some(sync_engine::SyncDirection::Pull)
+        } else {
+            None
+        };
+        opts.trash |= args.delete_to_trash;
+        opts.confirm_answer = (args.yes || args.no).then_some(args.yes);
+        opts.explain = args.explain;
+        opts.scan.checksum = args.checksum;
+        if max_file_size.is_some() {
+            opts.scan.max_file_size = max_file_size;
+        }
+        if let Some(n) = args.threads {
+            opts.scan.threads = n as usize;
+        }
+        opts.subpath = args.subpath.as_deref().map(normalize_subpath).transpose()?;
+        if let Some(sub) = &opts.subpath {
+            outln!("  Limited to: {}/", sub);
+        }
+        if let Some(n) = args.concurrency {
+            opts.copy_concurrency = n as usize;
+        }
+
+        let mut progress = TerminalProgress::new(&opts);
+        let synced = sync_engine::sync_one_mapping(
             local_path,
             ssd_path,
             &mapping.ssd,
             &config.machine.name,
-            &ignore,
-            &config.conflict.strategy,
-            dry_run,
-            verbose,
-        ) {
-            Ok((_plan, result)) => {
+            &opts,
+            &mut |p| progress.update(p),
+        );
+        progress.finish();
+        match synced {
+            Ok((plan, result)) => {
                 print_sync_result(&result);
+                if args.verbose || args.profile {
+                    print_copy_profile(&result);
+                }
+                if !dry_run && !args.no_remember {
+                    remember_ssd_mount(Path::new(&mapping.ssd).parent());
+                }
                 total_actions += result.total_actions();
+                if !dry_run {
+                    log_mapping_sync(&config, mapping, ssd_path, &plan, &result)?;
+                }
+                total_deferred += result.deferred + result.staged_deletes;
 
-                if !result.errors.is_empty() {
-                    println!("  Errors:");
-                    for err in &result.errors {
-                        println!("    - {}", err);
+                let mut errors = result.errors;
+                if !errors.is_empty() {
+                    outln!("  Errors:");
+                    for err in &errors {
+                        outln!("    - {}", err);
                     }
                 }
+                if args.verify_after && !dry_run {
+                    errors.extend(verify_converged(local_path, ssd_path, mapping, &config, &opts, result.deferred + result.staged_deletes)?);
+                }
+                row.to_ssd = result.copied_to_ssd;
+                row.to_local = result.copied_to_local;
+                row.deleted = result.deleted_from_ssd + result.deleted_from_local;
+                row.conflicts = result.conflicts;
+                row.errors = errors.len();
+                if !errors.is_empty() {
+                    all_errors.push((label.to_string(), errors));
+                }
+            }
+            Err(e) if e.downcast_ref::<sync_engine::SyncDeclined>().is_some() => {
+                outln!("  Skipped: {}", e);
+                row.outcome = Some("skipped");
+                declined += 1;
             }
             Err(e) => {
-                println!("  Error syncing '{}': {}", mapping.ssd, e);
+                if e.downcast_ref::<sync_engine::ConflictsFound>().is_some() {
+                    conflict_failures += 1;
+                }
+                outln!("  Error syncing '{}': {}", mapping.ssd, e);
+                all_errors.push((label.to_string(), vec![format!("Sync {}", e)]));
+                row.outcome = Some("failed");
+                row.errors = 1;
             }
         }
+        row.elapsed = mapping_start.elapsed();
+        summary.push(row);
 
-        println!();
+        outln!();
     }
 
-    // Append to sync log
-    if !dry_run && total_actions > 0 {
-        append_sync_log(ssd_path, &config.machine.name, total_actions)?;
-    }
+    print_summary_table(&summary);
 
-    if total_actions == 0 {
-        println!("Everything is in sync!");
+    if total_actions == 0 && total_deferred == 0 && conflict_failures == 0 && declined == 0 {
+        outln!("Everything is in sync!");
     }
 
     // 显示总耗时
     let elapsed = start_time.elapsed();
     let secs = elapsed.as_secs();
     if secs >= 60 {
-        println!("Total time: {}m {:.1}s", secs / 60, elapsed.as_secs_f64() % 60.0);
+        outln!("Total time: {}m {:.1}s", secs / 60, elapsed.as_secs_f64() % 60.0);
     } else {
-        println!("Total time: {:.1}s", elapsed.as_secs_f64());
+        outln!("Total time: {:.1}s", elapsed.as_secs_f64());
     }
 
+    let error_count: usize = all_errors.iter().map(|(_, errors)| errors.len()).sum();
+    if error_count > 0 {
+        print_error_report(&all_errors, error_count);
+    }
+
+    if conflict_failures > 0 {
+        anyhow::bail!(
+            "Sync aborted: {} mapping(s) had conflicts ([conflict] strategy = \"fail\")",
+            conflict_failures
+        );
+    }
+    if error_count > 0 {
+        anyhow::bail!("Sync finished with {} error(s)", error_count);
+    }
     Ok(())
 }
 
-fn cmd_status(name: Option<&str>) -> Result<()> {
-    let config = AppConfig::load()?;
-    let (ssd_mount_str, mappings) = resolve_mappings(name, &config)?;
-    let ssd_path = Path::new(&ssd_mount_str);
-
-    if !ssd_path.exists() {
-        anyhow::bail!("SSD mount point does not exist: {}", ssd_mount_str);
+/// 每个映射记一行；设置了 log = false 的映射不记录
+fn log_mapping_sync(
+    config: &AppConfig,
+    mapping: &config::SyncMapping,
+    ssd_path: &Path,
+    plan: &diff::SyncPlan,
+    result: &sync_engine::SyncResult,
+) -> Result<()> {
+    if !mapping.log || result.total_actions() == 0 {
+        return Ok(());
+    }
+    let label = mapping.name.as_deref().unwrap_or(&mapping.ssd);
+    let json = config.sync_settings.json_log;
+    let mut entry = sync_log::LogEntry::new(&config.machine.name, label, result);
+    if json {
+        entry = entry.with_changes(plan);
     }
+    sync_log::append_sync_log(ssd_path, &entry, json)
+}
+
+fn cmd_watch(name: Option<&str>, delay: Option<&str>) -> Result<()> {
+    use notify::Watcher;
 
+    let config = AppConfig::load()?;
+    let delay = match delay {
+        Some(delay) => std::time::Duration::from_secs(units::parse_duration(delay).context("Invalid --delay")?),
+        None => std::time::Duration::from_millis(config.watch.debounce_ms),
+    };
+    let mappings: Vec<&config::SyncMapping> = match name {
+        Some(n) => vec![config.find_mapping_by_name(n).ok_or_else(|| {
+            anyhow::anyhow!("No mapping found with name '{}'. Use `ssd-syncer list` to see configured mappings.", n)
+        })?],
+        None => config.sync.iter().collect(),
+    };
+    if mappings.is_empty() {
+        anyhow::bail!("No sync mappings configured. Use `ssd-syncer add` to add one.");
+    }
     let ignore = IgnoreMatcher::new(&config.ignore.patterns);
 
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("Failed to start the file watcher")?;
+    let mut roots = vec![];
+    let mut options = vec![];
     for mapping in &mappings {
-        let label = mapping.name.as_deref().unwrap_or(&mapping.ssd);
-        println!("━━━ Status: {} ↔ {} ━━━", mapping.local, label);
+        let opts = sync_options(&config, mapping, &ignore, false, false)?;
+        // 事件路径是解析后的真实路径（如 macOS 上的 /private/var），按真实路径匹配
+        let local = Path::new(&mapping.local);
+        let root = local.canonicalize().unwrap_or_else(|_| local.to_path_buf());
+        watcher
+            .watch(&root, notify::RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {}", mapping.local))?;
+        roots.push((root, opts.scan.clone()));
+        options.push(opts);
+    }
+    let mut scheduler = watch::SyncScheduler::new(roots, delay);
+    let mut waiting = vec![false; mappings.len()];
 
-        let local_path = Path::new(&mapping.local);
-        if !local_path.exists() {
-            println!("  ⚠ Local path does not exist: {}", mapping.local);
-            continue;
+    // 启动时先同步一次，之后只在本地文件变化时同步
+    for index in 0..mappings.len() {
+        scheduler.schedule_at(index, Instant::now());
+    }
+    outln!("Watching {} folder(s), press Ctrl-C to stop", mappings.len());
+
+    loop {
+        let timeout = scheduler
+            .next_deadline()
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+            .unwrap_or(std::time::Duration::from_secs(60));
+        match rx.recv_timeout(timeout) {
+            // 只关心内容和结构的变化；同步自身读取文件产生的访问事件不能再触发同步
+            Ok(Ok(event)) if !event.kind.is_access() => {
+                scheduler.on_event(&event.paths, Instant::now());
+            }
+            Ok(Ok(_)) | Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+            Ok(Err(e)) => log::warn!("File watcher error: {}", e),
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                anyhow::bail!("File watcher stopped unexpectedly")
+            }
         }
 
-        let ssd_folder = ssd_path.join(&mapping.ssd);
-        if !ssd_folder.exists() {
-            println!("  SSD folder does not exist yet (will be created on first sync)");
-            println!("  Local files will be copied to SSD");
+        for index in scheduler.take_due(Instant::now()) {
+            let mapping = mappings[index];
+            let label = mapping.name.as_deref().unwrap_or(&mapping.ssd);
+            let ssd_path = Path::new(&mapping.ssd);
+            // SSD 被拔出：暂停该映射，定期重试，重新连接后自动同步
+            if !ssd_path.exists() {
+                if !waiting[index] {
+                    outln!("[{}] SSD not connected, waiting for {}", chrono::Local::now().format("%H:%M:%S"), mapping.ssd);
+                    waiting[index] = true;
+                }
+                scheduler.retry_later(index, Instant::now());
+                continue;
+            }
+            // 同一路径上挂载的是另一块 SSD：不同步，等正确的硬盘接上（ID 由 sync 记录）
+            if let Err(e) = drive_id::verify(ssd_path, mapping.drive_id_on(config.ssd_profile()), false) {
+                if !waiting[index] {
+                    watch_error(&format!("[{}] {:#}", chrono::Local::now().format("%H:%M:%S"), e));
+                    waiting[index] = true;
+                }
+                scheduler.retry_later(index, Instant::now());
+                continue;
+            }
+            waiting[index] = false;
+
+            outln!("━━━ [{}] Syncing: {} ↔ {} ━━━", chrono::Local::now().format("%H:%M:%S"), mapping.local, label);
+            let mut progress = TerminalProgress::new(&options[index]);
+            let synced = sync_engine::sync_one_mapping(
+                Path::new(&mapping.local),
+                ssd_path,
+                &mapping.ssd,
+                &config.machine.name,
+                &options[index],
+                &mut |p| progress.update(p),
+            );
+            progress.finish();
+            let synced = synced.and_then(|(plan, result)| {
+                print_sync_result(&result);
+                if !result.errors.is_empty() {
+                    watch_error("  Errors:");
+                    for err in &result.errors {
+                        watch_error(&format!("    - {}", err));
+                    }
+                }
+                log_mapping_sync(&config, mapping, ssd_path, &plan, &result)
+            });
+            if let Err(e) = synced {
+                watch_error(&format!("  Error syncing '{}': {}", mapping.ssd, e));
+                if !ssd_path.exists() {
+                    scheduler.retry_later(index, Instant::now());
+                }
+            }
+            outln!();
+        }
+    }
+}
+
+/// Print an error of `watch`; with `--quiet`, where all other output is hidden, to stderr.
+fn watch_error(message: &str) {
+    if output::is_quiet() {
+        eprintln!("{}", output::text(message));
+    } else {
+        outln!("{}", message);
+    }
+}
+
+/// One row of the table printed at the end of `sync`.
+#[derive(Default)]
+struct SummaryRow {
+    label: String,
+    /// "skipped" or "failed" instead of counts
+    outcome: Option<&'static str>,
+    to_ssd: usize,
+    to_local: usize,
+    deleted: usize,
+    conflicts: usize,
+    errors: usize,
+    elapsed: std::time::Duration,
+}
+
+/// 每个映射一行的汇总表，便于同步多个映射后快速查看哪些有问题
+fn print_summary_table(rows: &[SummaryRow]) {
+    if rows.is_empty() {
+        return;
+    }
+    // 先转换符号再对齐，ASCII 模式下列宽同样正确
+    let cell = |s: &str| output::text(s).to_string();
+    let width = rows.iter().map(|r| r.label.chars().count()).max().unwrap_or(0).max(7);
+    outln!("━━━ Summary ━━━");
+    outln!(
+        "  {:<width$}  {:>8}  {:>8}  {:>7}  {:>9}  {:>6}  {:>7}",
+        "Mapping", cell("→ SSD"), cell("← Local"), "Deleted", "Conflicts", "Errors", "Time"
+    );
+    for row in rows {
+        let time = format!("{:.1}s", row.elapsed.as_secs_f64());
+        match row.outcome {
+            Some(outcome) => outln!(
+                "  {:<width$}  {:<39}  {:>6}  {:>7}",
+                row.label, outcome, row.errors, time
+            ),
+            None => outln!(
+                "  {:<width$}  {:>8}  {:>8}  {:>7}  {:>9}  {:>6}  {:>7}",
+                row.label, row.to_ssd, row.to_local, row.deleted, row.conflicts, row.errors, time
+            ),
+        }
+    }
+    outln!();
+}
+
+/// Errors of every mapping, grouped by mapping and then by kind (the action that failed,
+/// or `Sync` when the whole mapping failed).
+fn print_error_report(all_errors: &[(String, Vec<String>)], error_count: usize) {
+    use std::fmt::Write;
+
+    let mut report = String::new();
+    let _ = writeln!(report);
+    let _ = writeln!(report, "{}", output::text(&format!("━━━ Errors ({}) ━━━", error_count)));
+    for (label, errors) in all_errors {
+        let _ = writeln!(report, "  {} ({}):", label, errors.len());
+        let mut by_kind: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for err in errors {
+            // SyncResult 中的错误格式为 "<Kind> <path>: <message>"
+            let (kind, detail) = err.split_once(' ').unwrap_or(("Other", err));
+            by_kind.entry(kind).or_default().push(detail);
+        }
+        for (kind, details) in by_kind {
+            let _ = writeln!(report, "    {} ({}):", kind, details.len());
+            for detail in details {
+                let _ = writeln!(report, "      - {}", detail.replace('\n', "\n        "));
+            }
+        }
+    }
+    // --quiet 时其他输出都不显示，错误改写到 stderr
+    if output::is_quiet() {
+        eprint!("{}", report);
+    } else {
+        print!("{}", report);
+    }
+}
+
+/// `sync --verify-after`：重新扫描两侧，计划应为空；剩余的差异作为错误返回
+fn verify_converged(
+    local_path: &Path,
+    ssd_path: &Path,
+    mapping: &config::SyncMapping,
+    config: &AppConfig,
+    opts: &sync_engine::SyncOptions,
+    deferred: usize,
+) -> Result<Vec<String>> {
+    if deferred > 0 {
+        outln!("  Verification skipped: {} change(s) were deferred to the next sync", deferred);
+        return Ok(vec![]);
+    }
+    let plan = sync_engine::preview_plan(local_path, ssd_path, &mapping.ssd, &config.machine.name, opts)?;
+    if plan.actions.is_empty() {
+        outln!("  ✓ Verified: local and SSD are in sync");
+        return Ok(vec![]);
+    }
+    outln!("  ⚠ Verification found {} difference(s) left after the sync:", plan.actions.len());
+    let mut residual = vec![];
+    for entry in &plan.actions {
+        let action = output::text(action_symbol(&entry.action)).trim_end().to_string();
+        outln!("    {} {}", action, entry.display_path());
+        residual.push(format!("Verify {}: still pending ({})", entry.display_path(), action));
+    }
+    Ok(residual)
+}
+
+/// Record `dir` as the SSD directory for `add --ssd-template` runs without `--ssd`.
+fn remember_ssd_mount(dir: Option<&Path>) {
+    let Some(dir) = dir.filter(|d| !d.as_os_str().is_empty()) else {
+        return;
+    };
+    let mut state = state::State::load();
+    let dir = dir.to_string_lossy().to_string();
+    if state.last_ssd_mount.as_ref() == Some(&dir) {
+        return;
+    }
+    state.last_ssd_mount = Some(dir);
+    if let Err(e) = state.save() {
+        log::warn!("Failed to remember last SSD directory: {}", e);
+    }
+}
+
+fn sync_options(
+    config: &AppConfig,
+    mapping: &config::SyncMapping,
+    ignore: &IgnoreMatcher,
+    dry_run: bool,
+    verbose: bool,
+) -> Result<sync_engine::SyncOptions> {
+    // 持久化哈希缓存在第一次构建扫描选项时打开，之后的映射共用
+    if config.performance.hash_cache {
+        let max_bytes = config.performance.hash_cache_max_mb.saturating_mul(1024 * 1024);
+        hash_store::init(&hash_store::store_path()?, max_bytes)?;
+    }
+    let case_insensitive = config
+        .ignore
+        .case_insensitive
+        .unwrap_or_else(|| ignore::detect_case_insensitive(Path::new(&mapping.local)));
+    let ignore = ignore
+        .clone()
+        .with_ignore_file(Path::new(&mapping.local))?
+        .with_case_insensitive(case_insensitive);
+    // 两者都会拼进文件名：不能为空，也不能含路径分隔符
+    for (key, value) in [("[io] temp_suffix", &config.io.temp_suffix), ("[conflict] marker", &config.conflict.marker)] {
+        if value.is_empty() || value.contains(['/', '\\']) {
+            anyhow::bail!("Invalid {} '{}': must be non-empty and must not contain '/' or '\\'", key, value);
+        }
+    }
+    let mut scan = ScanOptions::for_mapping(ignore, mapping).with_filters(&config.filters)?;
+    scan.temp_suffix = config.io.temp_suffix.clone();
+    scan.follow_symlinks = config.scan.follow_symlinks;
+    let size = |value: &Option<String>, key: &str| -> Result<Option<u64>> {
+        value
+            .as_deref()
+            .map(|s| units::parse_size(s).with_context(|| format!("Invalid [scan] {}", key)))
+            .transpose()
+    };
+    scan.max_file_size = size(&config.scan.max_file_size, "max_file_size")?;
+    scan.ignore_larger_than = size(&config.scan.ignore_larger_than, "ignore_larger_than")?;
+    scan.ignore_smaller_than = size(&config.scan.ignore_smaller_than, "ignore_smaller_than")?;
+    scan.hash_algorithm = config.scan.hash_algorithm;
+    if let (Some(min), Some(max)) = (scan.ignore_smaller_than, scan.ignore_larger_than) {
+        if min > max {
+            anyhow::bail!("[scan] ignore_smaller_than must not be larger than ignore_larger_than; no file would be synced");
+        }
+    }
+    Ok(sync_engine::SyncOptions {
+        scan,
+        conflict_strategy: config.conflict_strategy_for(mapping),
+        change_detection: config.sync_settings.change_detection,
+        version_counters: config.sync_settings.version_counters,
+        empty_dirs: config.filters.empty_dirs,
+        copy_concurrency: config.performance.copy_concurrency.max(1),
+        safety: config.safety.clone(),
+        compat: config.compat.clone(),
+        wait_for_ssd: config
+            .io
+            .wait_for_ssd
+            .as_deref()
+            .map(|s| units::parse_duration(s).context("Invalid [io] wait_for_ssd"))
+            .transpose()?
+            .map(std::time::Duration::from_secs),
+        retry: sync_engine::RetryPolicy {
+            retries: config.io.retries,
+            delay: std::time::Duration::from_millis(config.io.retry_delay_ms),
+        },
+        conflict_marker: config.conflict.marker.clone(),
+        trash: config.conflict.trash,
+        dry_run,
+        verbose,
+        summary_only: false,
+        force: false,
+        only_conflicts: false,
+        direction: None,
+        explain: false,
+        subpath: None,
+        confirm: config.sync_settings.confirm,
+        confirm_answer: None,
+    })
+}
+
+/// `--subpath` as a '/'-separated path relative to the mapping root.
+fn normalize_subpath(subpath: &str) -> Result<String> {
+    let parts: Vec<&str> = subpath
+        .split(['/', '\\'])
+        .filter(|p| !p.is_empty() && *p != ".")
+        .collect();
+    if parts.is_empty() || parts.contains(&"..") {
+        anyhow::bail!("Invalid --subpath '{}': use a folder inside the mapping, e.g. 'app/src'", subpath);
+    }
+    Ok(parts.join("/"))
+}
+
+/// Snapshots written this far "in the future" mean some machine's clock is off.
+const CLOCK_DRIFT_WARN_SECS: i64 = 10 * 60;
+
+/// 如果 SSD 上任一机器的最近同步时间明显晚于本机当前时间，说明机器之间时钟不一致，
+/// 此时基于 mtime 的冲突解决（newer-wins）不可靠。Returns whether it warned.
+fn warn_clock_drift(ssd_mount: &Path, strategy: &config::ConflictStrategy) -> bool {
+    let snapshots_root = AppConfig::ssd_syncer_dir(ssd_mount).join("snapshots");
+    let Some((machine, synced_at)) = Snapshot::latest_sync_time(&snapshots_root) else {
+        return false;
+    };
+    let ahead = (synced_at - chrono::Utc::now()).num_seconds();
+    if ahead <= CLOCK_DRIFT_WARN_SECS {
+        return false;
+    }
+    outln!(
+        "⚠ WARNING: machine '{}' last synced at {}, {} ahead of this machine's clock.",
+        machine,
+        synced_at.format("%Y-%m-%d %H:%M:%S UTC"),
+        format_drift(ahead)
+    );
+    outln!("  One of the machines has a wrong system clock, so modification times are not comparable across machines.");
+    if *strategy == config::ConflictStrategy::NewerWins {
+        outln!("  Conflict strategy is 'newer-wins': consider 'both' until the clocks are fixed.");
+    } else {
+        outln!("  Avoid the 'newer-wins' conflict strategy until the clocks are fixed.");
+    }
+    outln!();
+    true
+}
+
+/// Identifies the drive `path` is on: the filesystem's device ID on Unix, otherwise the path.
+fn drive_key(path: &Path) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(meta) = std::fs::metadata(path) {
+            return format!("dev:{}", meta.dev());
+        }
+    }
+    path.to_string_lossy().to_string()
+}
+
+fn format_drift(secs: i64) -> String {
+    if secs >= 3600 {
+        format!("{}h {}m", secs / 3600, (secs % 3600) / 60)
+    } else {
+        format!("{}m", secs / 60)
+    }
+}
+
+/// 映射根目录名命中忽略规则通常是配置错误（例如忽略了 `build` 又映射到 `build` 目录）：
+/// 本映射仍会同步，但任何包含该目录的映射都会跳过它。
+fn warn_ignored_roots(mapping: &config::SyncMapping, ignore: &IgnoreMatcher) {
+    for (side, root) in [("Local", &mapping.local), ("SSD", &mapping.ssd)] {
+        let Some(name) = Path::new(root).file_name() else {
             continue;
+        };
+        let name = name.to_string_lossy();
+        if let Some(pattern) = ignore.matched_pattern(&name) {
+            outln!(
+                "  ⚠ WARNING: {} folder name '{}' matches ignore pattern '{}'.",
+                side, name, pattern
+            );
+            outln!(
+                "    Files in this mapping still sync, but any mapping that contains this folder will skip it."
+            );
+            outln!("    Rename the folder or adjust the ignore list (`ssd-syncer ignore-remove`) if this is unintended.");
         }
+    }
+}
 
-        let snapshot_dir =
-            AppConfig::ssd_snapshots_dir(ssd_path, &config.machine.name);
-        let snapshot_file = snapshot_dir.join(Snapshot::snapshot_filename(&mapping.ssd));
-        let base = Snapshot::load_or_empty(&snapshot_file, &mapping.ssd, &config.machine.name)?;
+/// Machine-readable status of one mapping for `status --format json`
+#[derive(Serialize)]
+struct StatusReport<'a> {
+    mapping: Option<&'a str>,
+    local: &'a str,
+    ssd: &'a str,
+    /// "in-sync", "pending", "local-missing" or "ssd-missing" (not synced yet)
+    state: &'static str,
+    #[serde(flatten)]
+    counts: diff::PlanCounts,
+}
 
-        let (local_snap, ssd_snap) = scanner::scan_pair(
-            local_path,
-            &ssd_folder,
-            &mapping.ssd,
-            &config.machine.name,
-            &ignore,
-            Some(&base),
-            Some(&base),
-        )?;
+fn cmd_status(
+    names: &[String],
+    format: OutputFormat,
+    exclude: &[String],
+    checksum: bool,
+    threads: Option<u64>,
+) -> Result<()> {
+    let config = AppConfig::load()?;
+    let (_, mappings) = resolve_mapping_patterns(names, &config)?;
+    for mapping in &mappings {
+        if !Path::new(&mapping.ssd).exists() {
+            anyhow::bail!("SSD mount point does not exist: {}", mapping.ssd);
+        }
+    }
 
-        let local_changes = diff::compute_changes(&base, &local_snap);
-        let ssd_changes = diff::compute_changes(&base, &ssd_snap);
+    let ignore = IgnoreMatcher::new(&config.ignore.patterns_with(exclude));
+    let json = format == OutputFormat::Json;
+    let mut reports = vec![];
 
-        let plan = diff::build_sync_plan(&local_changes, &ssd_changes);
+    for mapping in &mappings {
+        let mut report = StatusReport {
+            mapping: mapping.name.as_deref(),
+            local: &mapping.local,
+            ssd: &mapping.ssd,
+            state: "in-sync",
+            counts: diff::PlanCounts::default(),
+        };
+        let label = mapping.name.as_deref().unwrap_or(&mapping.ssd);
+        if !json {
+            outln!("━━━ Status: {} ↔ {} ━━━", mapping.local, label);
+        }
 
-        if plan.actions.is_empty() {
-            println!("  In sync ✓");
-        } else {
-            let mut copy_to_ssd = 0;
-            let mut copy_to_local = 0;
-            let mut del_ssd = 0;
-            let mut del_local = 0;
-            let mut conflicts = 0;
-
-            for a in &plan.actions {
-                match &a.action {
-                    SyncAction::CopyToSsd => copy_to_ssd += 1,
-                    SyncAction::CopyToLocal => copy_to_local += 1,
-                    SyncAction::DeleteFromSsd => del_ssd += 1,
-                    SyncAction::DeleteFromLocal => del_local += 1,
-                    SyncAction::Conflict(_) => conflicts += 1,
-                }
+        let local_path = Path::new(&mapping.local);
+        if !local_path.exists() {
+            if !json {
+                outln!("  ⚠ Local path does not exist: {}", mapping.local);
             }
+            report.state = "local-missing";
+            reports.push(report);
+            continue;
+        }
+        let ssd_path = Path::new(&mapping.ssd);
 
-            if copy_to_ssd > 0 {
-                println!("  → {} file(s) to copy to SSD", copy_to_ssd);
-            }
-            if copy_to_local > 0 {
-                println!("  ← {} file(s) to copy to local", copy_to_local);
-            }
-            if del_ssd > 0 {
-                println!("  ✕ {} file(s) to delete from SSD", del_ssd);
-            }
-            if del_local > 0 {
-                println!("  ✕ {} file(s) to delete from local", del_local);
-            }
-            if conflicts > 0 {
-                println!("  ⚠ {} conflict(s)", conflicts);
-            }
+        // SSD 文件夹不存在时 preview_plan 按空目录计算（所有本地文件都将复制到 SSD）
+        let mut opts = sync_options(&config, mapping, &ignore, true, false)?;
+        opts.scan.checksum = checksum;
+        if let Some(n) = threads {
+            opts.scan.threads = n as usize;
+        }
+        let plan = sync_engine::preview_plan(
+            local_path,
+            ssd_path,
+            &mapping.ssd,
+            &config.machine.name,
+            &opts,
+        )?;
+        report.counts = plan.counts();
+        if !ssd_path.join(&mapping.ssd).exists() {
+            report.state = "ssd-missing";
+        } else if !plan.actions.is_empty() {
+            report.state = "pending";
         }
 
-        println!();
+        if !json {
+            print_status(&report);
+            outln!();
+        }
+        reports.push(report);
     }
 
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    }
     Ok(())
 }
 
-fn cmd_diff(name: Option<&str>) -> Result<()> {
+fn print_status(report: &StatusReport) {
+    let counts = &report.counts;
+    if report.state == "ssd-missing" {
+        outln!("  SSD folder does not exist yet (will be created on first sync)");
+        outln!("  {} local file(s) will be copied to SSD", counts.copy_to_ssd);
+        return;
+    }
+    if report.state == "in-sync" {
+        outln!("  In sync ✓");
+        return;
+    }
+    if counts.copy_to_ssd > 0 {
+        outln!("  {}", paint(&format!("→ {} file(s) to copy to SSD", counts.copy_to_ssd), Tone::ToSsd));
+    }
+    if counts.copy_to_local > 0 {
+        outln!("  {}", paint(&format!("← {} file(s) to copy to local", counts.copy_to_local), Tone::ToLocal));
+    }
+    if counts.del_ssd > 0 {
+        outln!("  {}", paint(&format!("✕ {} file(s) to delete from SSD", counts.del_ssd), Tone::Delete));
+    }
+    if counts.del_local > 0 {
+        outln!("  {}", paint(&format!("✕ {} file(s) to delete from local", counts.del_local), Tone::Delete));
+    }
+    if counts.renames > 0 {
+        outln!("  ↻ {} rename(s)", counts.renames);
+    }
+    if counts.conflicts > 0 {
+        outln!("  {}", paint(&format!("⚠ {} conflict(s)", counts.conflicts), Tone::Conflict));
+    }
+}
+
+fn cmd_diff(name: Option<&str>, output: Option<&Path>, exclude: &[String], names_only: bool) -> Result<()> {
     let config = AppConfig::load()?;
     let (ssd_mount_str, mappings) = resolve_mappings(name, &config)?;
     let ssd_path = Path::new(&ssd_mount_str);
@@ -434,70 +1674,596 @@ fn cmd_diff(name: Option<&str>) -> Result<()> {
         anyhow::bail!("SSD mount point does not exist: {}", ssd_mount_str);
     }
 
-    let ignore = IgnoreMatcher::new(&config.ignore.patterns);
+    let ignore = IgnoreMatcher::new(&config.ignore.patterns_with(exclude));
+    // --output：每个映射一节（"@@ 映射名"），跳过的映射以注释说明原因
+    let mut export = String::new();
 
     for mapping in &mappings {
         let label = mapping.name.as_deref().unwrap_or(&mapping.ssd);
-        println!("━━━ Diff: {} ↔ {} ━━━", mapping.local, label);
+        outln!("━━━ Diff: {} ↔ {} ━━━", mapping.local, label);
+        export.push_str(&format!("@@ {}\n", label));
 
         let local_path = Path::new(&mapping.local);
         if !local_path.exists() {
-            println!("  ⚠ Local path does not exist: {}", mapping.local);
+            outln!("  ⚠ Local path does not exist: {}", mapping.local);
+            export.push_str("# local path does not exist\n");
             continue;
         }
 
         let ssd_folder = ssd_path.join(&mapping.ssd);
         if !ssd_folder.exists() {
             println!("  SSD folder does not exist yet");
+            export.push_str("# SSD folder does not exist yet\n");
             continue;
         }
 
-        let snapshot_dir =
-            AppConfig::ssd_snapshots_dir(ssd_path, &config.machine.name);
-        let snapshot_file = snapshot_dir.join(Snapshot::snapshot_filename(&mapping.ssd));
-        let base = Snapshot::load_or_empty(&snapshot_file, &mapping.ssd, &config.machine.name)?;
-
-        let (local_snap, ssd_snap) = scanner::scan_pair(
+        let opts = sync_options(&config, mapping, &ignore, true, false)?;
+        let (plan, local_snap, ssd_snap) = sync_engine::preview_plan_with_sides(
             local_path,
-            &ssd_folder,
+            ssd_path,
             &mapping.ssd,
             &config.machine.name,
-            &ignore,
-            Some(&base),
-            Some(&base),
+            &opts,
         )?;
-
-        let local_changes = diff::compute_changes(&base, &local_snap);
-        let ssd_changes = diff::compute_changes(&base, &ssd_snap);
-
-        let plan = diff::build_sync_plan(&local_changes, &ssd_changes);
+        export.push_str(&plan.to_text());
 
         if plan.actions.is_empty() {
             println!("  No differences.");
         } else {
             for entry in &plan.actions {
-                let symbol = match &entry.action {
-                    SyncAction::CopyToSsd => "→ SSD  ",
-                    SyncAction::CopyToLocal => "← LOCAL",
-                    SyncAction::DeleteFromSsd => "✕ SSD  ",
-                    SyncAction::DeleteFromLocal => "✕ LOCAL",
-                    SyncAction::Conflict(_) => "⚠ CONFLICT",
+                let symbol = output::text(action_symbol(&entry.action));
+                let symbol = match action_tone(&entry.action) {
+                    Some(tone) => paint(&symbol, tone).into_owned(),
+                    None => symbol.into_owned(),
                 };
-                println!("  {} {}", symbol, entry.path);
+                match entry.file_details(&local_snap, &ssd_snap).filter(|_| !names_only) {
+                    Some(details) => println!("  {} {}  ({})", symbol, entry.display_path(), details),
+                    None => println!("  {} {}", symbol, entry.display_path()),
+                }
+                // 冲突：显示两侧相对上次同步（共同祖先）各自的变化
+                if let SyncAction::Conflict(info) = &entry.action {
+                    outln!("      local: {}", info.local_delta());
+                    outln!("      SSD:   {}", info.ssd_delta());
+                }
             }
         }
 
         println!();
     }
 
+    if let Some(path) = output {
+        std::fs::write(path, export).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("Plan written to {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Colour of an action in `diff`; renames stay uncoloured.
+fn action_tone(action: &SyncAction) -> Option<Tone> {
+    match action {
+        SyncAction::CopyToSsd => Some(Tone::ToSsd),
+        SyncAction::CopyToLocal => Some(Tone::ToLocal),
+        SyncAction::DeleteFromSsd | SyncAction::DeleteFromLocal => Some(Tone::Delete),
+        SyncAction::Conflict(_) => Some(Tone::Conflict),
+        SyncAction::RenameOnSsd { .. } | SyncAction::RenameOnLocal { .. } => None,
+    }
+}
+
+fn action_symbol(action: &SyncAction) -> &'static str {
+    match action {
+        SyncAction::CopyToSsd => "→ SSD  ",
+        SyncAction::CopyToLocal => "← LOCAL",
+        SyncAction::DeleteFromSsd => "✕ SSD  ",
+        SyncAction::DeleteFromLocal => "✕ LOCAL",
+        SyncAction::RenameOnSsd { .. } => "↻ SSD  ",
+        SyncAction::RenameOnLocal { .. } => "↻ LOCAL",
+        SyncAction::Conflict(_) => "⚠ CONFLICT",
+    }
+}
+
+/// Machine-readable sync plan for `plan --format json`
+#[derive(Serialize)]
+struct PlanReport<'a> {
+    mapping: Option<&'a str>,
+    local: &'a str,
+    ssd: &'a str,
+    actions: Vec<PlanReportEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct PlanReportEntry<'a> {
+    path: &'a str,
+    action: &'static str,
+    is_dir: bool,
+    /// Source path of a folder rename
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<&'a str>,
+    /// Size of the file to copy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_change: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ssd_change: Option<&'static str>,
+    /// (local, SSD) version counters of a conflict, with `[sync_settings] version_counters`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    versions: Option<(u64, u64)>,
+    /// Conflict only: entry at the last sync and on each side now (null = absent)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base_entry: Option<Option<&'a FileEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_entry: Option<Option<&'a FileEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ssd_entry: Option<Option<&'a FileEntry>>,
+}
+
+impl<'a> PlanReportEntry<'a> {
+    fn new(entry: &'a diff::SyncPlanEntry) -> Self {
+        let (from, conflict) = match &entry.action {
+            SyncAction::RenameOnSsd { from } | SyncAction::RenameOnLocal { from } => (Some(from.as_str()), None),
+            SyncAction::Conflict(info) => (None, Some(info)),
+            _ => (None, None),
+        };
+        Self {
+            path: &entry.path,
+            action: entry.action.kind(),
+            is_dir: entry.is_dir,
+            from,
+            size: entry.entry.as_ref().filter(|e| !e.is_dir).map(|e| e.size),
+            local_change: conflict.map(|info| info.local_change.as_str()),
+            ssd_change: conflict.map(|info| info.ssd_change.as_str()),
+            versions: conflict.and_then(|info| info.versions),
+            base_entry: conflict.map(|info| info.base.as_ref()),
+            local_entry: conflict.map(|info| info.local.as_ref()),
+            ssd_entry: conflict.map(|info| info.ssd.as_ref()),
+        }
+    }
+}
+
+/// 只读：不写快照、不创建 SSD 目录（SSD 文件夹不存在时按空目录计算）
+fn cmd_plan(name: Option<&str>, format: OutputFormat) -> Result<()> {
+    let config = AppConfig::load()?;
+    let (ssd_mount_str, mappings) = resolve_mappings(name, &config)?;
+    let ssd_path = Path::new(&ssd_mount_str);
+    let ignore = IgnoreMatcher::new(&config.ignore.patterns);
+
+    for mapping in &mappings {
+        let local_path = Path::new(&mapping.local);
+        if !local_path.exists() {
+            anyhow::bail!("Local path does not exist: {}", mapping.local);
+        }
+        let opts = sync_options(&config, mapping, &ignore, true, false)?;
+        let plan = sync_engine::preview_plan(
+            local_path,
+            ssd_path,
+            &mapping.ssd,
+            &config.machine.name,
+            &opts,
+        )?;
+
+        if format == OutputFormat::Json {
+            let report = PlanReport {
+                mapping: mapping.name.as_deref(),
+                local: &mapping.local,
+                ssd: &mapping.ssd,
+                actions: plan.actions.iter().map(PlanReportEntry::new).collect(),
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            continue;
+        }
+
+        let label = mapping.name.as_deref().unwrap_or(&mapping.ssd);
+        outln!("━━━ Plan: {} ↔ {} ━━━", mapping.local, label);
+        if plan.actions.is_empty() {
+            println!("  Nothing to do.");
+        }
+        for entry in &plan.actions {
+            println!("  {} {}", output::text(action_symbol(&entry.action)), entry.display_path());
+        }
+        println!();
+    }
+
     Ok(())
 }
 
-fn cmd_log(name: Option<&str>, limit: usize) -> Result<()> {
+/// How many example paths to print per group in `doctor`.
+const DOCTOR_EXAMPLES: usize = 3;
+
+fn cmd_verify(name: Option<&str>) -> Result<()> {
+    let config = AppConfig::load()?;
+    let (ssd_mount_str, mappings) = resolve_mappings(name, &config)?;
+    let ssd_path = Path::new(&ssd_mount_str);
+    if !ssd_path.exists() {
+        anyhow::bail!("SSD mount point does not exist: {}", ssd_mount_str);
+    }
+
+    let mut problems = 0;
+    for mapping in &mappings {
+        let label = mapping.name.as_deref().unwrap_or(&mapping.ssd);
+        outln!("━━━ Verify: {} ━━━", label);
+        let report = sync_engine::verify_ssd(ssd_path, &mapping.ssd, &config.machine.name)?;
+        for (path, silent) in &report.mismatched {
+            if *silent {
+                outln!("  ✕ {} (content changed but size and modification time did not: possible corruption)", path);
+            } else {
+                outln!("  ✕ {} (modified since the last sync)", path);
+            }
+        }
+        for path in &report.missing {
+            outln!("  ✕ {} (missing)", path);
+        }
+        println!(
+            "  Checked {} file(s): {} mismatched, {} missing",
+            report.checked,
+            report.mismatched.len(),
+            report.missing.len()
+        );
+        println!();
+        problems += report.problems();
+    }
+
+    if problems > 0 {
+        anyhow::bail!("Verify found {} problem(s)", problems);
+    }
+    outln!("✓ All files on the SSD match the last sync");
+    Ok(())
+}
+
+fn cmd_doctor(name: Option<&str>) -> Result<()> {
+    let config = match AppConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            outln!("{} Config does not parse: {:#}", health::Status::Fail.symbol(), e);
+            anyhow::bail!("Doctor found 1 failed check(s)");
+        }
+    };
+    outln!("{} Config parses: {}", health::Status::Pass.symbol(), AppConfig::config_path()?.display());
+    println!();
+    let (ssd_mount_str, mappings) = resolve_mappings(name, &config)?;
+    let ssd_path = Path::new(&ssd_mount_str);
+    let ignore = IgnoreMatcher::new(&config.ignore.patterns);
+    let mut failed = 0;
+
+    for mapping in &mappings {
+        let label = mapping.name.as_deref().unwrap_or(&mapping.ssd);
+        outln!("━━━ Doctor: {} ↔ {} ━━━", mapping.local, label);
+        let checks = health::check_mapping(mapping);
+        for check in &checks {
+            outln!("  {} {}", check.status.symbol(), check.message);
+        }
+        failed += checks.iter().filter(|c| c.status == health::Status::Fail).count();
+        // 本地目录或 SSD 不可用时，漂移检查没有意义
+        if checks.iter().any(|c| c.status == health::Status::Fail) {
+            println!();
+            continue;
+        }
+        let ssd_folder = ssd_path.join(&mapping.ssd);
+        if !ssd_folder.exists() {
+            println!("  SSD folder does not exist yet, nothing to check");
+            println!();
+            continue;
+        }
+        let opts = sync_options(&config, mapping, &ignore, true, false)?;
+        let mut problems = 0;
+
+        // SSD 上存在、但被本机忽略规则排除的文件：本机永远看不到它们，
+        // 通常是另一台机器用不同的忽略规则同步上来的
+        let ssd_files = scanner::list_files(&ssd_folder)?;
+        let ignored_on_ssd = opts.scan.ignore.ignored_by_pattern(ssd_files.iter().map(|p| p.as_str()));
+        if !ignored_on_ssd.is_empty() {
+            let count: usize = ignored_on_ssd.values().map(|v| v.len()).sum();
+            problems += count;
+            outln!(
+                "  ⚠ {} file(s) on the SSD are ignored on this machine (another machine may sync them with different ignore patterns):",
+                count
+            );
+            for (pattern, paths) in &ignored_on_ssd {
+                let examples: Vec<&str> =
+                    paths.iter().take(DOCTOR_EXAMPLES).map(|p| p.as_str()).collect();
+                println!("    '{}': {} file(s), e.g. {}", pattern, paths.len(), examples.join(", "));
+            }
+        }
+
+        // 本机同步的文件在其他机器的快照中缺失（且在其上次同步之前就已存在）：对方很可能忽略了它们
+        let snapshots_root = AppConfig::ssd_syncer_dir(ssd_path).join("snapshots");
+        let snapshot_name = Snapshot::snapshot_filename(&mapping.ssd);
+        let own = Snapshot::load_or_empty(
+            &snapshots_root.join(&config.machine.name).join(&snapshot_name),
+            &mapping.ssd,
+            &config.machine.name,
+        )?;
+        let mut machines: Vec<String> = std::fs::read_dir(&snapshots_root)
+            .map(|entries| {
+                entries.flatten().map(|e| e.file_name().to_string_lossy().to_string()).collect()
+            })
+            .unwrap_or_default();
+        machines.sort();
+        for machine in machines.iter().filter(|m| **m != config.machine.name) {
+            let path = snapshots_root.join(machine).join(&snapshot_name);
+            let Ok(theirs) = Snapshot::load(&path) else {
+                continue;
+            };
+            let missing: Vec<&String> = own
+                .files
+                .iter()
+                .filter(|(p, e)| {
+                    !e.is_dir
+                        && !theirs.files.contains_key(*p)
+                        && e.mtime_secs < theirs.synced_at.timestamp()
+                })
+                .map(|(p, _)| p)
+                .collect();
+            if missing.is_empty() {
+                continue;
+            }
+            problems += missing.len();
+            outln!(
+                "  ⚠ {} file(s) synced here are missing from '{}' (last sync {}), probably ignored there:",
+                missing.len(),
+                machine,
+                theirs.synced_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+            );
+            for p in missing.iter().take(DOCTOR_EXAMPLES) {
+                println!("    {}", p);
+            }
+            if missing.len() > DOCTOR_EXAMPLES {
+                println!("    ... and {} more", missing.len() - DOCTOR_EXAMPLES);
+            }
+        }
+
+        if problems == 0 {
+            outln!("  ✓ No asymmetrically ignored files");
+        }
+        println!();
+    }
+    if failed > 0 {
+        anyhow::bail!("Doctor found {} failed check(s)", failed);
+    }
+    Ok(())
+}
+
+fn cmd_cache_clear() -> Result<()> {
+    let path = hash_store::store_path()?;
+    if !path.exists() {
+        println!("Hash cache is already empty.");
+        return Ok(());
+    }
+    let size = std::fs::metadata(&path)?.len();
+    std::fs::remove_file(&path)
+        .with_context(|| format!("Failed to delete {}", path.display()))?;
+    println!("Deleted hash cache {} ({})", path.display(), units::format_bytes(size));
+    Ok(())
+}
+
+fn cmd_snapshots_prune(older_than: &str, name: Option<&str>, dry_run: bool) -> Result<()> {
+    let config = AppConfig::load()?;
+    let max_age = units::parse_duration(older_than).context("Invalid --older-than")?;
+    let cutoff = chrono::Utc::now() - chrono::Duration::seconds(max_age as i64);
+
+    let mappings: Vec<&config::SyncMapping> = match name {
+        Some(_) => resolve_mappings(name, &config)?.1,
+        None => config.sync.iter().collect(),
+    };
+    let mut roots: Vec<PathBuf> = mappings
+        .iter()
+        .map(|m| AppConfig::ssd_syncer_dir(Path::new(&m.ssd)).join("snapshots"))
+        .collect();
+    roots.sort();
+    roots.dedup();
+
+    // 候选：最近一次同步早于阈值的机器目录（本机除外；没有可读快照的目录不动）
+    let mut stale: Vec<(PathBuf, String, chrono::DateTime<chrono::Utc>, u64)> = vec![];
+    for root in &roots {
+        let Ok(entries) = std::fs::read_dir(root) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let machine = entry.file_name().to_string_lossy().to_string();
+            let dir = entry.path();
+            if machine == config.machine.name || !dir.is_dir() {
+                continue;
+            }
+            match Snapshot::machine_last_sync(&dir) {
+                Some(last) if last < cutoff => {
+                    let size = walkdir::WalkDir::new(&dir)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|e| e.metadata().ok())
+                        .filter(|m| m.is_file())
+                        .map(|m| m.len())
+                        .sum();
+                    stale.push((dir, machine, last, size));
+                }
+                Some(_) => {}
+                None => log::info!("Skipping {}: no readable snapshot", dir.display()),
+            }
+        }
+    }
+
+    if stale.is_empty() {
+        println!("No machine snapshots older than {}.", older_than);
+        return Ok(());
+    }
+    stale.sort_by_key(|(_, _, last, _)| *last);
+    println!("Machines that have not synced for {} or longer:", older_than);
+    for (dir, machine, last, size) in &stale {
+        println!(
+            "  {}  last sync {}  {}  ({})",
+            machine,
+            last.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+            units::format_bytes(*size),
+            dir.display()
+        );
+    }
+    if dry_run {
+        println!("[DRY RUN] Would delete {} snapshot folder(s).", stale.len());
+        return Ok(());
+    }
+    println!("If a pruned machine syncs again, it is treated as a first sync: both folders are merged,");
+    println!("so files deleted elsewhere in the meantime come back.");
+    if !prompt::confirm(&format!("Delete {} snapshot folder(s)?", stale.len()))? {
+        println!("Nothing deleted.");
+        return Ok(());
+    }
+    for (dir, _, _, _) in &stale {
+        std::fs::remove_dir_all(dir)
+            .with_context(|| format!("Failed to delete {}", dir.display()))?;
+    }
+    println!("Deleted {} snapshot folder(s).", stale.len());
+    Ok(())
+}
+
+fn cmd_prune(conflicts_older_than: Option<&str>, dry_run: bool) -> Result<()> {
+    let config = AppConfig::load()?;
+    let live: Vec<&str> = config.sync.iter().map(|m| m.ssd.as_str()).collect();
+
+    // 只清理本机的快照目录：其他机器的映射配置不可见，无法判断它们的快照是否仍在使用
+    let mut dirs: Vec<PathBuf> = config
+        .sync
+        .iter()
+        .map(|m| AppConfig::ssd_snapshots_dir(Path::new(&m.ssd), &config.machine.name))
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    let mut files: Vec<(PathBuf, u64)> = dirs
+        .iter()
+        .flat_map(|dir| Snapshot::orphaned_files(dir, &live))
+        .map(|path| {
+            let size = std::fs::metadata(&path).map_or(0, |m| m.len());
+            (path, size)
+        })
+        .collect();
+    if files.is_empty() {
+        println!("No snapshots of removed mappings.");
+    } else {
+        println!("Snapshots of mappings that are no longer configured:");
+        for (path, size) in &files {
+            println!("  {}  ({})", path.display(), units::format_bytes(*size));
+        }
+    }
+
+    if let Some(older_than) = conflicts_older_than {
+        let max_age = units::parse_duration(older_than).context("Invalid --older-than")?;
+        let cutoff = chrono::Utc::now() - chrono::Duration::seconds(max_age as i64);
+        let mut old_copies = vec![];
+        for mapping in &config.sync {
+            for root in [Path::new(&mapping.local), Path::new(&mapping.ssd)] {
+                if !root.is_dir() {
+                    continue;
+                }
+                for rel in scanner::list_files(root)? {
+                    let name = rel.rsplit('/').next().unwrap_or(&rel);
+                    if sync_engine::conflict_copy_time(name, &config.conflict.marker).is_some_and(|t| t < cutoff) {
+                        let path = root.join(&rel);
+                        let size = std::fs::metadata(&path).map_or(0, |m| m.len());
+                        old_copies.push((path, size));
+                    }
+                }
+            }
+        }
+        old_copies.sort();
+        old_copies.dedup();
+        if old_copies.is_empty() {
+            println!("No conflict copies older than {}.", older_than);
+        } else {
+            println!("Conflict copies older than {}:", older_than);
+            for (path, size) in &old_copies {
+                println!("  {}  ({})", path.display(), units::format_bytes(*size));
+            }
+        }
+        files.extend(old_copies);
+    }
+
+    if files.is_empty() {
+        return Ok(());
+    }
+    let total: u64 = files.iter().map(|(_, size)| size).sum();
+    if dry_run {
+        println!("[DRY RUN] Would delete {} file(s), {}.", files.len(), units::format_bytes(total));
+        return Ok(());
+    }
+    if !prompt::confirm(&format!("Delete {} file(s), {}?", files.len(), units::format_bytes(total)))? {
+        println!("Nothing deleted.");
+        return Ok(());
+    }
+    for (path, _) in &files {
+        std::fs::remove_file(path).with_context(|| format!("Failed to delete {}", path.display()))?;
+    }
+    println!("Deleted {} file(s), {}.", files.len(), units::format_bytes(total));
+    Ok(())
+}
+
+fn cmd_bench(name: Option<&str>) -> Result<()> {
+    let config = AppConfig::load()?;
+    let (ssd_mount_str, mappings) = resolve_mappings(name, &config)?;
+    let ssd_path = Path::new(&ssd_mount_str);
+    let ignore = IgnoreMatcher::new(&config.ignore.patterns);
+
+    for mapping in &mappings {
+        let label = mapping.name.as_deref().unwrap_or(&mapping.ssd);
+        outln!("━━━ Bench: {} ↔ {} ━━━", mapping.local, label);
+
+        let opts = sync_options(&config, mapping, &ignore, true, false)?;
+        let sides = [
+            ("Local", Path::new(&mapping.local).to_path_buf()),
+            ("SSD", ssd_path.join(&mapping.ssd)),
+        ];
+        for (side, root) in &sides {
+            if !root.exists() {
+                println!("  {}: {} does not exist, skipping", side, root.display());
+                continue;
+            }
+            // 两侧依次扫描，避免互相争抢 I/O 影响测量结果
+            let bench = scanner::bench_scan(root, &opts.scan)?;
+            let per_sec = |n: f64, d: std::time::Duration| {
+                if d.as_secs_f64() > 0.0 { n / d.as_secs_f64() } else { 0.0 }
+            };
+            println!(
+                "  {}: {} files, {} ({:.1}s total)",
+                side,
+                bench.files,
+                units::format_bytes(bench.bytes),
+                bench.total.as_secs_f64()
+            );
+            println!(
+                "    Walk: {:.1}s ({:.0} files/s)",
+                bench.walking().as_secs_f64(),
+                per_sec(bench.files as f64, bench.walking())
+            );
+            println!(
+                "    Hash: {:.1}s ({}/s)",
+                bench.hashing.as_secs_f64(),
+                units::format_bytes(per_sec(bench.hashed_bytes as f64, bench.hashing) as u64)
+            );
+        }
+        println!();
+    }
+
+    println!("This is a first-sync scan: later syncs only re-hash files whose size or mtime changed.");
+    Ok(())
+}
+
+fn cmd_log(
+    name: Option<&str>,
+    limit: usize,
+    newer_than: Option<&str>,
+    since: Option<&str>,
+    older_than: Option<&str>,
+) -> Result<()> {
+    let newer_than = match since {
+        Some(since) => Some(sync_log::parse_since(since, chrono::Utc::now())?),
+        None => newer_than.map(sync_log::parse_date_arg).transpose()?,
+    };
+    let filter = sync_log::LogFilter {
+        newer_than,
+        older_than: older_than.map(sync_log::parse_date_arg).transpose()?,
+    };
+
     let config = AppConfig::load()?;
     let (ssd_mount_str, _mappings) = resolve_mappings(name, &config)?;
     let ssd_path = Path::new(&ssd_mount_str);
-    let log_path = AppConfig::ssd_syncer_dir(ssd_path).join("sync.log");
+    let log_path = sync_log::log_path(ssd_path);
 
     if !log_path.exists() {
         println!("No sync history found.");
@@ -505,54 +2271,382 @@ fn cmd_log(name: Option<&str>, limit: usize) -> Result<()> {
     }
 
     let content = std::fs::read_to_string(&log_path)?;
-    let lines: Vec<&str> = content.lines().collect();
-    let start = if lines.len() > limit {
-        lines.len() - limit
-    } else {
-        0
-    };
+    let lines = sync_log::recent_entries(&content, &filter, limit);
+
+    if !filter.is_empty() && lines.is_empty() {
+        println!("No sync history in the given time range.");
+        return Ok(());
+    }
 
     println!("Sync history (last {} entries):", limit);
-    for line in &lines[start..] {
-        println!("  {}", line);
+    for line in &lines {
+        // 旧格式（只有 machine/actions）的行原样输出
+        match sync_log::LogEntry::parse(line) {
+            Some(entry) => outln!("  {}", describe_log_entry(&entry)),
+            None => println!("  {}", line),
+        }
+    }
+
+    Ok(())
+}
+
+/// One `log` line, e.g. "[2024-03-10 11:45:00 UTC] WORK on mac: 2 → SSD, 1 deleted (2.0 KiB)".
+fn describe_log_entry(entry: &sync_log::LogEntry) -> String {
+    let parts: Vec<String> = [
+        (entry.to_ssd, "→ SSD"),
+        (entry.to_local, "← local"),
+        (entry.deleted, "deleted"),
+        (entry.renamed, "renamed"),
+        (entry.conflicts, "conflict(s)"),
+        (entry.errors, "error(s)"),
+    ]
+    .iter()
+    .filter(|(n, _)| *n > 0)
+    .map(|(n, what)| format!("{} {}", n, what))
+    .collect();
+    let mut line = format!(
+        "[{}] {} on {}: {}",
+        entry.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+        entry.mapping,
+        entry.machine,
+        if parts.is_empty() { "no changes".to_string() } else { parts.join(", ") }
+    );
+    if entry.bytes > 0 {
+        line.push_str(&format!(" ({})", units::format_bytes(entry.bytes)));
+    }
+    line
+}
+
+/// 重新哈希两侧文件，确认一致后写入基准快照，消除顽固的"误报变更"
+fn cmd_touch(name: &str, rel_path: &str) -> Result<()> {
+    let config = AppConfig::load()?;
+    let (ssd_mount_str, mappings) = resolve_mappings(Some(name), &config)?;
+    let mapping = mappings[0];
+    let ssd_path = Path::new(&ssd_mount_str);
+    let rel = rel_path.replace('\\', "/").trim_matches('/').to_string();
+    if rel.is_empty() {
+        anyhow::bail!("Please provide a file path relative to the sync folder root.");
+    }
+
+    let local_file = Path::new(&mapping.local).join(&rel);
+    let ssd_file = ssd_path.join(&mapping.ssd).join(&rel);
+    for file in [&local_file, &ssd_file] {
+        if !file.is_file() {
+            anyhow::bail!("Not a file: {}", file.display());
+        }
+    }
+
+    let algorithm = config.scan.hash_algorithm;
+    let local_entry = scanner::file_entry(&local_file, algorithm)?;
+    let ssd_entry = scanner::file_entry(&ssd_file, algorithm)?;
+    if local_entry.hash != ssd_entry.hash {
+        anyhow::bail!(
+            "'{}' differs between local and SSD (local {} bytes, SSD {} bytes). Use `ssd-syncer sync` to reconcile it.",
+            rel,
+            local_entry.size,
+            ssd_entry.size
+        );
+    }
+
+    let snapshot_dir = AppConfig::ssd_snapshots_dir(ssd_path, &config.machine.name);
+    let snapshot_file = snapshot_dir.join(Snapshot::snapshot_filename(&mapping.ssd));
+    let ssd_cache_file = snapshot_dir.join(Snapshot::ssd_cache_filename(&mapping.ssd));
+
+    let mut base = Snapshot::load_or_empty(&snapshot_file, &mapping.ssd, &config.machine.name)?;
+    let mut ssd_cache = Snapshot::load_or_empty(&ssd_cache_file, &mapping.ssd, &config.machine.name)?;
+    // 基准快照同时用作本地扫描缓存，因此记录本地的 size/mtime；SSD 缓存记录 SSD 侧的
+    base.files.insert(rel.clone(), local_entry);
+    ssd_cache.files.insert(rel.clone(), ssd_entry);
+    base.save(&snapshot_file)?;
+    ssd_cache.save(&ssd_cache_file)?;
+
+    println!("'{}' is identical on both sides; marked as in sync.", rel);
+    Ok(())
+}
+
+fn cmd_reset_base(name: &str, side: BaseSide) -> Result<()> {
+    let config = AppConfig::load()?;
+    let (ssd_mount_str, mappings) = resolve_mappings(Some(name), &config)?;
+    let mapping = mappings[0];
+    let ssd_path = Path::new(&ssd_mount_str);
+    let local_path = Path::new(&mapping.local);
+    if !local_path.exists() {
+        anyhow::bail!("Local path does not exist: {}", mapping.local);
+    }
+
+    let ignore = IgnoreMatcher::new(&config.ignore.patterns);
+    let opts = sync_options(&config, mapping, &ignore, true, false)?;
+    let pending = sync_engine::preview_plan(
+        local_path, ssd_path, &mapping.ssd, &config.machine.name, &opts)?.actions.len();
+
+    let (this_side, other_side) = match side {
+        BaseSide::Local => ("local folder", "SSD"),
+        BaseSide::Ssd => ("SSD folder", "local folder"),
+    };
+    outln!("⚠ The current {} becomes the base for '{}'.", this_side, name);
+    println!("  {} pending change(s) are discarded. Afterwards, every difference in the", pending);
+    println!("  {} counts as a change made there and is synced on the next run.", other_side);
+    if !prompt::confirm("Reset the base snapshot?")? {
+        println!("Nothing changed.");
+        return Ok(());
+    }
+
+    let count = sync_engine::reset_base(
+        local_path, ssd_path, &mapping.ssd, &config.machine.name, &opts, side == BaseSide::Ssd)?;
+    println!("Base snapshot reset: {} entries from the {}.", count, this_side);
+    Ok(())
+}
+
+/// 以 SSD 为准恢复本地目录：只复制，不删除
+fn cmd_restore_from_ssd(name: &str, dry_run: bool, overwrite: bool) -> Result<()> {
+    let config = AppConfig::load()?;
+    let (ssd_mount_str, mappings) = resolve_mappings(Some(name), &config)?;
+    let mapping = mappings[0];
+    let ssd_path = Path::new(&ssd_mount_str);
+    if !ssd_path.exists() {
+        anyhow::bail!("SSD mount point does not exist: {}", ssd_mount_str);
+    }
+
+    if dry_run {
+        println!("=== DRY RUN (no changes will be made) ===");
+        println!();
+    }
+    let label = mapping.name.as_deref().unwrap_or(&mapping.ssd);
+    outln!("━━━ Restoring: {} ← {} ━━━", mapping.local, label);
+
+    let ignore = IgnoreMatcher::new(&config.ignore.patterns);
+    let opts = sync_options(&config, mapping, &ignore, dry_run, false)?;
+    let mut progress = TerminalProgress::new(&opts);
+    let restored = sync_engine::restore_from_ssd(
+        Path::new(&mapping.local),
+        ssd_path,
+        &mapping.ssd,
+        &config.machine.name,
+        &opts,
+        overwrite,
+        &mut |p| progress.update(p),
+    );
+    progress.finish();
+    let (_plan, result, skipped) = restored?;
+
+    print_sync_result(&result);
+    if !result.errors.is_empty() {
+        println!("  Errors:");
+        for err in &result.errors {
+            println!("    - {}", err);
+        }
+    }
+    if !skipped.is_empty() {
+        println!("  Kept {} local file(s) that differ from the SSD:", skipped.len());
+        for path in &skipped {
+            println!("    ~ {}", path);
+        }
+        println!("  The next `ssd-syncer sync` treats them as conflicts; use --overwrite to take the SSD version.");
+    }
+    Ok(())
+}
+
+/// 把归档中的旧版本复制回 SSD；下次同步时会作为 SSD 侧修改传播到本地
+fn cmd_restore_archive(name: Option<&str>, rel_path: &str, date: Option<&str>, list: bool) -> Result<()> {
+    let config = AppConfig::load()?;
+    let (ssd_mount_str, mappings) = resolve_mappings(name, &config)?;
+    let mapping = mappings[0];
+    let ssd_path = Path::new(&ssd_mount_str);
+    let rel = rel_path.replace('\\', "/").trim_matches('/').to_string();
+    if rel.is_empty() {
+        anyhow::bail!("Please provide a file path relative to the sync folder root.");
+    }
+
+    let versions = archive::versions(&archive::archive_root(ssd_path), &rel);
+    if versions.is_empty() {
+        anyhow::bail!("No archived versions of '{}' found.", rel);
+    }
+
+    if list {
+        println!("Archived versions of '{}':", rel);
+        for (day, path) in &versions {
+            let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            println!("  {}  {} bytes", day, size);
+        }
+        return Ok(());
+    }
+
+    let (day, source) = match date {
+        Some(d) => {
+            let day = archive::parse_day(d)?;
+            versions
+                .into_iter()
+                .find(|(v, _)| *v == day)
+                .with_context(|| format!("No archived version of '{}' on {}", rel, day))?
+        }
+        None => versions.into_iter().next_back().unwrap(),
+    };
+
+    let target = ssd_path.join(&mapping.ssd).join(&rel);
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
     }
+    // 先复制到临时文件：归档当前版本时可能覆盖今天归档目录里的同名文件（即恢复源）
+    let staged = target.with_file_name(format!(
+        "{}.ssd-syncer-restore",
+        target.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    std::fs::copy(&source, &staged)
+        .with_context(|| format!("Failed to restore {} -> {}", source.display(), target.display()))?;
+    if config.safety.archive {
+        archive::Archive::new(ssd_path, &ssd_path.join(&mapping.ssd)).preserve(&target)?;
+    }
+    std::fs::rename(&staged, &target)
+        .with_context(|| format!("Failed to restore {}", target.display()))?;
 
+    println!("Restored '{}' from archive {} onto the SSD.", rel, day);
+    println!("Run `ssd-syncer sync` to bring it to the local folder.");
     Ok(())
 }
 
+/// Terminal rendering of sync progress: a bar over the bytes to copy (with speed and ETA), or
+/// one line per action with `--verbose`. Nothing with `--summary-only` or `--quiet`, and no bar
+/// when stdout is not a terminal (piped output gets no progress at all).
+struct TerminalProgress {
+    verbose: bool,
+    summary_only: bool,
+    bar: Option<indicatif::ProgressBar>,
+}
+
+impl TerminalProgress {
+    fn new(opts: &sync_engine::SyncOptions) -> Self {
+        Self { verbose: opts.verbose, summary_only: opts.summary_only, bar: None }
+    }
+
+    fn update(&mut self, progress: &sync_engine::SyncProgress) {
+        if self.summary_only || output::is_quiet() {
+            return;
+        }
+        if progress.done {
+            if let Some(bar) = &self.bar {
+                bar.inc(progress.bytes);
+            }
+            return;
+        }
+        let line = format!(
+            "[{}/{}] {} {}",
+            progress.index,
+            progress.total,
+            output::text(sync_engine::action_label(&progress.entry.action)),
+            progress.entry.display_path()
+        );
+        if self.verbose {
+            outln!("  {}", line);
+            return;
+        }
+        if self.bar.is_none() && std::io::stdout().is_terminal() {
+            let style = indicatif::ProgressStyle::with_template(
+                "  {bar:30} {bytes}/{total_bytes} {bytes_per_sec} ETA {eta} {wide_msg}",
+            )
+            .expect("valid progress template")
+            .progress_chars(if output::is_ascii() { "#>-" } else { "█▉▊▋▌▍▎▏ " });
+            let bar = indicatif::ProgressBar::with_draw_target(
+                Some(progress.total_bytes),
+                indicatif::ProgressDrawTarget::stdout(),
+            );
+            bar.set_style(style);
+            self.bar = Some(bar);
+        }
+        if let Some(bar) = &self.bar {
+            bar.set_message(line);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(bar) = self.bar.take() {
+            bar.finish_and_clear();
+        }
+    }
+}
+
 fn print_sync_result(result: &sync_engine::SyncResult) {
     if result.total_files > 0 {
-        println!("  Total files in sync folder: {}", result.total_files);
+        outln!("  Total files in sync folder: {}", result.total_files);
+    }
+    if result.deferred > 0 {
+        outln!("  ⏸ Deferred: {} change(s), proposed again on the next sync", result.deferred);
+    }
+    if result.staged_deletes > 0 {
+        outln!(
+            "  ⏸ Staged for deletion: {} item(s), deleted by the next sync if still missing",
+            result.staged_deletes
+        );
+    }
+    if !result.skipped.is_empty() {
+        outln!("  ⚠ Skipped {} file(s) over the size limit (max_file_size), not synced:", result.skipped.len());
+        for (path, size) in &result.skipped {
+            outln!("    {} ({})", path, units::format_bytes(*size));
+        }
     }
     if result.total_actions() == 0 {
-        println!("  No changes needed.");
+        if result.deferred == 0 && result.staged_deletes == 0 {
+            outln!("  No changes needed.");
+        }
         return;
     }
 
     if result.copied_to_ssd > 0 {
-        println!("  → Copied to SSD: {} file(s)", result.copied_to_ssd);
+        outln!("  {}", paint(&format!("→ Copied to SSD: {} file(s)", result.copied_to_ssd), Tone::ToSsd));
     }
     if result.copied_to_local > 0 {
-        println!("  ← Copied to local: {} file(s)", result.copied_to_local);
+        outln!("  {}", paint(&format!("← Copied to local: {} file(s)", result.copied_to_local), Tone::ToLocal));
     }
     if result.deleted_from_ssd > 0 {
-        println!("  ✕ Deleted from SSD: {} file(s)", result.deleted_from_ssd);
+        outln!("  {}", paint(&format!("✕ Deleted from SSD: {} file(s)", result.deleted_from_ssd), Tone::Delete));
     }
     if result.deleted_from_local > 0 {
-        println!(
-            "  ✕ Deleted from local: {} file(s)",
-            result.deleted_from_local
+        outln!(
+            "  {}",
+            paint(&format!("✕ Deleted from local: {} file(s)", result.deleted_from_local), Tone::Delete)
         );
     }
+    if result.renamed > 0 {
+        outln!("  ↻ Renamed: {}", result.renamed);
+    }
     if result.conflicts > 0 {
-        println!("  ⚠ Conflicts handled: {}", result.conflicts);
+        outln!("  {}", paint(&format!("⚠ Conflicts handled: {}", result.conflicts), Tone::Conflict));
+    }
+    if result.bytes_copied > 0 {
+        let secs = result.elapsed.as_secs_f64();
+        outln!(
+            "  Transferred: {} in {:.1}s ({}/s)",
+            units::format_bytes(result.bytes_copied),
+            secs,
+            units::format_bytes((result.bytes_copied as f64 / secs.max(0.001)) as u64)
+        );
+    }
+}
+
+/// How many of the slowest copies `sync --profile` lists.
+const SLOWEST_COPIES: usize = 10;
+
+/// The copies of a mapping that took longest.
+fn print_copy_profile(result: &sync_engine::SyncResult) {
+    if result.copy_times.is_empty() {
+        return;
+    }
+    outln!("  Slowest files:");
+    for timing in result.slowest_copies(SLOWEST_COPIES) {
+        let secs = timing.elapsed.as_secs_f64();
+        outln!(
+            "    {:>7.2}s {:>10} {:>12}  {}",
+            secs,
+            units::format_bytes(timing.size),
+            format!("{}/s", units::format_bytes((timing.size as f64 / secs.max(0.001)) as u64)),
+            timing.path
+        );
     }
 }
 
 fn cmd_ignore_reset() -> Result<()> {
     let mut config = AppConfig::load()?;
     let old_count = config.ignore.patterns.len();
-    config.ignore = config::IgnoreConfig::default();
+    config.ignore.patterns = config::IgnoreConfig::default().patterns;
     config.save()?;
     println!("Ignore patterns reset to defaults.");
     println!("  Before: {} patterns", old_count);
@@ -578,6 +2672,13 @@ fn cmd_ignore_add(patterns: &[String]) -> Result<()> {
     if patterns.is_empty() {
         anyhow::bail!("Please provide at least one pattern to add.");
     }
+    let invalid: Vec<String> = patterns
+        .iter()
+        .filter_map(|p| ignore::validate_pattern(p).err().map(|e| format!("  '{}': {}", p, e)))
+        .collect();
+    if !invalid.is_empty() {
+        anyhow::bail!("Invalid ignore pattern(s), nothing was added:\n{}", invalid.join("\n"));
+    }
     let mut config = AppConfig::load()?;
     let mut added = Vec::new();
     let mut skipped = Vec::new();
@@ -606,6 +2707,26 @@ fn cmd_ignore_add(patterns: &[String]) -> Result<()> {
     Ok(())
 }
 
+fn cmd_ignore_validate() -> Result<()> {
+    let config = AppConfig::load()?;
+    let mut invalid = 0;
+    for p in &config.ignore.patterns {
+        if let Err(e) = ignore::validate_pattern(p) {
+            outln!("  ✕ '{}': {}", p, e);
+            invalid += 1;
+        }
+    }
+    if invalid > 0 {
+        anyhow::bail!(
+            "{} of {} pattern(s) are invalid. Fix them with `ssd-syncer ignore-remove` / `ignore-add`.",
+            invalid,
+            config.ignore.patterns.len()
+        );
+    }
+    println!("All {} ignore patterns are valid.", config.ignore.patterns.len());
+    Ok(())
+}
+
 fn cmd_ignore_remove(patterns: &[String]) -> Result<()> {
     if patterns.is_empty() {
         anyhow::bail!("Please provide at least one pattern to remove.");
@@ -638,19 +2759,3 @@ fn cmd_ignore_remove(patterns: &[String]) -> Result<()> {
     println!("Total: {} patterns (was {})", config.ignore.patterns.len(), before);
     Ok(())
 }
-
-fn append_sync_log(ssd_mount: &Path, machine: &str, actions: usize) -> Result<()> {
-    let log_path = AppConfig::ssd_syncer_dir(ssd_mount).join("sync.log");
-    let timestamp = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC");
-    let entry = format!("[{}] machine={} actions={}\n", timestamp, machine, actions);
-
-    use std::io::Write;
-    let mut file = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&log_path)?;
-    file.write_all(entry.as_bytes())?;
-
-    Ok(())
-}
-
@@ -0,0 +1,155 @@
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use owo_colors::OwoColorize;
+
+/// Plain-ASCII output (`--ascii` or `[output] ascii = true`) for terminals and CI logs
+/// that cannot render the symbols below.
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+/// `--quiet`: no banners, progress or summaries; errors still go to stderr.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Colour the direction of changes (`--color`, `NO_COLOR`).
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// `--color <WHEN>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Only when stdout is a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to colour output. `always` wins over `NO_COLOR`; an empty `NO_COLOR` is ignored
+    /// (https://no-color.org).
+    pub fn enabled(self, stdout_is_terminal: bool, no_color: Option<&str>) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => stdout_is_terminal && no_color.is_none_or(str::is_empty),
+        }
+    }
+}
+
+/// What a coloured piece of output is about.
+#[derive(Debug, Clone, Copy)]
+pub enum Tone {
+    /// Copy to the SSD (green)
+    ToSsd,
+    /// Copy to the local folder (blue)
+    ToLocal,
+    /// Deletion on either side (red)
+    Delete,
+    /// Conflict (yellow)
+    Conflict,
+}
+
+/// Symbols used in progress and summary output, with their ASCII replacements.
+const SYMBOLS: [(char, &str); 9] = [
+    ('━', "-"),
+    ('→', "->"),
+    ('←', "<-"),
+    ('↔', "<->"),
+    ('↻', "~"),
+    ('✕', "x"),
+    ('⚠', "!"),
+    ('✓', "OK"),
+    ('⏸', "||"),
+];
+
+pub fn set_ascii(ascii: bool) {
+    ASCII.store(ascii, Ordering::Relaxed);
+}
+
+pub fn is_ascii() -> bool {
+    ASCII.load(Ordering::Relaxed)
+}
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+pub fn set_color(color: bool) {
+    COLOR.store(color, Ordering::Relaxed);
+}
+
+/// `s` in the colour of `tone`, or unchanged when colour is off.
+pub fn paint(s: &str, tone: Tone) -> Cow<'_, str> {
+    if !COLOR.load(Ordering::Relaxed) {
+        return Cow::Borrowed(s);
+    }
+    Cow::Owned(match tone {
+        Tone::ToSsd => s.green().to_string(),
+        Tone::ToLocal => s.blue().to_string(),
+        Tone::Delete => s.red().to_string(),
+        Tone::Conflict => s.yellow().to_string(),
+    })
+}
+
+/// `s` as it should be printed: unchanged, or with the symbols replaced in ASCII mode.
+pub fn text(s: &str) -> Cow<'_, str> {
+    if !ASCII.load(Ordering::Relaxed) {
+        return Cow::Borrowed(s);
+    }
+    to_ascii(s)
+}
+
+fn to_ascii(s: &str) -> Cow<'_, str> {
+    if !s.chars().any(|c| SYMBOLS.iter().any(|(sym, _)| *sym == c)) {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match SYMBOLS.iter().find(|(sym, _)| *sym == c) {
+            Some((_, ascii)) => out.push_str(ascii),
+            None => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// `println!` that honours ASCII and quiet mode.
+macro_rules! outln {
+    () => {
+        if !$crate::output::is_quiet() {
+            println!()
+        }
+    };
+    ($($arg:tt)*) => {
+        if !$crate::output::is_quiet() {
+            println!("{}", $crate::output::text(&format!($($arg)*)))
+        }
+    };
+}
+pub(crate) use outln;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_choice() {
+        assert!(ColorChoice::Auto.enabled(true, None));
+        assert!(ColorChoice::Auto.enabled(true, Some("")));
+        assert!(!ColorChoice::Auto.enabled(true, Some("1")));
+        // 管道输出不着色
+        assert!(!ColorChoice::Auto.enabled(false, None));
+        assert!(ColorChoice::Always.enabled(false, Some("1")));
+        assert!(!ColorChoice::Never.enabled(true, None));
+    }
+
+    #[test]
+    fn test_to_ascii() {
+        assert_eq!(to_ascii("━━━ Syncing: a ↔ b ━━━"), "--- Syncing: a <-> b ---");
+        assert_eq!(to_ascii("  ⚠ 2 conflict(s), ✕ 1 → SSD"), "  ! 2 conflict(s), x 1 -> SSD");
+        // 普通文本（包括中文文件名）保持不变
+        assert!(matches!(to_ascii("报告.txt"), Cow::Borrowed(_)));
+    }
+}
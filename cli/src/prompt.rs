@@ -0,0 +1,15 @@
+use anyhow::Result;
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Ask a `[y/N]` question on the terminal. Anything but "y"/"yes" counts as no;
+/// 标准输入不是终端（脚本、管道）时不询问，直接返回 false。
+pub fn confirm(question: &str) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(false);
+    }
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
@@ -0,0 +1,115 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::snapshot::FileEntry;
+
+/// One completed copy from an interrupted sync.
+/// `source` / `dest` 分别记录源端和目标端的 size/mtime/hash，
+/// 下次运行时作为扫描缓存，避免重新哈希已经复制完成的文件。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeEntry {
+    pub path: String,
+    pub to_ssd: bool,
+    pub source: FileEntry,
+    pub dest: FileEntry,
+}
+
+/// Append-only log of copies executed so far (JSON lines).
+/// 每完成一个文件就追加一行，进程被中断时已写入的记录依然有效。
+pub struct ResumeLog {
+    file: Mutex<std::fs::File>,
+}
+
+impl ResumeLog {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open resume file: {}", path.display()))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, entry: &ResumeEntry) -> Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Load entries from a previous interrupted run.
+    /// 最后一行可能因中断而不完整，解析失败的行直接跳过。
+    pub fn load(path: &Path) -> Result<Vec<ResumeEntry>> {
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read resume file: {}", path.display()))?;
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    pub fn remove(path: &Path) -> Result<()> {
+        if path.exists() {
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove resume file: {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str) -> ResumeEntry {
+        let file = FileEntry {
+            size: 1,
+            mtime_secs: 1_700_000_000,
+            hash: "abc".to_string(),
+            is_dir: false,
+            version: 0,
+            is_symlink: false,
+        };
+        ResumeEntry {
+            path: path.to_string(),
+            to_ssd: true,
+            source: file.clone(),
+            dest: file,
+        }
+    }
+
+    #[test]
+    fn test_load_skips_truncated_line() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("snapshots/work_resume.jsonl");
+        let log = ResumeLog::open(&path).unwrap();
+        log.record(&entry("a.txt")).unwrap();
+        log.record(&entry("dir/b.txt")).unwrap();
+        drop(log);
+        // 进程在写最后一行时被中断
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"path":"c.txt","to_ssd":tr"#).unwrap();
+        drop(file);
+
+        let entries = ResumeLog::load(&path).unwrap();
+        let paths: Vec<&str> = entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "dir/b.txt"]);
+
+        ResumeLog::remove(&path).unwrap();
+        assert!(!path.exists());
+        assert!(ResumeLog::load(&path).unwrap().is_empty());
+    }
+}
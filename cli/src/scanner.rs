@@ -1,19 +1,202 @@
 use anyhow::{Context, Result};
-use std::collections::BTreeSet;
-use std::io::Write;
-use std::path::Path;
+use rayon::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{ErrorKind, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use walkdir::WalkDir;
 
+use crate::config::{FiltersConfig, HashAlgorithm, SyncMapping, DEFAULT_TEMP_SUFFIX, SYNCER_DIR_NAME};
+use crate::hash_store;
 use crate::ignore::IgnoreMatcher;
 use crate::snapshot::{FileEntry, Snapshot};
+use crate::units;
+
+/// What to include when scanning one mapping.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    pub ignore: IgnoreMatcher,
+    /// Only record files with these extensions (lowercase, no leading dot). Empty = all files.
+    pub extensions: Vec<String>,
+    /// Skip files modified less than this many seconds ago
+    pub min_age_secs: Option<i64>,
+    /// Skip files last modified more than this many seconds ago
+    pub max_age_secs: Option<i64>,
+    /// Fail the scan once more files than this are found (`max_files` of the mapping)
+    pub max_files: Option<usize>,
+    /// Names ending with this are in-progress copies (`[io] temp_suffix`) and never recorded
+    pub temp_suffix: String,
+    /// Scan what symlinks point to (`[scan] follow_symlinks`); otherwise record the links
+    pub follow_symlinks: bool,
+    /// Hash every file instead of reusing hashes of files with unchanged size and mtime (`--checksum`)
+    pub checksum: bool,
+    /// Leave out files larger than this many bytes (`[scan] max_file_size`, `--max-file-size`)
+    pub max_file_size: Option<u64>,
+    /// Leave out files larger than this many bytes, as if ignored (`[scan] ignore_larger_than`)
+    pub ignore_larger_than: Option<u64>,
+    /// Leave out files smaller than this many bytes (`[scan] ignore_smaller_than`)
+    pub ignore_smaller_than: Option<u64>,
+    /// Content hash of new and changed files (`[scan] hash_algorithm`)
+    pub hash_algorithm: HashAlgorithm,
+    /// Files hashed at the same time on each side (`--threads`); 1 = one after another
+    pub threads: usize,
+    now_secs: i64,
+}
+
+impl ScanOptions {
+    pub fn new(ignore: IgnoreMatcher) -> Self {
+        Self {
+            ignore,
+            extensions: vec![],
+            min_age_secs: None,
+            max_age_secs: None,
+            max_files: None,
+            temp_suffix: DEFAULT_TEMP_SUFFIX.to_string(),
+            follow_symlinks: false,
+            checksum: false,
+            max_file_size: None,
+            ignore_larger_than: None,
+            ignore_smaller_than: None,
+            hash_algorithm: HashAlgorithm::default(),
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            now_secs: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    /// Apply the `[filters]` age limits.
+    pub fn with_filters(mut self, filters: &FiltersConfig) -> Result<Self> {
+        let parse = |v: &Option<String>, key: &str| -> Result<Option<i64>> {
+            v.as_deref()
+                .map(|s| {
+                    units::parse_duration(s)
+                        .map(|secs| secs as i64)
+                        .with_context(|| format!("Invalid [filters] {}", key))
+                })
+                .transpose()
+        };
+        self.min_age_secs = parse(&filters.min_age, "min_age")?;
+        self.max_age_secs = parse(&filters.max_age, "max_age")?;
+        Ok(self)
+    }
+
+    fn has_age_filter(&self) -> bool {
+        self.min_age_secs.is_some() || self.max_age_secs.is_some()
+    }
+
+    fn age_allowed(&self, mtime_secs: i64) -> bool {
+        let age = self.now_secs - mtime_secs;
+        self.min_age_secs.is_none_or(|min| age >= min)
+            && self.max_age_secs.is_none_or(|max| age <= max)
+    }
+
+    fn has_size_filter(&self) -> bool {
+        self.ignore_larger_than.is_some() || self.ignore_smaller_than.is_some()
+    }
+
+    fn size_allowed(&self, len: u64) -> bool {
+        self.ignore_larger_than.is_none_or(|max| len <= max)
+            && self.ignore_smaller_than.is_none_or(|min| len >= min)
+    }
+
+    pub fn for_mapping(ignore: IgnoreMatcher, mapping: &SyncMapping) -> Self {
+        Self {
+            extensions: mapping
+                .extensions
+                .iter()
+                .map(|e| e.trim_start_matches('.').to_lowercase())
+                .filter(|e| !e.is_empty())
+                .collect(),
+            max_files: mapping.max_files,
+            ..Self::new(ignore)
+        }
+    }
+
+    fn extension_allowed(&self, path: &Path) -> bool {
+        if self.extensions.is_empty() {
+            return true;
+        }
+        path.extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .is_some_and(|e| self.extensions.contains(&e))
+    }
+}
+
+/// Filled once by whichever scan thread hashes the file first (None = hashing failed).
+type HashSlot = Arc<OnceLock<Option<String>>>;
+
+/// Hashes shared by the local and SSD scan threads of one `scan_pair`, keyed by
+/// path + size + full-precision mtime. 两侧同一路径、同样 size/mtime 的文件（例如保留了 mtime 的副本）只哈希一次；
+/// 另一线程正在哈希同一文件时等待其结果，而不是重复计算。
+/// 不使用秒级 mtime 作键：两侧在同一秒内被改成同样大小的不同内容时会误用对方的哈希。
+#[derive(Default)]
+pub struct HashCache {
+    entries: Mutex<HashMap<(String, u64, u128), HashSlot>>,
+    hashed: AtomicUsize,
+    reused: AtomicUsize,
+    hashed_bytes: AtomicU64,
+    hash_nanos: AtomicU64,
+}
+
+impl HashCache {
+    fn hash(
+        &self,
+        rel_str: &str,
+        size: u64,
+        mtime_nanos: u128,
+        abs_path: &Path,
+        algorithm: HashAlgorithm,
+    ) -> Result<String> {
+        let cell = self
+            .entries
+            .lock()
+            .unwrap()
+            .entry((rel_str.to_string(), size, mtime_nanos))
+            .or_default()
+            .clone();
+        let mut computed = false;
+        let cached = cell.get_or_init(|| {
+            computed = true;
+            let started = Instant::now();
+            let hash = compute_file_hash(abs_path, algorithm).ok();
+            self.hash_nanos
+                .fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+            hash
+        });
+        if computed {
+            self.hashed.fetch_add(1, Ordering::Relaxed);
+            self.hashed_bytes.fetch_add(size, Ordering::Relaxed);
+        } else {
+            self.reused.fetch_add(1, Ordering::Relaxed);
+        }
+        match cached {
+            Some(hash) => Ok(hash.clone()),
+            // 另一侧哈希失败：自己重新计算，返回真实的错误
+            None => compute_file_hash(abs_path, algorithm),
+        }
+    }
+}
 
 pub fn scan_directory(
     root: &Path,
     sync_folder: &str,
     machine: &str,
-    ignore: &IgnoreMatcher,
+    opts: &ScanOptions,
     base_snapshot: Option<&Snapshot>,
 ) -> Result<Snapshot> {
+    scan_directory_shared(root, sync_folder, machine, opts, base_snapshot, None)
+}
+
+fn scan_directory_shared(
+    root: &Path,
+    sync_folder: &str,
+    machine: &str,
+    opts: &ScanOptions,
+    base_snapshot: Option<&Snapshot>,
+    hash_cache: Option<&HashCache>,
+) -> Result<Snapshot> {
+    let ignore = &opts.ignore;
     let mut snapshot = Snapshot::new(sync_folder, machine);
 
     if !root.exists() {
@@ -24,16 +207,40 @@ pub fn scan_directory(
         anyhow::bail!("Path is not a directory: {}", root.display());
     }
 
+    // 映射根目录本身可能是符号链接：先解析成真实路径再遍历，各平台行为一致；
+    // 目录内部的符号链接按 follow_symlinks 处理：跟随到目标，或作为链接本身记录
+    let canonical_root = root
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve directory: {}", root.display()))?;
+    let root = canonical_root.as_path();
+
     // Collect all directories for empty-dir detection
     let mut all_dirs: BTreeSet<String> = BTreeSet::new();
     // Track which directories contain files (directly or indirectly)
     let mut non_empty_dirs: BTreeSet<String> = BTreeSet::new();
 
     let mut file_count: usize = 0;
+    // 需要读取元数据并哈希的文件，遍历结束后在线程池里并行处理
+    let mut pending: Vec<(PathBuf, String)> = vec![];
+    // 输出被重定向时（如 `--format json`）不打印进度，避免 \r 混入输出
+    let show_progress = std::io::stdout().is_terminal() && !crate::output::is_quiet();
+    let scanned = AtomicUsize::new(0);
+    let tick = || {
+        let n = scanned.fetch_add(1, Ordering::Relaxed) + 1;
+        if show_progress && n.is_multiple_of(100) {
+            let mut stdout = std::io::stdout().lock();
+            let _ = write!(stdout, "\r  Scanning... {} files", n);
+            let _ = stdout.flush();
+        }
+    };
 
-    let walker = WalkDir::new(root).follow_links(false).into_iter();
+    let walker = WalkDir::new(root).follow_links(opts.follow_symlinks).into_iter();
     // 使用 filter_entry 跳过忽略目录的整个子树
     for entry in walker.filter_entry(|e| {
+        // 工具自身的元数据目录（快照、日志、归档）永远不是用户数据
+        if e.depth() > 0 && e.file_type().is_dir() && e.file_name() == SYNCER_DIR_NAME {
+            return false;
+        }
         let rel = e.path().strip_prefix(root).unwrap_or(e.path());
         let rel_str = rel
             .components()
@@ -42,7 +249,22 @@ pub fn scan_directory(
             .join("/");
         rel_str.is_empty() || !ignore.is_ignored(&rel_str)
     }) {
-        let entry = entry.with_context(|| format!("Failed to walk directory: {}", root.display()))?;
+        let entry = match entry {
+            Ok(entry) => entry,
+            // 扫描过程中被删除的文件/目录：跳过即可
+            Err(e) if e.io_error().is_some_and(|io| io.kind() == ErrorKind::NotFound) => {
+                log::debug!("Skipping vanished entry during scan: {}", e);
+                continue;
+            }
+            // 跟随符号链接时指回上级目录的链接：跳过，否则会无限递归
+            Err(e) if e.loop_ancestor().is_some() => {
+                log::warn!("Skipping symlink loop: {}", e);
+                continue;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to walk directory: {}", root.display()));
+            }
+        };
 
         let abs_path = entry.path();
         let rel_path = abs_path
@@ -65,77 +287,106 @@ pub fn scan_directory(
             continue;
         }
 
-        if !entry.file_type().is_file() {
+        // 不跟随时符号链接作为独立条目记录（含指向目标），同步时在另一侧重建
+        let is_symlink = entry.file_type().is_symlink();
+        if !entry.file_type().is_file() && !is_symlink {
             continue;
         }
 
-        // Mark all ancestor directories as non-empty
-        let mut ancestor = rel_path.parent();
-        while let Some(p) = ancestor {
-            if p == Path::new("") {
-                break;
-            }
-            let ancestor_str = p
-                .components()
-                .map(|c| c.as_os_str().to_string_lossy().to_string())
-                .collect::<Vec<_>>()
-                .join("/");
-            non_empty_dirs.insert(ancestor_str);
-            ancestor = p.parent();
+        if entry.file_name().to_string_lossy().ends_with(&opts.temp_suffix) {
+            continue;
         }
 
-        let metadata = std::fs::metadata(abs_path)
-            .with_context(|| format!("Failed to read metadata: {}", abs_path.display()))?;
+        if !opts.extension_allowed(rel_path) {
+            continue;
+        }
 
-        let size = metadata.len();
-        let mtime_secs = metadata
-            .modified()
-            .ok()
-            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-            .map(|d| d.as_secs() as i64)
-            .unwrap_or(0);
-
-        // Optimization: check if file changed since last snapshot
-        let needs_hash = if let Some(base) = base_snapshot {
-            if let Some(prev_entry) = base.files.get(&rel_str) {
-                // If size and mtime match, reuse previous hash
-                if prev_entry.size == size && prev_entry.mtime_secs == mtime_secs {
-                    snapshot.files.insert(rel_str, prev_entry.clone());
-                    continue;
-                }
-                true
-            } else {
-                true // New file
-            }
-        } else {
-            true // No base snapshot, must hash
-        };
+        // 文件数突然暴涨（例如忽略规则失效、node_modules 被扫进来）：在哈希和同步之前中止
+        if let Some(max) = opts.max_files.filter(|max| file_count >= *max) {
+            anyhow::bail!(
+                "{} contains more than {} files (max_files of this mapping); nothing was synced. \
+                 Check the ignore patterns, or raise max_files if the growth is expected.",
+                root.display(),
+                max
+            );
+        }
 
-        let hash = if needs_hash {
-            compute_file_hash(abs_path)?
+        // 超出 [filters] 年龄范围或 [scan] 大小范围的文件不记录；之前已同步过的沿用缓存条目，避免被当成删除
+        let aged_out = opts.has_age_filter()
+            && entry.metadata().is_ok_and(|m| !opts.age_allowed(mtime_secs(&m)));
+        let sized_out = opts.has_size_filter()
+            && !is_symlink
+            && entry.metadata().is_ok_and(|m| !opts.size_allowed(m.len()));
+        let oversized = match opts.max_file_size {
+            Some(max) if !is_symlink => entry.metadata().ok().map(|m| m.len()).filter(|len| *len > max),
+            _ => None,
+        };
+        let file_entry = if is_symlink {
+            match symlink_entry(abs_path) {
+                Ok(file_entry) => file_entry,
+                Err(e) if is_not_found(&e) => continue,
+                Err(e) => return Err(e),
+            }
+        } else if aged_out || sized_out {
+            match base_snapshot.and_then(|base| base.files.get(&rel_str)) {
+                Some(prev) => prev.clone(),
+                None => continue,
+            }
+        } else if let Some(size) = oversized {
+            // 超过 max_file_size 的文件不哈希、不同步；同样沿用已同步过的缓存条目
+            snapshot.oversized.insert(rel_str.clone(), size);
+            match base_snapshot.and_then(|base| base.files.get(&rel_str)) {
+                Some(prev) => prev.clone(),
+                None => continue,
+            }
         } else {
-            unreachable!()
+            pending.push((abs_path.to_path_buf(), rel_str));
+            file_count += 1;
+            continue;
         };
 
-        snapshot.files.insert(
-            rel_str,
-            FileEntry {
-                size,
-                mtime_secs,
-                hash,
-                is_dir: false,
-            },
-        );
-
+        mark_ancestors(&rel_str, &mut non_empty_dirs);
+        snapshot.files.insert(rel_str, file_entry);
         file_count += 1;
-        if file_count % 100 == 0 {
-            print!("\r  Scanning... {} files", file_count);
-            let _ = std::io::stdout().flush();
-        }
+        tick();
+    }
+
+    // 哈希是扫描的主要耗时：按 --threads 并行计算，结果仍按遍历顺序处理
+    let read = |(abs_path, rel_str): &(PathBuf, String)| {
+        let result = read_file_entry(abs_path, rel_str, base_snapshot, hash_cache, opts);
+        tick();
+        result
+    };
+    let results: Vec<Result<(FileEntry, bool)>> = if opts.threads > 1 && pending.len() > 1 {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(opts.threads)
+            .build()
+            .context("Failed to start scan threads")?;
+        pool.install(|| pending.par_iter().map(read).collect())
+    } else {
+        pending.iter().map(read).collect()
+    };
+    for ((abs_path, rel_str), result) in pending.into_iter().zip(results) {
+        // 文件可能在 WalkDir 返回之后、读取元数据/哈希之前被删除（TOCTOU），视为已消失
+        let file_entry = match result {
+            Ok((file_entry, in_flux)) => {
+                if in_flux {
+                    snapshot.in_flux.insert(rel_str.clone());
+                }
+                file_entry
+            }
+            Err(e) if is_not_found(&e) => {
+                log::debug!("File disappeared during scan, skipping: {}", abs_path.display());
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        mark_ancestors(&rel_str, &mut non_empty_dirs);
+        snapshot.files.insert(rel_str, file_entry);
     }
 
     // 清除进度行
-    if file_count >= 100 {
+    if show_progress && scanned.into_inner() >= 100 {
         print!("\r{}", " ".repeat(40));
         print!("\r");
         let _ = std::io::stdout().flush();
@@ -151,19 +402,240 @@ pub fn scan_directory(
                     mtime_secs: 0,
                     hash: "empty-dir".to_string(),
                     is_dir: true,
+                    version: 0,
+                    is_symlink: false,
                 },
             );
         }
     }
 
+    hash_store::flush();
     Ok(snapshot)
 }
 
-pub fn compute_file_hash(path: &Path) -> Result<String> {
-    let content = std::fs::read(path)
-        .with_context(|| format!("Failed to read file for hashing: {}", path.display()))?;
-    let hash = blake3::hash(&content);
-    Ok(format!("blake3:{}", hash.to_hex()))
+/// Mark all ancestor directories of a recorded entry as non-empty.
+fn mark_ancestors(rel_str: &str, non_empty_dirs: &mut BTreeSet<String>) {
+    let mut path = rel_str;
+    while let Some((parent, _)) = path.rsplit_once('/') {
+        non_empty_dirs.insert(parent.to_string());
+        path = parent;
+    }
+}
+
+/// Build the snapshot entry for one file, reusing the base snapshot's hash
+/// when size and mtime are unchanged (unless `checksum`). The flag is true when the file kept
+/// changing while it was hashed, i.e. the hash may not match any real state.
+fn read_file_entry(
+    abs_path: &Path,
+    rel_str: &str,
+    base_snapshot: Option<&Snapshot>,
+    hash_cache: Option<&HashCache>,
+    opts: &ScanOptions,
+) -> Result<(FileEntry, bool)> {
+    let metadata = std::fs::metadata(abs_path)
+        .with_context(|| format!("Failed to read metadata: {}", abs_path.display()))?;
+
+    let size = metadata.len();
+    let mtime_secs = mtime_secs(&metadata);
+
+    // --checksum：不信任任何按 size/mtime 复用的哈希（基准快照、持久化缓存、两侧共享），
+    // 保留原 mtime 的修改（部分编辑器、rsync 类工具）也能发现
+    let checksum = opts.checksum;
+    let (base_snapshot, hash_cache) = if checksum { (None, None) } else { (base_snapshot, hash_cache) };
+    // 换了 [scan] hash_algorithm 后，旧算法的哈希不再复用，按新算法重新计算
+    let algorithm = opts.hash_algorithm;
+    let current_algorithm = |hash: &str| HashAlgorithm::of(hash) == Some(algorithm);
+
+    // Optimization: check if file changed since last snapshot
+    if let Some(prev_entry) = base_snapshot.and_then(|base| base.files.get(rel_str)) {
+        // If size and mtime match, reuse previous hash
+        if prev_entry.size == size && prev_entry.mtime_secs == mtime_secs && current_algorithm(&prev_entry.hash) {
+            return Ok((prev_entry.clone(), false));
+        }
+    }
+
+    // 快照里没有时查询持久化哈希缓存（删除快照或新机器首次同步时仍可复用）
+    if let Some(hash) = hash_store::lookup(abs_path, size, mtime_secs).filter(|h| !checksum && current_algorithm(h)) {
+        return Ok((FileEntry { size, mtime_secs, hash, is_dir: false, version: 0, is_symlink: false }, false));
+    }
+
+    // 正在被写入的文件（日志、数据库）：哈希前后 size/mtime 不一致时重读一次，
+    // 仍不一致则标记为 in flux，由同步跳过，避免下次把不完整的内容当成变更
+    let mut metadata = metadata;
+    let mut retried = false;
+    loop {
+        let size = metadata.len();
+        let mtime_secs = self::mtime_secs(&metadata);
+        let mtime_nanos = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos());
+        let hash = match (hash_cache, mtime_nanos) {
+            (Some(cache), Some(nanos)) => cache.hash(rel_str, size, nanos, abs_path, algorithm)?,
+            _ => compute_file_hash(abs_path, algorithm)?,
+        };
+        let after = std::fs::metadata(abs_path)
+            .with_context(|| format!("Failed to read metadata: {}", abs_path.display()))?;
+        let entry = FileEntry { size, mtime_secs, hash, is_dir: false, version: 0, is_symlink: false };
+        if after.len() == size && after.modified().ok() == metadata.modified().ok() {
+            hash_store::record(abs_path, size, mtime_secs, &entry.hash);
+            return Ok((entry, false));
+        }
+        if retried {
+            log::warn!("{} changed while being hashed; leaving it for the next sync", abs_path.display());
+            return Ok((entry, true));
+        }
+        retried = true;
+        metadata = after;
+    }
+}
+
+/// Stat and hash a single file, ignoring any cached state.
+pub fn file_entry(abs_path: &Path, algorithm: HashAlgorithm) -> Result<FileEntry> {
+    let metadata = std::fs::metadata(abs_path)
+        .with_context(|| format!("Failed to read metadata: {}", abs_path.display()))?;
+    Ok(FileEntry {
+        size: metadata.len(),
+        mtime_secs: mtime_secs(&metadata),
+        hash: compute_file_hash(abs_path, algorithm)?,
+        is_dir: false,
+        version: 0,
+        is_symlink: false,
+    })
+}
+
+/// Snapshot entry for a symlink recorded as a link. The hash holds the target, so
+/// pointing a link somewhere else is a change like editing a file.
+fn symlink_entry(abs_path: &Path) -> Result<FileEntry> {
+    let metadata = std::fs::symlink_metadata(abs_path)
+        .with_context(|| format!("Failed to read metadata: {}", abs_path.display()))?;
+    let target = std::fs::read_link(abs_path)
+        .with_context(|| format!("Failed to read symlink: {}", abs_path.display()))?;
+    let target = target.to_string_lossy();
+    Ok(FileEntry {
+        size: target.len() as u64,
+        mtime_secs: mtime_secs(&metadata),
+        hash: format!("symlink:{}", target),
+        is_dir: false,
+        version: 0,
+        is_symlink: true,
+    })
+}
+
+fn is_not_found(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == ErrorKind::NotFound)
+}
+
+/// File modification time as seconds since the Unix epoch (0 if unavailable).
+pub fn mtime_secs(metadata: &std::fs::Metadata) -> i64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Read size used when hashing; memory use does not grow with the file size.
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Content hash of a file, prefixed with the algorithm (`blake3:<hex>`, `xxh3:<hex>`).
+pub fn compute_file_hash(path: &Path, algorithm: HashAlgorithm) -> Result<String> {
+    let context = || format!("Failed to read file for hashing: {}", path.display());
+    let mut file = std::fs::File::open(path).with_context(context)?;
+    let mut blake3 = blake3::Hasher::new();
+    let mut xxh3 = xxhash_rust::xxh3::Xxh3::new();
+    let mut buffer = vec![0u8; HASH_CHUNK_SIZE];
+    loop {
+        let n = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).with_context(context),
+        };
+        match algorithm {
+            HashAlgorithm::Blake3 => {
+                blake3.update(&buffer[..n]);
+            }
+            HashAlgorithm::Xxh3 => xxh3.update(&buffer[..n]),
+        }
+    }
+    let hex = match algorithm {
+        HashAlgorithm::Blake3 => blake3.finalize().to_hex().to_string(),
+        HashAlgorithm::Xxh3 => format!("{:032x}", xxh3.digest128()),
+    };
+    Ok(format!("{}:{}", algorithm.prefix(), hex))
+}
+
+/// Relative paths of all files under `root`, without ignore patterns, filters or hashing.
+/// 仅用于诊断（例如找出两侧忽略规则不一致的文件），只跳过工具自身的元数据目录。
+pub fn list_files(root: &Path) -> Result<Vec<String>> {
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve directory: {}", root.display()))?;
+    let mut files = vec![];
+    let walker = WalkDir::new(&root).follow_links(false).into_iter();
+    for entry in walker.filter_entry(|e| {
+        !(e.depth() > 0 && e.file_type().is_dir() && e.file_name() == SYNCER_DIR_NAME)
+    }) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if e.io_error().is_some_and(|io| io.kind() == ErrorKind::NotFound) => continue,
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to walk directory: {}", root.display()));
+            }
+        };
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+        files.push(
+            rel.components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join("/"),
+        );
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Timing of one uncached scan, reported by `bench`.
+#[derive(Debug, Clone)]
+pub struct ScanBench {
+    pub files: usize,
+    pub bytes: u64,
+    pub hashed_bytes: u64,
+    /// Wall time of the whole scan
+    pub total: Duration,
+    /// Time spent hashing file contents (the rest is walking + stat)
+    pub hashing: Duration,
+}
+
+impl ScanBench {
+    pub fn walking(&self) -> Duration {
+        self.total.saturating_sub(self.hashing)
+    }
+}
+
+/// Scan `root` from scratch (no cached hashes) and measure where the time goes.
+/// 相当于首次同步时的扫描；之后的同步只重新哈希 size/mtime 变化的文件。
+pub fn bench_scan(root: &Path, opts: &ScanOptions) -> Result<ScanBench> {
+    let hash_cache = HashCache::default();
+    let started = Instant::now();
+    let snapshot = scan_directory_shared(root, "", "", opts, None, Some(&hash_cache))?;
+    let total = started.elapsed();
+
+    let files = snapshot.files.values().filter(|e| !e.is_dir);
+    Ok(ScanBench {
+        files: files.clone().count(),
+        bytes: files.map(|e| e.size).sum(),
+        hashed_bytes: hash_cache.hashed_bytes.load(Ordering::Relaxed),
+        total,
+        hashing: Duration::from_nanos(hash_cache.hash_nanos.load(Ordering::Relaxed)),
+    })
 }
 
 pub fn scan_pair(
@@ -171,19 +643,21 @@ pub fn scan_pair(
     ssd_root: &Path,
     sync_folder: &str,
     machine: &str,
-    ignore: &IgnoreMatcher,
+    opts: &ScanOptions,
     local_cache: Option<&Snapshot>,
     ssd_cache: Option<&Snapshot>,
 ) -> Result<(Snapshot, Snapshot)> {
     log::info!("Scanning local + SSD in parallel...");
 
+    let hash_cache = HashCache::default();
+
     // 并行扫描本地和 SSD 目录，大幅减少总扫描时间
     let (local_result, ssd_result) = std::thread::scope(|s| {
         let local_handle = s.spawn(|| {
-            scan_directory(local_root, sync_folder, machine, ignore, local_cache)
+            scan_directory_shared(local_root, sync_folder, machine, opts, local_cache, Some(&hash_cache))
         });
         let ssd_handle = s.spawn(|| {
-            scan_directory(ssd_root, sync_folder, machine, ignore, ssd_cache)
+            scan_directory_shared(ssd_root, sync_folder, machine, opts, ssd_cache, Some(&hash_cache))
         });
 
         let local_res = local_handle.join().expect("local scan thread panicked");
@@ -194,6 +668,12 @@ pub fn scan_pair(
     let local_snap = local_result?;
     let ssd_snap = ssd_result?;
 
+    log::debug!(
+        "Hashed {} file(s), reused {} hash(es) shared between local and SSD",
+        hash_cache.hashed.load(Ordering::Relaxed),
+        hash_cache.reused.load(Ordering::Relaxed)
+    );
+
     log::info!(
         "Scan complete: {} local files, {} SSD files",
         local_snap.files.len(),
@@ -202,3 +682,332 @@ pub fn scan_pair(
 
     Ok((local_snap, ssd_snap))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streamed_hash_matches_whole_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("video.bin");
+        // 不是块大小整数倍，覆盖最后一个不满的块
+        let content: Vec<u8> = (0..HASH_CHUNK_SIZE * 40 + 1234).map(|i| (i * 31 % 251) as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+        let expected = format!("blake3:{}", blake3::hash(&content).to_hex());
+        assert_eq!(compute_file_hash(&path, HashAlgorithm::Blake3).unwrap(), expected);
+        let expected = format!("xxh3:{:032x}", xxhash_rust::xxh3::xxh3_128(&content));
+        assert_eq!(compute_file_hash(&path, HashAlgorithm::Xxh3).unwrap(), expected);
+
+        std::fs::write(&path, "").unwrap();
+        let empty = compute_file_hash(&path, HashAlgorithm::Blake3).unwrap();
+        assert_eq!(empty, format!("blake3:{}", blake3::hash(b"").to_hex()));
+        assert_eq!(HashAlgorithm::of(&empty), Some(HashAlgorithm::Blake3));
+    }
+
+    #[test]
+    fn test_parallel_scan_matches_serial() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        for i in 0..300 {
+            let dir = root.join(format!("d{}/sub{}", i % 7, i % 3));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join(format!("f{}.txt", i)), format!("content {}", i)).unwrap();
+        }
+        std::fs::create_dir_all(root.join("empty/nested")).unwrap();
+
+        let mut opts = ScanOptions::new(IgnoreMatcher::new(&[]));
+        opts.threads = 1;
+        let serial = scan_directory(root, "test", "mac", &opts, None).unwrap();
+        opts.threads = 8;
+        let parallel = scan_directory(root, "test", "mac", &opts, None).unwrap();
+        assert_eq!(parallel.files, serial.files);
+        assert!(parallel.files["empty/nested"].is_dir);
+        assert!(!parallel.files.contains_key("d0"));
+
+        // size/mtime 未变的文件仍沿用基准快照中的哈希
+        let mut base = serial.clone();
+        base.files.get_mut("d0/sub0/f0.txt").unwrap().hash = "blake3:reused".to_string();
+        let rescanned = scan_directory(root, "test", "mac", &opts, Some(&base)).unwrap();
+        assert_eq!(rescanned.files, base.files);
+    }
+
+    #[test]
+    fn test_switching_algorithm_rehashes() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("a.txt"), "alpha").unwrap();
+        let mut opts = ScanOptions::new(IgnoreMatcher::new(&[]));
+        let base = scan_directory(tmp.path(), "test", "mac", &opts, None).unwrap();
+        assert!(base.files["a.txt"].hash.starts_with("blake3:"));
+
+        // size/mtime 未变，但基准里是旧算法的哈希：按新算法重新计算
+        opts.hash_algorithm = HashAlgorithm::Xxh3;
+        let snap = scan_directory(tmp.path(), "test", "mac", &opts, Some(&base)).unwrap();
+        let expected = format!("xxh3:{:032x}", xxhash_rust::xxh3::xxh3_128(b"alpha"));
+        assert_eq!(snap.files["a.txt"].hash, expected);
+        let again = scan_directory(tmp.path(), "test", "mac", &opts, Some(&snap)).unwrap();
+        assert_eq!(again.files["a.txt"], snap.files["a.txt"]);
+    }
+
+    #[test]
+    fn test_hash_cache_hashes_once_across_threads() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("a.bin");
+        std::fs::write(&file, vec![7u8; 1 << 20]).unwrap();
+
+        let cache = HashCache::default();
+        let hashes: Vec<String> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| s.spawn(|| cache.hash("a.bin", 1 << 20, 1000, &file, HashAlgorithm::Blake3).unwrap()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert!(hashes.iter().all(|h| *h == hashes[0]));
+        assert_eq!(cache.hashed.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.reused.load(Ordering::Relaxed), 3);
+
+        // 同路径同大小但 mtime 不同（即使在同一秒内）：分别哈希
+        let other = tmp.path().join("b.bin");
+        std::fs::write(&other, vec![8u8; 1 << 20]).unwrap();
+        let second = cache.hash("a.bin", 1 << 20, 1001, &other, HashAlgorithm::Blake3).unwrap();
+        assert_ne!(second, hashes[0]);
+        assert_eq!(cache.hashed.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_age_filter_keeps_tracked_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let old_time = std::time::SystemTime::now() - std::time::Duration::from_secs(200 * 86_400);
+        for name in ["old-tracked.txt", "old-new.txt"] {
+            let f = std::fs::File::create(root.join(name)).unwrap();
+            f.set_modified(old_time).unwrap();
+        }
+        std::fs::write(root.join("recent.txt"), "now").unwrap();
+
+        let mut base = Snapshot::new("test", "mac");
+        let tracked = FileEntry {
+            size: 0,
+            mtime_secs: 1,
+            hash: "blake3:tracked".to_string(),
+            is_dir: false,
+            version: 0,
+            is_symlink: false,
+        };
+        base.files.insert("old-tracked.txt".to_string(), tracked.clone());
+
+        let filters = FiltersConfig { min_age: None, max_age: Some("90d".to_string()), ..Default::default() };
+        let opts = ScanOptions::new(IgnoreMatcher::new(&[])).with_filters(&filters).unwrap();
+        let snap = scan_directory(root, "test", "mac", &opts, Some(&base)).unwrap();
+        let paths: Vec<&str> = snap.files.keys().map(|k| k.as_str()).collect();
+        // 已跟踪的旧文件保持原条目（不视为删除），未跟踪的旧文件被跳过
+        assert_eq!(paths, vec!["old-tracked.txt", "recent.txt"]);
+        assert_eq!(snap.files["old-tracked.txt"], tracked);
+
+        let filters = FiltersConfig { min_age: Some("1h".to_string()), max_age: None, ..Default::default() };
+        let opts = ScanOptions::new(IgnoreMatcher::new(&[])).with_filters(&filters).unwrap();
+        let snap = scan_directory(root, "test", "mac", &opts, None).unwrap();
+        assert!(!snap.files.contains_key("recent.txt"));
+        assert_eq!(snap.files.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_symlinked_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let real = tmp.path().join("volume/Projects");
+        std::fs::create_dir_all(real.join("app")).unwrap();
+        std::fs::write(real.join("app/main.rs"), "fn main() {}").unwrap();
+        std::fs::create_dir(tmp.path().join("elsewhere")).unwrap();
+        std::fs::write(tmp.path().join("elsewhere/other.txt"), "x").unwrap();
+        // 根目录内部的符号链接不跟随
+        std::os::unix::fs::symlink(tmp.path().join("elsewhere"), real.join("link")).unwrap();
+        let root_link = tmp.path().join("Projects");
+        std::os::unix::fs::symlink(&real, &root_link).unwrap();
+
+        let opts = ScanOptions::new(IgnoreMatcher::new(&[]));
+        let via_link = scan_directory(&root_link, "test", "mac", &opts, None).unwrap();
+        let direct = scan_directory(&real, "test", "mac", &opts, None).unwrap();
+        assert!(via_link.files.contains_key("app/main.rs"));
+        assert!(!via_link.files.contains_key("link/other.txt"));
+        assert_eq!(via_link.files, direct.files);
+    }
+
+    #[test]
+    fn test_checksum_ignores_cached_hashes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        let mtime = std::time::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let write = |path: &Path, content: &str| {
+            std::fs::write(path, content).unwrap();
+            std::fs::File::options().write(true).open(path).unwrap().set_modified(mtime).unwrap();
+        };
+        write(&local.join("f.txt"), "aaaa");
+        write(&ssd.join("f.txt"), "bbbb");
+
+        // 两侧 size/mtime 完全相同：默认共享同一个哈希，看不出内容不同
+        let mut opts = ScanOptions::new(IgnoreMatcher::new(&[]));
+        let (l, s) = scan_pair(&local, &ssd, "t", "mac", &opts, None, None).unwrap();
+        assert_eq!(l.files["f.txt"].hash, s.files["f.txt"].hash);
+        opts.checksum = true;
+        let (l, s) = scan_pair(&local, &ssd, "t", "mac", &opts, None, None).unwrap();
+        assert_ne!(l.files["f.txt"].hash, s.files["f.txt"].hash);
+
+        // 保留 mtime 的修改：默认沿用基准快照中的哈希，--checksum 重新计算
+        let base = l;
+        write(&local.join("f.txt"), "cccc");
+        opts.checksum = false;
+        let snap = scan_directory(&local, "t", "mac", &opts, Some(&base)).unwrap();
+        assert_eq!(snap.files["f.txt"].hash, base.files["f.txt"].hash);
+        opts.checksum = true;
+        let snap = scan_directory(&local, "t", "mac", &opts, Some(&base)).unwrap();
+        assert_eq!(snap.files["f.txt"].hash, compute_file_hash(&local.join("f.txt"), HashAlgorithm::Blake3).unwrap());
+        assert_ne!(snap.files["f.txt"].hash, base.files["f.txt"].hash);
+    }
+
+    #[test]
+    fn test_max_file_size_skips_large_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join("img")).unwrap();
+        std::fs::write(tmp.path().join("notes.txt"), "small").unwrap();
+        std::fs::File::create(tmp.path().join("img/disk.img")).unwrap().set_len(2048).unwrap();
+
+        let mut opts = ScanOptions::new(IgnoreMatcher::new(&[]));
+        opts.max_file_size = Some(1024);
+        let snap = scan_directory(tmp.path(), "t", "mac", &opts, None).unwrap();
+        assert!(snap.files.contains_key("notes.txt"));
+        assert!(!snap.files.contains_key("img/disk.img"));
+        assert_eq!(snap.oversized.get("img/disk.img"), Some(&2048));
+        // 只含超大文件的目录按空目录记录
+        assert!(snap.files["img"].is_dir);
+
+        // 之前已同步过（文件后来才变大）：沿用基准条目，不会被当成删除
+        let mut base = Snapshot::new("t", "mac");
+        let prev = snap.files["notes.txt"].clone();
+        base.files.insert("img/disk.img".to_string(), prev.clone());
+        let snap = scan_directory(tmp.path(), "t", "mac", &opts, Some(&base)).unwrap();
+        assert_eq!(snap.files["img/disk.img"], prev);
+        assert!(snap.oversized.contains_key("img/disk.img"));
+    }
+
+    #[test]
+    fn test_size_band_excludes_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("notes.txt"), "small").unwrap();
+        std::fs::File::create(tmp.path().join("empty.txt")).unwrap();
+        // 稀疏文件：不实际占用 2 GB 磁盘空间
+        std::fs::File::create(tmp.path().join("disk.img")).unwrap().set_len(2 << 30).unwrap();
+
+        let mut opts = ScanOptions::new(IgnoreMatcher::new(&[]));
+        opts.ignore_larger_than = Some(1 << 30);
+        let snap = scan_directory(tmp.path(), "t", "mac", &opts, None).unwrap();
+        assert!(!snap.files.contains_key("disk.img"));
+        assert!(snap.files.contains_key("notes.txt") && snap.files.contains_key("empty.txt"));
+
+        opts.ignore_smaller_than = Some(1);
+        let snap = scan_directory(tmp.path(), "t", "mac", &opts, None).unwrap();
+        let paths: Vec<&String> = snap.files.keys().collect();
+        assert_eq!(paths, vec!["notes.txt"]);
+        // 与 max_file_size 不同，不报告为跳过
+        assert!(snap.oversized.is_empty());
+
+        // 之前已同步过：沿用基准条目，不会被当成删除
+        let mut base = Snapshot::new("t", "mac");
+        let prev = snap.files["notes.txt"].clone();
+        base.files.insert("disk.img".to_string(), prev.clone());
+        let snap = scan_directory(tmp.path(), "t", "mac", &opts, Some(&base)).unwrap();
+        assert_eq!(snap.files["disk.img"], prev);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_symlinks() {
+        use std::os::unix::fs::symlink;
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("root");
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(root.join("docs/a.txt"), "a").unwrap();
+        std::fs::create_dir(tmp.path().join("elsewhere")).unwrap();
+        std::fs::write(tmp.path().join("elsewhere/other.txt"), "x").unwrap();
+        symlink(tmp.path().join("elsewhere"), root.join("shared")).unwrap();
+        symlink("docs/a.txt", root.join("latest.txt")).unwrap();
+        symlink("missing.txt", root.join("broken")).unwrap();
+        symlink("..", root.join("docs/up")).unwrap();
+
+        // 默认：链接本身作为条目记录，不进入目标
+        let mut opts = ScanOptions::new(IgnoreMatcher::new(&[]));
+        let snap = scan_directory(&root, "test", "mac", &opts, None).unwrap();
+        let links: Vec<(&str, Option<&str>)> = snap
+            .files
+            .iter()
+            .filter(|(_, e)| e.is_symlink)
+            .map(|(p, e)| (p.as_str(), e.symlink_target()))
+            .collect();
+        let elsewhere = tmp.path().join("elsewhere").to_string_lossy().to_string();
+        assert_eq!(
+            links,
+            vec![("broken", Some("missing.txt")), ("docs/up", Some("..")), ("latest.txt", Some("docs/a.txt")), ("shared", Some(elsewhere.as_str()))]
+        );
+        assert_eq!(snap.files["latest.txt"].hash, "symlink:docs/a.txt");
+        assert!(!snap.files.contains_key("shared/other.txt"));
+
+        // 跟随：按目标内容记录；断开的链接和指回上级的循环被跳过
+        opts.follow_symlinks = true;
+        let snap = scan_directory(&root, "test", "mac", &opts, None).unwrap();
+        let paths: Vec<&str> = snap.files.keys().map(String::as_str).collect();
+        assert_eq!(paths, vec!["docs/a.txt", "latest.txt", "shared/other.txt"]);
+        assert_eq!(snap.files["latest.txt"].hash, snap.files["docs/a.txt"].hash);
+        assert!(snap.files.values().all(|e| !e.is_symlink));
+    }
+
+    #[test]
+    fn test_scan_skips_syncer_dir_without_ignore_pattern() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join(".ssd-syncer/snapshots/mac")).unwrap();
+        std::fs::write(root.join(".ssd-syncer/snapshots/mac/w.json"), "{}").unwrap();
+        std::fs::create_dir_all(root.join("docs/.ssd-syncer")).unwrap();
+        std::fs::write(root.join("docs/.ssd-syncer/sync.log"), "x").unwrap();
+        std::fs::write(root.join("docs/a.txt"), "hello").unwrap();
+
+        let opts = ScanOptions::new(IgnoreMatcher::new(&[]));
+        let snap = scan_directory(root, "test", "mac", &opts, None).unwrap();
+        let paths: Vec<&str> = snap.files.keys().map(|k| k.as_str()).collect();
+        assert_eq!(paths, vec!["docs/a.txt"]);
+    }
+
+    #[test]
+    fn test_scan_skips_temp_copies() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("a.txt"), "hello").unwrap();
+        std::fs::write(tmp.path().join("b.txt.ssd-syncer.tmp"), "partial").unwrap();
+        std::fs::write(tmp.path().join("c.txt.partial"), "partial").unwrap();
+
+        let mut opts = ScanOptions::new(IgnoreMatcher::new(&[]));
+        let snap = scan_directory(tmp.path(), "test", "mac", &opts, None).unwrap();
+        let paths: Vec<&str> = snap.files.keys().map(|k| k.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "c.txt.partial"]);
+
+        opts.temp_suffix = ".partial".to_string();
+        let snap = scan_directory(tmp.path(), "test", "mac", &opts, None).unwrap();
+        let paths: Vec<&str> = snap.files.keys().map(|k| k.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "b.txt.ssd-syncer.tmp"]);
+    }
+
+    #[test]
+    fn test_max_files_aborts_scan() {
+        let tmp = tempfile::tempdir().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(tmp.path().join(name), name).unwrap();
+        }
+        let mut opts = ScanOptions::new(IgnoreMatcher::new(&[]));
+        opts.max_files = Some(3);
+        assert_eq!(scan_directory(tmp.path(), "test", "mac", &opts, None).unwrap().files.len(), 3);
+
+        opts.max_files = Some(2);
+        let err = scan_directory(tmp.path(), "test", "mac", &opts, None).unwrap_err();
+        assert!(err.to_string().contains("more than 2 files"), "{}", err);
+    }
+}
@@ -1,7 +1,9 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::config::EmptyDirPolicy;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Snapshot {
@@ -9,6 +11,22 @@ pub struct Snapshot {
     pub machine: String,
     pub synced_at: chrono::DateTime<chrono::Utc>,
     pub files: BTreeMap<String, FileEntry>,
+    /// `[filters] empty_dirs` in effect when this base snapshot was written
+    #[serde(default, skip_serializing_if = "is_both")]
+    pub empty_dirs: EmptyDirPolicy,
+    /// Deletions staged by `[safety] deferred_delete`; the next sync carries them out
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub pending_deletes: BTreeSet<String>,
+    /// Files that changed while being hashed during this scan (never saved)
+    #[serde(skip)]
+    pub in_flux: BTreeSet<String>,
+    /// Files larger than `[scan] max_file_size` left out of this scan, with their sizes (never saved)
+    #[serde(skip)]
+    pub oversized: BTreeMap<String, u64>,
+}
+
+fn is_both(policy: &EmptyDirPolicy) -> bool {
+    *policy == EmptyDirPolicy::Both
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -18,6 +36,33 @@ pub struct FileEntry {
     pub hash: String,
     #[serde(default)]
     pub is_dir: bool,
+    /// Sync version of the content (only with `[sync_settings] version_counters`)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub version: u64,
+    /// A symlink recorded as a link (`[scan] follow_symlinks` off); `hash` is "symlink:<target>"
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub is_symlink: bool,
+}
+
+impl FileEntry {
+    /// Where the link points, for entries recorded as symlinks.
+    pub fn symlink_target(&self) -> Option<&str> {
+        self.hash.strip_prefix("symlink:").filter(|_| self.is_symlink)
+    }
+}
+
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    machine: String,
+    synced_at: chrono::DateTime<chrono::Utc>,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
 }
 
 impl Snapshot {
@@ -27,12 +72,76 @@ impl Snapshot {
             machine: machine.to_string(),
             synced_at: chrono::Utc::now(),
             files: BTreeMap::new(),
+            empty_dirs: EmptyDirPolicy::Both,
+            pending_deletes: BTreeSet::new(),
+            in_flux: BTreeSet::new(),
+            oversized: BTreeMap::new(),
         }
     }
 
+    fn safe_name(ssd_rel: &str) -> String {
+        ssd_rel.replace(['/', '\\', ':'], "_")
+    }
+
+    /// Reset the entries at and below each of `paths` to how they are in `previous`.
+    /// 用于本次延后、未执行的变更：恢复同步前的状态，下次同步时会再次提出同样的变更。
+    pub fn restore_paths(&mut self, previous: &Snapshot, paths: &[String]) {
+        for path in paths {
+            let prefix = format!("{}/", path);
+            let covered = |p: &str| p == path || p.starts_with(&prefix);
+            self.files.retain(|p, _| !covered(p));
+            for (p, entry) in previous
+                .files
+                .range(path.clone()..)
+                .take_while(|(p, _)| p.starts_with(path.as_str()))
+            {
+                if covered(p) {
+                    self.files.insert(p.clone(), entry.clone());
+                }
+            }
+        }
+    }
+
+    /// Entries under `dir/`, keyed relative to `dir` (`sync --subpath`).
+    pub fn subtree(&self, dir: &str) -> Snapshot {
+        let prefix = format!("{}/", dir);
+        let mut sub = Snapshot::new(&self.sync_folder, &self.machine);
+        sub.files = self
+            .files
+            .range(prefix.clone()..)
+            .take_while(|(p, _)| p.starts_with(&prefix))
+            .map(|(p, e)| (p[prefix.len()..].to_string(), e.clone()))
+            .collect();
+        sub
+    }
+
+    /// Replace everything under `dir/` with the entries of `sub`, which are keyed relative to `dir`.
+    pub fn graft(&mut self, dir: &str, sub: Snapshot) {
+        let prefix = format!("{}/", dir);
+        self.files.retain(|p, _| !p.starts_with(&prefix));
+        self.in_flux.retain(|p| !p.starts_with(&prefix));
+        self.oversized.retain(|p, _| !p.starts_with(&prefix));
+        // 目录下有内容时不再是空目录条目
+        if !sub.files.is_empty() {
+            self.files.remove(dir);
+        }
+        self.files.extend(sub.files.into_iter().map(|(p, e)| (format!("{}{}", prefix, p), e)));
+        self.in_flux.extend(sub.in_flux.into_iter().map(|p| format!("{}{}", prefix, p)));
+        self.oversized.extend(sub.oversized.into_iter().map(|(p, size)| (format!("{}{}", prefix, p), size)));
+    }
+
     pub fn snapshot_filename(ssd_rel: &str) -> String {
-        let safe_name = ssd_rel.replace('/', "_").replace('\\', "_").replace(':', "_");
-        format!("{}.json", safe_name)
+        format!("{}.json", Self::safe_name(ssd_rel))
+    }
+
+    /// SSD 侧扫描缓存文件名（记录 SSD 文件的 size/mtime，用于跳过哈希）
+    pub fn ssd_cache_filename(ssd_rel: &str) -> String {
+        format!("{}_ssd_cache.json", Self::safe_name(ssd_rel))
+    }
+
+    /// 中断续传记录文件名（同步完成后删除）
+    pub fn resume_filename(ssd_rel: &str) -> String {
+        format!("{}_resume.jsonl", Self::safe_name(ssd_rel))
     }
 
     pub fn load(path: &Path) -> Result<Self> {
@@ -48,15 +157,158 @@ impl Snapshot {
             std::fs::create_dir_all(parent)?;
         }
         let content = serde_json::to_string_pretty(self)?;
-        std::fs::write(path, content)?;
+        // 先写临时文件再改名：进程中途被杀也不会留下截断的快照
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, path)?;
         Ok(())
     }
 
+    /// Most recent `synced_at` recorded in any machine's snapshots under `snapshots_root`.
+    pub fn latest_sync_time(snapshots_root: &Path) -> Option<(String, chrono::DateTime<chrono::Utc>)> {
+        let mut latest: Option<(String, chrono::DateTime<chrono::Utc>)> = None;
+        for machine_dir in std::fs::read_dir(snapshots_root).ok()?.flatten() {
+            let Some(header) = Self::newest_header(&machine_dir.path()) else {
+                continue;
+            };
+            if latest.as_ref().is_none_or(|(_, t)| header.synced_at > *t) {
+                latest = Some((header.machine, header.synced_at));
+            }
+        }
+        latest
+    }
+
+    /// Most recent `synced_at` among the snapshots of one machine (`snapshots/<machine>`).
+    pub fn machine_last_sync(machine_dir: &Path) -> Option<chrono::DateTime<chrono::Utc>> {
+        Self::newest_header(machine_dir).map(|h| h.synced_at)
+    }
+
+    /// 只解析头部字段，不构建文件列表；无法读取的文件直接跳过。
+    fn newest_header(machine_dir: &Path) -> Option<SnapshotHeader> {
+        let mut newest: Option<SnapshotHeader> = None;
+        for file in std::fs::read_dir(machine_dir).ok()?.flatten() {
+            let path = file.path();
+            if path.extension().is_none_or(|e| e != "json") {
+                continue;
+            }
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let Ok(header) = serde_json::from_str::<SnapshotHeader>(&content) else {
+                continue;
+            };
+            if newest.as_ref().is_none_or(|n| header.synced_at > n.synced_at) {
+                newest = Some(header);
+            }
+        }
+        newest
+    }
+
+    /// Snapshot, SSD cache and resume files in `machine_dir` that belong to none of the `live`
+    /// mappings (by SSD path), e.g. left behind by a removed mapping. Other files are kept.
+    pub fn orphaned_files(machine_dir: &Path, live: &[&str]) -> Vec<PathBuf> {
+        let live_names: BTreeSet<String> = live
+            .iter()
+            .flat_map(|ssd_rel| {
+                [
+                    Self::snapshot_filename(ssd_rel),
+                    Self::ssd_cache_filename(ssd_rel),
+                    Self::resume_filename(ssd_rel),
+                ]
+            })
+            .collect();
+        let Ok(entries) = std::fs::read_dir(machine_dir) else {
+            return vec![];
+        };
+        let mut orphans: Vec<PathBuf> = entries
+            .flatten()
+            .filter(|e| e.path().is_file())
+            .filter(|e| {
+                let name = e.file_name().to_string_lossy().to_string();
+                (name.ends_with(".json") || name.ends_with("_resume.jsonl")) && !live_names.contains(&name)
+            })
+            .map(|e| e.path())
+            .collect();
+        orphans.sort();
+        orphans
+    }
+
+    /// Load a snapshot, or start from an empty one if the file is missing or corrupt.
+    /// 损坏的快照（例如旧版本写入时被中断）只告警，不让整个命令失败。
     pub fn load_or_empty(path: &Path, sync_folder: &str, machine: &str) -> Result<Self> {
-        if path.exists() {
-            Self::load(path)
-        } else {
-            Ok(Self::new(sync_folder, machine))
+        if !path.exists() {
+            return Ok(Self::new(sync_folder, machine));
         }
+        match Self::load(path) {
+            Ok(snap) => Ok(snap),
+            Err(e) => {
+                log::warn!(
+                    "Ignoring corrupt snapshot {} ({:#}); starting from an empty snapshot",
+                    path.display(),
+                    e
+                );
+                Ok(Self::new(sync_folder, machine))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orphaned_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for name in [
+            Snapshot::snapshot_filename("/ssd/work"),
+            Snapshot::ssd_cache_filename("/ssd/work"),
+            Snapshot::resume_filename("/ssd/work"),
+            Snapshot::snapshot_filename("/ssd/old"),
+            Snapshot::ssd_cache_filename("/ssd/old"),
+            Snapshot::resume_filename("/ssd/old"),
+            "notes.txt".to_string(),
+        ] {
+            std::fs::write(dir.join(name), "{}").unwrap();
+        }
+
+        let orphans = Snapshot::orphaned_files(dir, &["/ssd/work"]);
+        let mut expected = vec![
+            dir.join(Snapshot::snapshot_filename("/ssd/old")),
+            dir.join(Snapshot::ssd_cache_filename("/ssd/old")),
+            dir.join(Snapshot::resume_filename("/ssd/old")),
+        ];
+        expected.sort();
+        assert_eq!(orphans, expected);
+        // 没有映射时全部视为孤立；不存在的目录没有孤立文件
+        assert_eq!(Snapshot::orphaned_files(dir, &[]).len(), 6);
+        assert!(Snapshot::orphaned_files(&dir.join("missing"), &[]).is_empty());
+    }
+
+    #[test]
+    fn test_truncated_snapshot_recovers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("snapshots/mac").join(Snapshot::snapshot_filename("work"));
+        let mut snap = Snapshot::new("work", "mac");
+        snap.files.insert(
+            "a.txt".to_string(),
+            FileEntry { size: 1, mtime_secs: 0, hash: "h".to_string(), is_dir: false, version: 0, is_symlink: false },
+        );
+        snap.save(&path).unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+        assert_eq!(Snapshot::load_or_empty(&path, "work", "mac").unwrap().files.len(), 1);
+
+        // 模拟写到一半被中断
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, &content[..content.len() / 2]).unwrap();
+        assert!(Snapshot::load(&path).is_err());
+        let recovered = Snapshot::load_or_empty(&path, "work", "mac").unwrap();
+        assert!(recovered.files.is_empty());
+        assert_eq!(recovered.machine, "mac");
+
+        // 下次保存覆盖损坏的文件
+        snap.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap().files.len(), 1);
     }
 }
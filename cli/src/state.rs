@@ -0,0 +1,44 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::config::AppConfig;
+
+/// Small per-machine state kept next to the config; written by the tool, not meant to be edited.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    /// SSD directory of the last successful sync or `add`, used by `add --ssd-template` when
+    /// neither `--ssd` nor `[machine] ssd_mount` is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_ssd_mount: Option<String>,
+}
+
+impl State {
+    pub fn path() -> Result<PathBuf> {
+        Ok(AppConfig::config_dir()?.join("state.toml"))
+    }
+
+    /// Load the state, falling back to empty state if it is missing or unreadable.
+    pub fn load() -> Self {
+        let Ok(path) = Self::path() else {
+            return Self::default();
+        };
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        toml::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable state file {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string_pretty(self)?;
+        std::fs::write(&path, content)
+            .with_context(|| format!("Failed to write state: {}", path.display()))
+    }
+}
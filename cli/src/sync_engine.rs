@@ -1,30 +1,318 @@
 use anyhow::{Context, Result};
-use std::io::Write;
-use std::path::Path;
+use std::collections::{BTreeSet, HashMap};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use crate::config::{AppConfig, ConflictStrategy};
-use crate::diff::{ConflictInfo, SyncAction, SyncPlan};
-use crate::ignore::IgnoreMatcher;
-use crate::scanner;
-use crate::snapshot::Snapshot;
+use crate::archive::{self, Archive};
+use crate::compat::{self, NameMap};
+use crate::config::{
+    AppConfig, ChangeDetection, CompatConfig, ConfirmPolicy, ConflictStrategy, EmptyDirPolicy,
+    HashAlgorithm, IllegalCharPolicy, SafetyConfig,
+};
+use crate::diff::{ConflictInfo, SyncAction, SyncPlan, SyncPlanEntry};
+use crate::output::outln;
+use crate::resume::{ResumeEntry, ResumeLog};
+use crate::scanner::{self, ScanOptions};
+use crate::snapshot::{FileEntry, Snapshot};
+use crate::versions::VersionMap;
 
 pub struct SyncEngine {
     pub machine_name: String,
     pub conflict_strategy: ConflictStrategy,
     pub dry_run: bool,
+    pub resume_log: Option<ResumeLog>,
+    /// Number of file copies run in parallel (1 = strictly sequential)
+    pub copy_concurrency: usize,
+    /// SSD files are archived instead of overwritten/deleted when set
+    pub archive: Option<Archive>,
+    /// SSD names that differ from the local ones (sanitized for the SSD's filesystem)
+    pub names: Mutex<NameMap>,
+    /// Set when the SSD's filesystem rejects some characters in file names
+    pub illegal_char_policy: Option<IllegalCharPolicy>,
+    /// Dry run: directories already probed for write access (error message if not writable)
+    probed_dirs: Mutex<HashMap<PathBuf, Option<String>>>,
+    /// Folders that emptying a folder never removes: both sync roots and folders this run created
+    kept_dirs: Mutex<BTreeSet<PathBuf>>,
+    /// Pause and poll this long when the SSD root disappears mid-sync (`[io] wait_for_ssd`)
+    pub wait_for_ssd: Option<Duration>,
+    /// Held while waiting for the SSD, so parallel workers wait only once
+    ssd_wait: Mutex<()>,
+    /// Copies are written to `<name><temp_suffix>` and renamed into place when complete
+    pub temp_suffix: String,
+    /// `[conflict] marker` used in conflict copy names
+    pub conflict_marker: String,
+    /// Move deleted files and folders to the system trash (`[conflict] trash`, `--delete-to-trash`)
+    pub trash: bool,
+    /// Copy what symlinks point to; otherwise symlinks are recreated as links (`[scan] follow_symlinks`)
+    pub follow_symlinks: bool,
+    /// Bytes written by `copy_file`, conflict resolution included
+    bytes_copied: AtomicU64,
+    /// Retries of file operations that fail with a transient error (`[io] retries`)
+    pub retry: RetryPolicy,
+}
+
+/// How often and how patiently to retry a file operation that failed with a transient error.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    /// Wait before the first retry; doubled for each further one
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Run `op`, retrying it while it fails with a transient error and retries are left.
+    /// Errors such as NotFound or PermissionDenied are returned right away.
+    pub fn run<T>(&self, what: &str, mut op: impl FnMut() -> std::io::Result<T>) -> std::io::Result<T> {
+        let mut delay = self.delay;
+        let mut attempt = 0;
+        loop {
+            match op() {
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    attempt += 1;
+                    log::warn!(
+                        "{} failed ({}), retrying in {}ms ({}/{})",
+                        what,
+                        e,
+                        delay.as_millis(),
+                        attempt,
+                        self.retries
+                    );
+                    std::thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                }
+                outcome => return outcome,
+            }
+        }
+    }
+}
+
+/// When a conflict copy (named `<stem>.<marker>.<machine>.<time>[.<n>][.ext]`) was made,
+/// or None if `file_name` is not a conflict copy.
+pub fn conflict_copy_time(file_name: &str, marker: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    // 取最后一个标记：冲突副本再次冲突时，名字里会有两段标记
+    let (_, rest) = file_name.rsplit_once(&format!(".{}.", marker))?;
+    rest.split('.')
+        .skip(1)
+        .filter(|part| part.len() == 14 && part.bytes().all(|b| b.is_ascii_digit()))
+        .find_map(|part| chrono::NaiveDateTime::parse_from_str(part, "%Y%m%d%H%M%S").ok())
+        .map(|t| t.and_utc())
+}
+
+/// Whether an operation failed because the destination has no space (or quota) left.
+fn is_out_of_space(e: &anyhow::Error) -> bool {
+    use std::io::ErrorKind;
+    e.chain()
+        .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .any(|io| matches!(io.kind(), ErrorKind::StorageFull | ErrorKind::QuotaExceeded))
+}
+
+/// Context of an error of a write to the SSD that failed because the SSD is full.
+#[derive(Debug)]
+struct SsdFull;
+
+impl std::fmt::Display for SsdFull {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SSD full")
+    }
+}
+
+/// Mark an out-of-space error of a write to the SSD, so that [`SyncEngine::execute_plan`]
+/// stops copying to the SSD. 本地磁盘写满不算：那些错误照常逐条报告。
+fn on_ssd<T>(outcome: Result<T>) -> Result<T> {
+    outcome.map_err(|e| if is_out_of_space(&e) { e.context(SsdFull) } else { e })
+}
+
+/// Warn before copying when the SSD has less free space than the plan copies to it.
+/// 被覆盖的旧文件会释放空间，所以这只是估计，不阻止同步。
+fn warn_if_ssd_too_small(plan: &SyncPlan, ssd_data_root: &Path) {
+    let needed = plan.ssd_copy_bytes();
+    let Ok(available) = fs2::available_space(ssd_data_root) else {
+        return;
+    };
+    if needed > available {
+        outln!(
+            "  ⚠ The SSD has {} free but this sync copies {} to it; it will stop when the SSD is full",
+            crate::units::format_bytes(available),
+            crate::units::format_bytes(needed)
+        );
+    }
+}
+
+/// I/O errors that often go away on a second try, e.g. a USB drive briefly not responding.
+fn is_transient(e: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    // EIO（Linux/macOS 上均为 5）没有专门的 ErrorKind，USB 硬盘的偶发读写错误多为此类
+    matches!(
+        e.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::TimedOut
+            | ErrorKind::WouldBlock
+            | ErrorKind::ResourceBusy
+            | ErrorKind::UnexpectedEof
+    ) || (cfg!(unix) && e.raw_os_error() == Some(5))
+}
+
+/// Per-run options for syncing one mapping.
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    pub scan: ScanOptions,
+    pub conflict_strategy: ConflictStrategy,
+    pub change_detection: ChangeDetection,
+    pub version_counters: bool,
+    pub empty_dirs: EmptyDirPolicy,
+    pub copy_concurrency: usize,
+    pub safety: SafetyConfig,
+    pub compat: CompatConfig,
+    pub wait_for_ssd: Option<Duration>,
+    pub retry: RetryPolicy,
+    pub conflict_marker: String,
+    pub trash: bool,
+    pub dry_run: bool,
     pub verbose: bool,
+    pub summary_only: bool,
+    /// Merge on a first sync even when the two folders look unrelated
+    pub force: bool,
+    /// Only resolve conflicts; other changes are deferred to a later sync
+    pub only_conflicts: bool,
+    /// `sync --push` / `--pull`: only change one side; the rest is deferred to a later sync
+    pub direction: Option<SyncDirection>,
+    /// Print why each planned action was chosen (`sync --dry-run --explain`)
+    pub explain: bool,
+    /// Only scan and sync this folder of the mapping (relative, '/'-separated)
+    pub subpath: Option<String>,
+    /// `[sync_settings] confirm`: when to ask before executing the plan
+    pub confirm: ConfirmPolicy,
+    /// Answer given up front with `--yes` / `--no` instead of asking
+    pub confirm_answer: Option<bool>,
+}
+
+/// One-way sync: which side a `--push` / `--pull` run is allowed to change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncDirection {
+    /// Only write to the SSD (backup)
+    Push,
+    /// Only write to the local folder (restore)
+    Pull,
+}
+
+impl SyncDirection {
+    fn flag(self) -> &'static str {
+        match self {
+            SyncDirection::Push => "--push",
+            SyncDirection::Pull => "--pull",
+        }
+    }
+
+    /// Whether this direction carries out `action`. Conflicts touch both sides and are skipped.
+    pub fn allows(self, action: &SyncAction) -> bool {
+        match self {
+            SyncDirection::Push => matches!(
+                action,
+                SyncAction::CopyToSsd | SyncAction::DeleteFromSsd | SyncAction::RenameOnSsd { .. }
+            ),
+            SyncDirection::Pull => matches!(
+                action,
+                SyncAction::CopyToLocal | SyncAction::DeleteFromLocal | SyncAction::RenameOnLocal { .. }
+            ),
+        }
+    }
+}
+
+/// Below this share of matching files, a first sync asks before merging the two folders.
+const LOW_OVERLAP_PERCENT: f64 = 20.0;
+
+/// How often to check whether a disconnected SSD is back (`[io] wait_for_ssd`).
+const SSD_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// One step of [`SyncEngine::execute_plan`], passed to its progress callback when an action
+/// starts and again when it has finished. main.rs renders these as the terminal progress bar.
+#[derive(Debug, Clone, Copy)]
+pub struct SyncProgress<'a> {
+    /// Position of the action among those started so far (1-based)
+    pub index: usize,
+    /// Number of actions in the plan
+    pub total: usize,
+    /// The action: its path, what is done and whether it is a folder
+    pub entry: &'a SyncPlanEntry,
+    /// Bytes the action copies: the file's size for a file copy, otherwise 0
+    pub bytes: u64,
+    /// Bytes all file copies of the plan add up to
+    pub total_bytes: u64,
+    /// false when the action starts, true once it has finished (whether or not it succeeded)
+    pub done: bool,
 }
 
+/// Progress callback of [`SyncEngine::execute_plan`]; called from the copy workers too.
+pub type ProgressFn<'a> = dyn FnMut(&SyncProgress) + Send + 'a;
+
+#[derive(Default)]
 pub struct SyncResult {
     pub copied_to_ssd: usize,
     pub copied_to_local: usize,
     pub deleted_from_ssd: usize,
     pub deleted_from_local: usize,
+    pub renamed: usize,
     pub conflicts: usize,
     pub errors: Vec<String>,
     pub total_files: usize,
+    /// Planned changes left for a later sync (e.g. by `--only-conflicts`)
+    pub deferred: usize,
+    /// Deletions staged by `[safety] deferred_delete`, carried out by the next sync
+    pub staged_deletes: usize,
+    /// Files over `[scan] max_file_size` that were not synced, with their sizes
+    pub skipped: Vec<(String, u64)>,
+    /// Bytes written by file copies
+    pub bytes_copied: u64,
+    /// Every file copied by `execute_plan`, with how long it took
+    pub copy_times: Vec<CopyTiming>,
+    /// Wall time of `execute_plan` (parallel copies overlap, so this is not the sum of `copy_times`)
+    pub elapsed: Duration,
+}
+
+/// How long one file copy took.
+#[derive(Debug, Clone)]
+pub struct CopyTiming {
+    pub path: String,
+    pub size: u64,
+    pub elapsed: Duration,
+}
+
+/// Returned when `[conflict] strategy = "fail"` finds conflicts; nothing has been changed.
+#[derive(Debug)]
+pub struct ConflictsFound(pub Vec<String>);
+
+impl std::fmt::Display for ConflictsFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} conflict(s) with [conflict] strategy = \"fail\", nothing was changed:",
+            self.0.len()
+        )?;
+        for path in &self.0 {
+            write!(f, "\n    - {}", path)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConflictsFound {}
+
+/// Returned when the plan was shown for confirmation (`[sync_settings] confirm`) and not
+/// accepted; nothing has been changed.
+#[derive(Debug)]
+pub struct SyncDeclined;
+
+impl std::fmt::Display for SyncDeclined {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "sync not confirmed, nothing was changed")
+    }
 }
 
+impl std::error::Error for SyncDeclined {}
+
 impl SyncResult {
     fn new() -> Self {
         Self {
@@ -32,9 +320,37 @@ impl SyncResult {
             copied_to_local: 0,
             deleted_from_ssd: 0,
             deleted_from_local: 0,
+            renamed: 0,
             conflicts: 0,
             errors: vec![],
             total_files: 0,
+            deferred: 0,
+            staged_deletes: 0,
+            skipped: vec![],
+            bytes_copied: 0,
+            copy_times: vec![],
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Count a finished plan entry, or keep its error.
+    fn record(&mut self, entry: &SyncPlanEntry, outcome: Result<()>) {
+        let (counter, label) = match (&entry.action, entry.is_dir) {
+            (SyncAction::CopyToSsd, true) => (&mut self.copied_to_ssd, "CreateDirSsd"),
+            (SyncAction::CopyToSsd, false) => (&mut self.copied_to_ssd, "CopyToSsd"),
+            (SyncAction::CopyToLocal, true) => (&mut self.copied_to_local, "CreateDirLocal"),
+            (SyncAction::CopyToLocal, false) => (&mut self.copied_to_local, "CopyToLocal"),
+            (SyncAction::DeleteFromSsd, true) => (&mut self.deleted_from_ssd, "DeleteDirSsd"),
+            (SyncAction::DeleteFromSsd, false) => (&mut self.deleted_from_ssd, "DeleteFromSsd"),
+            (SyncAction::DeleteFromLocal, true) => (&mut self.deleted_from_local, "DeleteDirLocal"),
+            (SyncAction::DeleteFromLocal, false) => (&mut self.deleted_from_local, "DeleteFromLocal"),
+            (SyncAction::RenameOnSsd { .. }, _) => (&mut self.renamed, "RenameOnSsd"),
+            (SyncAction::RenameOnLocal { .. }, _) => (&mut self.renamed, "RenameOnLocal"),
+            (SyncAction::Conflict(_), _) => (&mut self.conflicts, "Conflict"),
+        };
+        match outcome {
+            Ok(()) => *counter += 1,
+            Err(e) => self.errors.push(format!("{} {}: {}", label, entry.path, e)),
         }
     }
 
@@ -43,132 +359,238 @@ impl SyncResult {
             + self.copied_to_local
             + self.deleted_from_ssd
             + self.deleted_from_local
+            + self.renamed
             + self.conflicts
     }
+
+    /// The `n` copies that took longest, slowest first.
+    pub fn slowest_copies(&self, n: usize) -> Vec<&CopyTiming> {
+        let mut times: Vec<&CopyTiming> = self.copy_times.iter().collect();
+        times.sort_by(|a, b| b.elapsed.cmp(&a.elapsed).then_with(|| a.path.cmp(&b.path)));
+        times.truncate(n);
+        times
+    }
 }
 
 impl SyncEngine {
-    pub fn new(machine_name: &str, conflict_strategy: ConflictStrategy, dry_run: bool, verbose: bool) -> Self {
+    pub fn new(machine_name: &str, conflict_strategy: ConflictStrategy, dry_run: bool) -> Self {
         Self {
             machine_name: machine_name.to_string(),
             conflict_strategy,
             dry_run,
-            verbose,
+            resume_log: None,
+            copy_concurrency: 1,
+            archive: None,
+            names: Mutex::new(NameMap::default()),
+            illegal_char_policy: None,
+            probed_dirs: Mutex::new(HashMap::new()),
+            kept_dirs: Mutex::new(BTreeSet::new()),
+            wait_for_ssd: None,
+            ssd_wait: Mutex::new(()),
+            temp_suffix: crate::config::DEFAULT_TEMP_SUFFIX.to_string(),
+            conflict_marker: "conflict".to_string(),
+            trash: false,
+            follow_symlinks: false,
+            bytes_copied: AtomicU64::new(0),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Whether the SSD root is there to run the next action. With `[io] wait_for_ssd`, a
+    /// missing root is polled for until it is back; on timeout `aborted` is set.
+    /// USB 硬盘盒偶尔会断开并在原挂载点重新出现，短暂等待即可继续，不必中断整个同步；
+    /// 也避免了在断开期间把文件写进挂载点下的本地目录。
+    fn ssd_root_available(&self, ssd_root: &Path, aborted: &AtomicBool) -> bool {
+        let Some(timeout) = self.wait_for_ssd else {
+            return true;
+        };
+        if ssd_root.exists() {
+            return true;
+        }
+        let _waiting = self.ssd_wait.lock().unwrap();
+        if aborted.load(Ordering::SeqCst) {
+            return false;
+        }
+        // 其他 worker 已经等到 SSD 恢复
+        if ssd_root.exists() {
+            return true;
+        }
+        outln!();
+        outln!(
+            "  ⚠ SSD is not accessible ({}), waiting up to {}s for it to come back...",
+            ssd_root.display(),
+            timeout.as_secs()
+        );
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            std::thread::sleep(SSD_POLL_INTERVAL);
+            if ssd_root.exists() {
+                outln!("  SSD is back after {:.1}s, resuming", start.elapsed().as_secs_f64());
+                return true;
+            }
+        }
+        aborted.store(true, Ordering::SeqCst);
+        false
+    }
+
+    /// Dry run pre-flight: fail if `target` could not be created or removed, so the preview
+    /// reports permission problems the real run would hit partway through.
+    /// 通过在最近的已存在上级目录中创建并删除临时文件来探测写权限。
+    fn check_writable(&self, target: &Path) -> Result<()> {
+        let Some(dir) = target.ancestors().skip(1).find(|dir| dir.is_dir()) else {
+            return Ok(());
+        };
+        let mut probed = self.probed_dirs.lock().unwrap();
+        let problem = probed.entry(dir.to_path_buf()).or_insert_with(|| {
+            let probe = dir.join(format!(".ssd-syncer-probe-{}", std::process::id()));
+            match std::fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+                Ok(_) => std::fs::remove_file(&probe)
+                    .err()
+                    .map(|e| format!("Cannot remove files in {}: {}", dir.display(), e)),
+                Err(e) => Some(format!("No write permission in {}: {}", dir.display(), e)),
+            }
+        });
+        match problem {
+            Some(msg) => anyhow::bail!("{}", msg),
+            None => Ok(()),
+        }
+    }
+
+    /// Path of `rel_path` on the SSD. Recorded sanitized names are reused; a new name the SSD's
+    /// filesystem rejects is sanitized or refused according to `[compat] illegal_char_policy`.
+    fn ssd_path(&self, ssd_root: &Path, rel_path: &str) -> Result<PathBuf> {
+        let mut names = self.names.lock().unwrap();
+        let target = names.encode(rel_path);
+        let Some(policy) = self.illegal_char_policy else {
+            return Ok(ssd_root.join(target));
+        };
+        let Some(c) = compat::illegal_char(&target) else {
+            return Ok(ssd_root.join(target));
+        };
+        match policy {
+            IllegalCharPolicy::Error => anyhow::bail!(
+                "illegal filename for target filesystem: {:?} is not allowed on the SSD \
+                 (set [compat] illegal_char_policy = \"sanitize\" to store it under a substitute name)",
+                c
+            ),
+            IllegalCharPolicy::Sanitize => {
+                let sanitized = compat::sanitize(&target);
+                if !self.dry_run {
+                    names.record(rel_path, &sanitized);
+                }
+                Ok(ssd_root.join(sanitized))
+            }
         }
     }
 
+    /// Carry out `plan` in [`SyncPlan::ordered`] order. `progress` hears about every action
+    /// as it starts and finishes; the engine itself prints no per-file output.
     pub fn execute_plan(
         &self,
         plan: &SyncPlan,
         local_root: &Path,
         ssd_root: &Path,
+        progress: &mut ProgressFn,
     ) -> Result<SyncResult> {
-        let mut result = SyncResult::new();
+        let started = Instant::now();
         let total = plan.actions.len();
+        let result = Mutex::new(SyncResult::new());
+        let done = AtomicUsize::new(0);
+        let aborted = AtomicBool::new(false);
+        let ssd_full = AtomicBool::new(false);
+        let skipped_full = AtomicUsize::new(0);
+        let total_bytes = plan.copy_bytes();
+        let progress = Mutex::new(progress);
+        let report = |index: usize, entry: &SyncPlanEntry, done: bool| {
+            let bytes = if is_file_copy(entry) { entry.entry.as_ref().map_or(0, |e| e.size) } else { 0 };
+            (progress.lock().unwrap())(&SyncProgress { index, total, entry, bytes, total_bytes, done });
+        };
+        self.kept_dirs.lock().unwrap().extend([local_root.to_path_buf(), ssd_root.to_path_buf()]);
+        let actions = plan.ordered();
 
-        for (idx, entry) in plan.actions.iter().enumerate() {
-            let progress = format!("[{}/{}]", idx + 1, total);
-            let action_desc = match &entry.action {
-                SyncAction::CopyToSsd => "→ SSD",
-                SyncAction::CopyToLocal => "← Local",
-                SyncAction::DeleteFromSsd => "✕ SSD",
-                SyncAction::DeleteFromLocal => "✕ Local",
-                SyncAction::Conflict(_) => "⚠ Conflict",
-            };
-            if self.verbose {
-                println!("  {} {} {}", progress, action_desc, entry.path);
-            } else {
-                print!("\r  {} {} {}", progress, action_desc, entry.path);
-                // 用空格覆盖可能的残留字符
-                print!("{}", " ".repeat(10));
-                let _ = std::io::stdout().flush();
-            }
-            match &entry.action {
-                SyncAction::CopyToSsd => {
-                    if entry.is_dir {
-                        if let Err(e) = self.create_dir(&ssd_root.join(&entry.path)) {
-                            result.errors.push(format!("CreateDirSsd {}: {}", entry.path, e));
-                        } else {
-                            result.copied_to_ssd += 1;
-                        }
-                    } else if let Err(e) = self.copy_file(
-                        &local_root.join(&entry.path),
-                        &ssd_root.join(&entry.path),
-                    ) {
-                        result
-                            .errors
-                            .push(format!("CopyToSsd {}: {}", entry.path, e));
-                    } else {
-                        result.copied_to_ssd += 1;
-                    }
-                }
-                SyncAction::CopyToLocal => {
-                    if entry.is_dir {
-                        if let Err(e) = self.create_dir(&local_root.join(&entry.path)) {
-                            result.errors.push(format!("CreateDirLocal {}: {}", entry.path, e));
-                        } else {
-                            result.copied_to_local += 1;
-                        }
-                    } else if let Err(e) = self.copy_file(
-                        &ssd_root.join(&entry.path),
-                        &local_root.join(&entry.path),
-                    ) {
-                        result
-                            .errors
-                            .push(format!("CopyToLocal {}: {}", entry.path, e));
-                    } else {
-                        result.copied_to_local += 1;
-                    }
+        let run = |entry: &SyncPlanEntry| {
+            if aborted.load(Ordering::SeqCst) || !self.ssd_root_available(ssd_root, &aborted) {
+                return;
+            }
+            // SSD 已满：剩余复制到 SSD 的动作不再尝试，下次同步会重新提出
+            let to_ssd = matches!(entry.action, SyncAction::CopyToSsd);
+            if to_ssd && ssd_full.load(Ordering::SeqCst) {
+                skipped_full.fetch_add(1, Ordering::SeqCst);
+                return;
+            }
+            let idx = done.fetch_add(1, Ordering::SeqCst);
+            report(idx + 1, entry, false);
+            let copy_start = Instant::now();
+            let mut outcome = self.execute_entry(entry, local_root, ssd_root);
+            // 执行过程中 SSD 断开：等它恢复后重试一次
+            if outcome.is_err() && self.wait_for_ssd.is_some() && !ssd_root.exists() {
+                if !self.ssd_root_available(ssd_root, &aborted) {
+                    return;
                 }
-                SyncAction::DeleteFromSsd => {
-                    if entry.is_dir {
-                        if let Err(e) = self.delete_dir(&ssd_root.join(&entry.path)) {
-                            result.errors.push(format!("DeleteDirSsd {}: {}", entry.path, e));
-                        } else {
-                            result.deleted_from_ssd += 1;
-                        }
-                    } else if let Err(e) = self.delete_file(&ssd_root.join(&entry.path)) {
-                        result
-                            .errors
-                            .push(format!("DeleteFromSsd {}: {}", entry.path, e));
-                    } else {
-                        result.deleted_from_ssd += 1;
-                    }
+                outcome = self.execute_entry(entry, local_root, ssd_root);
+            }
+            // 复制到 SSD 或处理冲突时写满 SSD（冲突副本也会写到 SSD）
+            report(idx + 1, entry, true);
+            if outcome.as_ref().is_err_and(|e| e.downcast_ref::<SsdFull>().is_some()) {
+                if !ssd_full.swap(true, Ordering::SeqCst) {
+                    log::warn!("SSD full while writing {}, stopping", entry.path);
                 }
-                SyncAction::DeleteFromLocal => {
-                    if entry.is_dir {
-                        if let Err(e) = self.delete_dir(&local_root.join(&entry.path)) {
-                            result.errors.push(format!("DeleteDirLocal {}: {}", entry.path, e));
-                        } else {
-                            result.deleted_from_local += 1;
+                skipped_full.fetch_add(1, Ordering::SeqCst);
+                return;
+            }
+            let mut result = result.lock().unwrap();
+            if outcome.is_ok() && !self.dry_run && is_file_copy(entry) {
+                result.copy_times.push(CopyTiming {
+                    path: entry.path.clone(),
+                    size: entry.entry.as_ref().map_or(0, |e| e.size),
+                    elapsed: copy_start.elapsed(),
+                });
+            }
+            result.record(entry, outcome);
+        };
+
+        if self.copy_concurrency <= 1 {
+            actions.into_iter().for_each(run);
+        } else {
+            // 目录创建、改名和冲突处理先串行执行，互相独立的文件复制交给 worker 并行处理，
+            // 删除等复制全部完成后再串行执行
+            let first_copy = actions.iter().position(|e| is_file_copy(e)).unwrap_or(actions.len());
+            let after_copies = actions.iter().rposition(|e| is_file_copy(e)).map_or(actions.len(), |i| i + 1);
+            actions[..first_copy].iter().for_each(|e| run(e));
+
+            let copies = &actions[first_copy..after_copies];
+            let next = AtomicUsize::new(0);
+            std::thread::scope(|scope| {
+                for _ in 0..self.copy_concurrency.min(copies.len()) {
+                    scope.spawn(|| {
+                        while let Some(entry) = copies.get(next.fetch_add(1, Ordering::SeqCst)) {
+                            run(entry);
                         }
-                    } else if let Err(e) = self.delete_file(&local_root.join(&entry.path)) {
-                        result
-                            .errors
-                            .push(format!("DeleteFromLocal {}: {}", entry.path, e));
-                    } else {
-                        result.deleted_from_local += 1;
-                    }
-                }
-                SyncAction::Conflict(info) => {
-                    if let Err(e) =
-                        self.handle_conflict(&entry.path, info, local_root, ssd_root)
-                    {
-                        result
-                            .errors
-                            .push(format!("Conflict {}: {}", entry.path, e));
-                    } else {
-                        result.conflicts += 1;
-                    }
+                    });
                 }
-            }
+            });
+            actions[after_copies..].iter().for_each(|e| run(e));
+        }
+
+        let mut result = result.into_inner().unwrap();
+        result.elapsed = started.elapsed();
+        if ssd_full.load(Ordering::SeqCst) {
+            // 只报告一次，而不是每个剩余文件各报一条错误
+            result.errors.push(format!(
+                "SSD full, stopping: {} copy(ies) to the SSD were not done. Free up space and sync again",
+                skipped_full.load(Ordering::SeqCst)
+            ));
         }
+        result.bytes_copied = self.bytes_copied.load(Ordering::SeqCst);
 
-        // compact 模式下清除进度行
-        if !self.verbose && total > 0 {
-            print!("\r{}", " ".repeat(80));
-            print!("\r");
-            let _ = std::io::stdout().flush();
+        if aborted.load(Ordering::SeqCst) {
+            // 快照不更新；续传记录保留，重新运行 sync 会跳过已完成的复制
+            anyhow::bail!(
+                "SSD did not come back within {}s; stopped after {} of {} actions. Run sync again to resume",
+                self.wait_for_ssd.unwrap_or_default().as_secs(),
+                done.load(Ordering::SeqCst),
+                total
+            );
         }
 
         // 通知 Windows 资源管理器刷新所有受影响的目录
@@ -194,27 +616,101 @@ impl SyncEngine {
         Ok(result)
     }
 
+    /// Execute a single plan entry.
+    fn execute_entry(&self, entry: &SyncPlanEntry, local_root: &Path, ssd_root: &Path) -> Result<()> {
+        let local_path = local_root.join(&entry.path);
+        let ssd_path = self.ssd_path(ssd_root, &entry.path)?;
+        match &entry.action {
+            SyncAction::CopyToSsd => {
+                let ssd_rel = ssd_path.strip_prefix(ssd_root).unwrap_or(&ssd_path).to_string_lossy();
+                self.clear_type_mismatch(ssd_root, &ssd_rel, entry.is_dir)?
+            }
+            SyncAction::CopyToLocal => self.clear_type_mismatch(local_root, &entry.path, entry.is_dir)?,
+            _ => {}
+        }
+        match &entry.action {
+            SyncAction::CopyToSsd if entry.is_dir => on_ssd(self.create_dir(&ssd_path)),
+            SyncAction::CopyToSsd => {
+                on_ssd(self.copy_into(&local_path, &ssd_path))?;
+                self.record_resume(entry, &ssd_path, true);
+                Ok(())
+            }
+            SyncAction::CopyToLocal if entry.is_dir => self.create_dir(&local_path),
+            SyncAction::CopyToLocal => {
+                self.copy_into(&ssd_path, &local_path)?;
+                self.record_resume(entry, &local_path, false);
+                Ok(())
+            }
+            SyncAction::DeleteFromSsd if entry.is_dir => self.delete_dir(&ssd_path),
+            SyncAction::DeleteFromSsd => self.delete_file(&ssd_path),
+            SyncAction::DeleteFromLocal if entry.is_dir => self.delete_dir(&local_path),
+            SyncAction::DeleteFromLocal => self.delete_file(&local_path),
+            SyncAction::RenameOnSsd { from } => {
+                self.rename_entry(&self.ssd_path(ssd_root, from)?, &ssd_path)
+            }
+            SyncAction::RenameOnLocal { from } => self.rename_entry(&local_root.join(from), &local_path),
+            SyncAction::Conflict(info) => {
+                self.handle_conflict(&entry.path, info, local_root, ssd_root)
+            }
+        }
+    }
+
+    /// 记录已完成的复制，供中断后续传使用
+    fn record_resume(&self, entry: &SyncPlanEntry, dst: &Path, to_ssd: bool) {
+        let (Some(resume), Some(source)) = (&self.resume_log, &entry.entry) else {
+            return;
+        };
+        if self.dry_run {
+            return;
+        }
+        let Ok(meta) = std::fs::symlink_metadata(dst) else {
+            return;
+        };
+        let record = ResumeEntry {
+            path: entry.path.clone(),
+            to_ssd,
+            source: source.clone(),
+            dest: FileEntry {
+                size: meta.len(),
+                mtime_secs: scanner::mtime_secs(&meta),
+                hash: source.hash.clone(),
+                is_dir: false,
+                version: 0,
+                is_symlink: source.is_symlink,
+            },
+        };
+        if let Err(e) = resume.record(&record) {
+            log::debug!("Failed to record resume entry for {}: {}", entry.path, e);
+        }
+    }
+
     fn create_dir(&self, path: &Path) -> Result<()> {
         if self.dry_run {
             log::info!("[DRY RUN] Create dir {}", path.display());
-            return Ok(());
+            return self.check_writable(path);
         }
 
         std::fs::create_dir_all(path)
             .with_context(|| format!("Failed to create dir: {}", path.display()))?;
         log::debug!("Created dir {}", path.display());
+        self.kept_dirs.lock().unwrap().insert(path.to_path_buf());
         Ok(())
     }
 
     fn delete_dir(&self, path: &Path) -> Result<()> {
         if self.dry_run {
             log::info!("[DRY RUN] Delete dir {}", path.display());
-            return Ok(());
+            return self.check_writable(path);
         }
 
         if path.exists() && path.is_dir() {
-            std::fs::remove_dir(path)
-                .with_context(|| format!("Failed to delete dir: {}", path.display()))?;
+            // 目录里还有文件（例如同步期间新建的）时不放进回收站：remove_dir 报错，文件保留
+            let outcome = if path.read_dir()?.next().is_none() {
+                self.remove(path, |p| std::fs::remove_dir(p))
+            } else {
+                std::fs::remove_dir(path)
+            };
+            outcome.with_context(|| format!("Failed to delete dir: {}", path.display()))?;
             log::debug!("Deleted dir {}", path.display());
             self.cleanup_empty_parents(path)?;
         }
@@ -222,34 +718,132 @@ impl SyncEngine {
         Ok(())
     }
 
-    fn copy_file(&self, src: &Path, dst: &Path) -> Result<()> {
+    /// Rename a folder or file within one side (renames detected by the diff).
+    fn rename_entry(&self, src: &Path, dst: &Path) -> Result<()> {
         if self.dry_run {
-            log::info!("[DRY RUN] Copy {} -> {}", src.display(), dst.display());
-            return Ok(());
+            log::info!("[DRY RUN] Rename {} -> {}", src.display(), dst.display());
+            self.check_writable(src)?;
+            return self.check_writable(dst);
         }
 
+        if exists(dst) {
+            anyhow::bail!("Rename target already exists: {}", dst.display());
+        }
         if let Some(parent) = dst.parent() {
             std::fs::create_dir_all(parent)
                 .with_context(|| format!("Failed to create dir: {}", parent.display()))?;
         }
-
-        std::fs::copy(src, dst).with_context(|| {
-            format!("Failed to copy {} -> {}", src.display(), dst.display())
+        std::fs::rename(src, dst).with_context(|| {
+            format!("Failed to rename {} -> {}", src.display(), dst.display())
         })?;
+        log::debug!("Renamed {} -> {}", src.display(), dst.display());
+        self.cleanup_empty_parents(src)?;
+        Ok(())
+    }
+
+    /// Copy `src` to `dst`, creating the folder it goes into first. Empty folders are plan
+    /// actions of their own and run before any copy; this covers folders that already hold
+    /// files on the source side. Each folder is created once per run, not once per file.
+    fn copy_into(&self, src: &Path, dst: &Path) -> Result<()> {
+        if let (Some(parent), false) = (dst.parent(), self.dry_run) {
+            if !self.kept_dirs.lock().unwrap().contains(parent) {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create dir: {}", parent.display()))?;
+                self.kept_dirs.lock().unwrap().insert(parent.to_path_buf());
+            }
+        }
+        self.copy_file(src, dst)
+    }
+
+    /// Copy a file into an existing folder, via a temporary file renamed into place.
+    fn copy_file(&self, src: &Path, dst: &Path) -> Result<()> {
+        if self.dry_run {
+            log::info!("[DRY RUN] Copy {} -> {}", src.display(), dst.display());
+            if std::fs::metadata(dst).is_ok_and(|m| m.is_file() && m.permissions().readonly()) {
+                anyhow::bail!("File is read-only: {}", dst.display());
+            }
+            return self.check_writable(dst);
+        }
+
+        if let Some(archive) = &self.archive {
+            archive.preserve(dst)?;
+        }
+
+        if !self.follow_symlinks && is_symlink(src) {
+            return self.copy_symlink(src, dst);
+        }
+
+        // 改名覆盖会绕过只读属性：与 dry run 的检查保持一致
+        if std::fs::metadata(dst).is_ok_and(|m| m.is_file() && m.permissions().readonly()) {
+            anyhow::bail!("File is read-only: {}", dst.display());
+        }
+
+        // 先写到临时文件再改名：中断的复制不会留下半个目标文件
+        let temp = self.temp_path(dst);
+        let copied = self
+            .retry
+            .run(&format!("Copy of {}", src.display()), || std::fs::copy(src, &temp))
+            .with_context(|| format!("Failed to copy {} -> {}", src.display(), dst.display()))
+            .and_then(|bytes| {
+                // 保留源文件的 mtime：newer-wins 比较的是修改时间，扫描缓存也按 size/mtime 复用哈希
+                if let Err(e) = copy_mtime(src, &temp) {
+                    log::warn!("Failed to preserve modification time of {}: {}", dst.display(), e);
+                }
+                self.retry
+                    .run(&format!("Rename to {}", dst.display()), || std::fs::rename(&temp, dst))
+                    .with_context(|| format!("Failed to move copy into place: {}", dst.display()))
+                    .map(|_| bytes)
+            });
+        if copied.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+        self.bytes_copied.fetch_add(copied?, Ordering::SeqCst);
 
         log::debug!("Copied {} -> {}", src.display(), dst.display());
         Ok(())
     }
 
+    /// Recreate the symlink `src` at `dst` with the same target (which may not exist there).
+    fn copy_symlink(&self, src: &Path, dst: &Path) -> Result<()> {
+        let target = std::fs::read_link(src)
+            .with_context(|| format!("Failed to read symlink: {}", src.display()))?;
+        let temp = self.temp_path(dst);
+        let _ = std::fs::remove_file(&temp);
+        create_symlink(&target, &temp)
+            .with_context(|| format!("Failed to create symlink {} -> {}", dst.display(), target.display()))?;
+        if let Err(e) = std::fs::rename(&temp, dst) {
+            let _ = std::fs::remove_file(&temp);
+            return Err(e).with_context(|| format!("Failed to move symlink into place: {}", dst.display()));
+        }
+        log::debug!("Linked {} -> {}", dst.display(), target.display());
+        Ok(())
+    }
+
+    /// `<dst><temp_suffix>`: copies are written here and renamed into place when complete
+    fn temp_path(&self, dst: &Path) -> PathBuf {
+        let mut temp_name = dst.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(&self.temp_suffix);
+        dst.with_file_name(temp_name)
+    }
+
     fn delete_file(&self, path: &Path) -> Result<()> {
         if self.dry_run {
             log::info!("[DRY RUN] Delete {}", path.display());
-            return Ok(());
+            return self.check_writable(path);
         }
 
-        if path.exists() {
-            std::fs::remove_file(path)
-                .with_context(|| format!("Failed to delete: {}", path.display()))?;
+        if exists(path) {
+            let archived = match &self.archive {
+                Some(archive) => archive.preserve(path)?,
+                None => false,
+            };
+            if !archived {
+                self.retry
+                    .run(&format!("Delete of {}", path.display()), || {
+                        self.remove(path, |p| std::fs::remove_file(p))
+                    })
+                    .with_context(|| format!("Failed to delete: {}", path.display()))?;
+            }
             log::debug!("Deleted {}", path.display());
 
             // Clean up empty parent directories
@@ -259,9 +853,33 @@ impl SyncEngine {
         Ok(())
     }
 
+    /// Delete `path` with `delete`, or move it to the system trash when `trash` is set. If the
+    /// trash is unavailable (e.g. no trash folder on the drive), it is deleted permanently.
+    fn remove(&self, path: &Path, delete: fn(&Path) -> std::io::Result<()>) -> std::io::Result<()> {
+        if self.trash {
+            match trash::delete(path) {
+                Ok(()) => {
+                    log::debug!("Moved {} to the trash", path.display());
+                    return Ok(());
+                }
+                Err(e) => log::warn!(
+                    "Cannot move {} to the trash, deleting it permanently: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+        delete(path)
+    }
+
     fn cleanup_empty_parents(&self, path: &Path) -> Result<()> {
+        let kept = self.kept_dirs.lock().unwrap();
         let mut current = path.parent();
         while let Some(dir) = current {
+            // 同步根目录以及本次同步特意创建的空目录不删除，否则另一侧的空目录会被抹掉
+            if kept.contains(dir) {
+                break;
+            }
             if dir.read_dir()?.next().is_none() {
                 std::fs::remove_dir(dir).ok();
                 current = dir.parent();
@@ -275,12 +893,12 @@ impl SyncEngine {
     fn handle_conflict(
         &self,
         rel_path: &str,
-        _info: &ConflictInfo,
+        info: &ConflictInfo,
         local_root: &Path,
         ssd_root: &Path,
     ) -> Result<()> {
         let local_path = local_root.join(rel_path);
-        let ssd_path = ssd_root.join(rel_path);
+        let ssd_path = self.ssd_path(ssd_root, rel_path)?;
 
         match &self.conflict_strategy {
             ConflictStrategy::Both => {
@@ -288,34 +906,159 @@ impl SyncEngine {
             }
             ConflictStrategy::LocalWins => {
                 // Local version wins: copy local to SSD
-                if local_path.exists() {
-                    self.copy_file(&local_path, &ssd_path)
+                if exists(&local_path) {
+                    on_ssd(self.copy_into(&local_path, &ssd_path))
                 } else {
                     self.delete_file(&ssd_path)
                 }
             }
             ConflictStrategy::SsdWins => {
                 // SSD version wins: copy SSD to local
-                if ssd_path.exists() {
-                    self.copy_file(&ssd_path, &local_path)
+                if exists(&ssd_path) {
+                    self.copy_into(&ssd_path, &local_path)
                 } else {
                     self.delete_file(&local_path)
                 }
             }
             ConflictStrategy::NewerWins => {
-                self.resolve_newer(&local_path, &ssd_path)
+                self.resolve_newer(&local_path, &ssd_path, info.versions)
+            }
+            ConflictStrategy::Fail => {
+                // 正常情况下不会执行到这里：计划中有冲突时同步在执行前就已中止
+                Err(ConflictsFound(vec![rel_path.to_string()]).into())
             }
             ConflictStrategy::Ask => {
                 // In non-interactive mode, fall back to Both
                 log::warn!(
-                    "Conflict on '{}': interactive mode not available, keeping both versions",
-                    rel_path
+                    "Conflict on '{}': interactive mode not available, keeping both versions (local: {}; SSD: {})",
+                    rel_path,
+                    info.local_delta(),
+                    info.ssd_delta()
                 );
                 self.resolve_both(rel_path, &local_path, &ssd_path, local_root, ssd_root)
             }
         }
     }
 
+    /// Conflict copy name: `<stem>.<marker>.<machine>.<timestamp><.ext>` next to the original.
+    /// `taken` tells whether a candidate is already in use; a counter (`.1`, `.2`, ...) is then
+    /// added before the extension.
+    fn conflict_rel(&self, rel_path: &str, taken: impl Fn(&str) -> bool) -> String {
+        let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S").to_string();
+        self.conflict_rel_at(rel_path, &timestamp, taken)
+    }
+
+    fn conflict_rel_at(&self, rel_path: &str, timestamp: &str, taken: impl Fn(&str) -> bool) -> String {
+        let path_obj = Path::new(rel_path);
+        let file_name = path_obj
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        // 点开头的文件（.env）和以点结尾的名字没有扩展名：整个名字作为主干
+        let (stem, extension) = match file_name.rfind('.') {
+            Some(i) if i > 0 && i + 1 < file_name.len() => file_name.split_at(i),
+            _ => (file_name.as_str(), ""),
+        };
+        let parent = path_obj.parent().unwrap_or(Path::new(""));
+
+        // 同一秒内同一文件再次冲突（或重新运行）时，加序号避免覆盖已有的冲突副本
+        let base = format!("{}.{}.{}.{}", stem, self.conflict_marker, self.machine_name, timestamp);
+        let mut counter = 0;
+        loop {
+            let conflict_name = if counter == 0 {
+                format!("{}{}", base, extension)
+            } else {
+                format!("{}.{}{}", base, counter, extension)
+            };
+            let candidate = if parent == Path::new("") {
+                conflict_name
+            } else {
+                format!("{}/{}", parent.display(), conflict_name)
+            };
+            if !taken(&candidate) {
+                return candidate;
+            }
+            counter += 1;
+        }
+    }
+
+    /// 目标路径（或其上级目录）的类型与计划不符时（文件 ↔ 目录），先处理掉挡路的条目：
+    /// `both`/`ask` 策略将其改名为冲突副本保留，其余策略直接删除（SSD 侧开启归档时归档文件）。
+    fn clear_type_mismatch(&self, root: &Path, rel_path: &str, expect_dir: bool) -> Result<()> {
+        let components: Vec<_> = Path::new(rel_path).components().collect();
+        let mut current = root.to_path_buf();
+        let mut obstacle = None;
+        for (i, component) in components.iter().enumerate() {
+            current.push(component);
+            // 跟随符号链接时，指向目录的链接就是目录
+            let meta = if self.follow_symlinks {
+                std::fs::metadata(&current)
+            } else {
+                std::fs::symlink_metadata(&current)
+            };
+            let Ok(meta) = meta else {
+                break;
+            };
+            let is_last = i + 1 == components.len();
+            let mismatch = if is_last { meta.is_dir() != expect_dir } else { !meta.is_dir() };
+            if mismatch {
+                obstacle = Some((current.clone(), meta.is_dir()));
+                break;
+            }
+        }
+        let Some((obstacle, obstacle_is_dir)) = obstacle else {
+            return Ok(());
+        };
+
+        let obstacle_rel = obstacle
+            .strip_prefix(root)
+            .unwrap_or(&obstacle)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let (found, wanted) = if obstacle_is_dir { ("directory", "file") } else { ("file", "directory") };
+
+        if self.dry_run {
+            log::info!(
+                "[DRY RUN] '{}' is a {} where a {} is expected; would move it out of the way",
+                obstacle_rel, found, wanted
+            );
+            return Ok(());
+        }
+
+        match self.conflict_strategy {
+            ConflictStrategy::Both | ConflictStrategy::Ask => {
+                let aside = root.join(self.conflict_rel(&obstacle_rel, |c| exists(&root.join(c))));
+                std::fs::rename(&obstacle, &aside).with_context(|| {
+                    format!("Failed to move {} -> {}", obstacle.display(), aside.display())
+                })?;
+                log::warn!(
+                    "'{}' was a {} where a {} is expected; kept it as '{}'",
+                    obstacle_rel, found, wanted, aside.display()
+                );
+            }
+            _ => {
+                if obstacle_is_dir {
+                    self.remove(&obstacle, |p| std::fs::remove_dir_all(p))
+                        .with_context(|| format!("Failed to delete dir: {}", obstacle.display()))?;
+                } else {
+                    let archived = match &self.archive {
+                        Some(archive) => archive.preserve(&obstacle)?,
+                        None => false,
+                    };
+                    if !archived {
+                        self.remove(&obstacle, |p| std::fs::remove_file(p))
+                            .with_context(|| format!("Failed to delete: {}", obstacle.display()))?;
+                    }
+                }
+                log::warn!(
+                    "'{}' was a {} where a {} is expected; replaced it",
+                    obstacle_rel, found, wanted
+                );
+            }
+        }
+        Ok(())
+    }
+
     fn resolve_both(
         &self,
         rel_path: &str,
@@ -324,8 +1067,6 @@ impl SyncEngine {
         local_root: &Path,
         ssd_root: &Path,
     ) -> Result<()> {
-        let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
-
         if self.dry_run {
             log::info!(
                 "[DRY RUN] Conflict '{}': would keep both versions",
@@ -334,31 +1075,15 @@ impl SyncEngine {
             return Ok(());
         }
 
-        // Generate conflict file names
-        let path_obj = Path::new(rel_path);
-        let stem = path_obj
-            .file_stem()
-            .map(|s| s.to_string_lossy().to_string())
-            .unwrap_or_default();
-        let extension = path_obj
-            .extension()
-            .map(|e| format!(".{}", e.to_string_lossy()))
-            .unwrap_or_default();
-        let parent = path_obj.parent().unwrap_or(Path::new(""));
-
-        let conflict_name = format!(
-            "{}.conflict.{}.{}{}",
-            stem, self.machine_name, timestamp, extension
-        );
-        let conflict_rel = if parent == Path::new("") {
-            conflict_name.clone()
-        } else {
-            format!("{}/{}", parent.display(), conflict_name)
-        };
+        // 冲突副本会同时出现在两侧：两侧都不存在的名字才可用
+        let conflict_rel = self.conflict_rel(rel_path, |candidate| {
+            exists(&local_root.join(candidate))
+                || exists(&ssd_root.join(self.names.lock().unwrap().encode(candidate)))
+        });
 
         // Keep SSD version as-is in both locations
         // Rename local version with conflict suffix in both locations
-        if local_path.exists() && ssd_path.exists() {
+        if exists(local_path) && exists(ssd_path) {
             // Copy SSD version to local (overwrite local with SSD version)
             let local_conflict = local_root.join(&conflict_rel);
             // Rename current local file to conflict name
@@ -369,24 +1094,24 @@ impl SyncEngine {
             // Copy SSD version to local
             self.copy_file(ssd_path, local_path)?;
             // Also copy conflict version to SSD
-            let ssd_conflict = ssd_root.join(&conflict_rel);
-            self.copy_file(&local_conflict, &ssd_conflict)?;
+            let ssd_conflict = self.ssd_path(ssd_root, &conflict_rel)?;
+            on_ssd(self.copy_file(&local_conflict, &ssd_conflict))?;
 
             log::warn!(
                 "Conflict '{}': kept both. SSD version → original name, local version → '{}'",
                 rel_path,
                 conflict_rel
             );
-        } else if local_path.exists() {
+        } else if exists(local_path) {
             // SSD was deleted but local was modified → keep local, copy to SSD
-            self.copy_file(local_path, ssd_path)?;
+            on_ssd(self.copy_into(local_path, ssd_path))?;
             log::warn!(
                 "Conflict '{}': SSD deleted but local modified → kept local version",
                 rel_path
             );
-        } else if ssd_path.exists() {
+        } else if exists(ssd_path) {
             // Local was deleted but SSD was modified → keep SSD, copy to local
-            self.copy_file(ssd_path, local_path)?;
+            self.copy_into(ssd_path, local_path)?;
             log::warn!(
                 "Conflict '{}': local deleted but SSD modified → kept SSD version",
                 rel_path
@@ -396,32 +1121,36 @@ impl SyncEngine {
         Ok(())
     }
 
-    fn resolve_newer(&self, local_path: &Path, ssd_path: &Path) -> Result<()> {
-        let local_mtime = local_path
-            .metadata()
+    /// Keep the side that advanced further from the base (version counters, if enabled),
+    /// otherwise the one modified last.
+    fn resolve_newer(&self, local_path: &Path, ssd_path: &Path, versions: Option<(u64, u64)>) -> Result<()> {
+        let local_mtime = std::fs::symlink_metadata(local_path)
             .ok()
             .and_then(|m| m.modified().ok())
             .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
             .map(|d| d.as_secs())
             .unwrap_or(0);
 
-        let ssd_mtime = ssd_path
-            .metadata()
+        let ssd_mtime = std::fs::symlink_metadata(ssd_path)
             .ok()
             .and_then(|m| m.modified().ok())
             .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
             .map(|d| d.as_secs())
             .unwrap_or(0);
 
-        if local_mtime >= ssd_mtime {
-            if local_path.exists() {
-                self.copy_file(local_path, ssd_path)
+        let local_wins = match versions {
+            Some((local, ssd)) if local != ssd => local > ssd,
+            _ => local_mtime >= ssd_mtime,
+        };
+        if local_wins {
+            if exists(local_path) {
+                on_ssd(self.copy_into(local_path, ssd_path))
             } else {
                 self.delete_file(ssd_path)
             }
         } else {
-            if ssd_path.exists() {
-                self.copy_file(ssd_path, local_path)
+            if exists(ssd_path) {
+                self.copy_into(ssd_path, local_path)
             } else {
                 self.delete_file(local_path)
             }
@@ -429,6 +1158,32 @@ impl SyncEngine {
     }
 }
 
+/// Like `Path::exists`, but also true for a symlink whose target is missing.
+fn exists(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok()
+}
+
+fn is_symlink(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn create_symlink(_target: &Path, _link: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "symlinks can only be recreated on Unix; set [scan] follow_symlinks = true to copy their targets",
+    ))
+}
+
+fn is_file_copy(entry: &SyncPlanEntry) -> bool {
+    !entry.is_dir && matches!(entry.action, SyncAction::CopyToSsd | SyncAction::CopyToLocal)
+}
+
 /// 通知操作系统文件管理器刷新目录显示
 #[cfg(target_os = "windows")]
 fn notify_shell_update(path: &Path) {
@@ -454,24 +1209,461 @@ fn notify_shell_update(_path: &Path) {
     // macOS/Linux 文件管理器通常会自动刷新
 }
 
-/// Run a full sync for one mapping (从磁盘加载快照).
-pub fn sync_one_mapping(
+/// Give `dst` the modification time of `src`.
+fn copy_mtime(src: &Path, dst: &Path) -> std::io::Result<()> {
+    let mtime = std::fs::metadata(src)?.modified()?;
+    // Unix 上文件所有者可通过只读句柄修改时间（目标文件可能是只读的）；Windows 需要写权限
+    let file = if cfg!(windows) {
+        std::fs::OpenOptions::new().write(true).open(dst)?
+    } else {
+        std::fs::File::open(dst)?
+    };
+    file.set_modified(mtime)
+}
+
+/// Short label of an action in progress and `--explain` output.
+pub fn action_label(action: &SyncAction) -> &'static str {
+    match action {
+        SyncAction::CopyToSsd => "→ SSD",
+        SyncAction::CopyToLocal => "← Local",
+        SyncAction::DeleteFromSsd => "✕ SSD",
+        SyncAction::DeleteFromLocal => "✕ Local",
+        SyncAction::RenameOnSsd { .. } => "↻ SSD",
+        SyncAction::RenameOnLocal { .. } => "↻ Local",
+        SyncAction::Conflict(_) => "⚠ Conflict",
+    }
+}
+
+/// With `--subpath`: the state of everything outside the subpath, taken from the base
+/// (local) and the base with SSD sizes/mtimes (SSD), so that the diff sees it as unchanged.
+fn outside_subpath(opts: &SyncOptions, base: &Snapshot, ssd_cache: &Snapshot) -> Option<(Snapshot, Snapshot)> {
+    opts.subpath.as_ref()?;
+    Some((base.clone(), crate::diff::rebase_stats(base, ssd_cache)))
+}
+
+/// `scanner::scan_pair`, or with `outside` set only the `--subpath` folder on each side,
+/// grafted onto `outside`. Entries outside the subpath are neither scanned nor dropped.
+fn scan_sides(
     local_root: &Path,
-    ssd_data_root: &Path,
+    ssd_folder: &Path,
     ssd_rel: &str,
     machine_name: &str,
-    ignore: &IgnoreMatcher,
-    conflict_strategy: &ConflictStrategy,
-    dry_run: bool,
-    verbose: bool,
+    opts: &SyncOptions,
+    (local_cache, ssd_cache): (&Snapshot, &Snapshot),
+    outside: Option<&(Snapshot, Snapshot)>,
+) -> Result<(Snapshot, Snapshot)> {
+    let (Some(sub), Some((outside_local, outside_ssd))) = (opts.subpath.as_deref(), outside) else {
+        return scanner::scan_pair(
+            local_root, ssd_folder, ssd_rel, machine_name, &opts.scan, Some(local_cache), Some(ssd_cache));
+    };
+    let (local_dir, ssd_dir) = (local_root.join(sub), ssd_folder.join(sub));
+    let (local_cache, ssd_cache) = (local_cache.subtree(sub), ssd_cache.subtree(sub));
+    let scan_one = |dir: &Path, cache: &Snapshot| {
+        scanner::scan_directory(dir, ssd_rel, machine_name, &opts.scan, Some(cache))
+    };
+    // 子目录只在一侧存在时，另一侧按空目录处理（新建或整体删除由基准决定）
+    let (sub_local, sub_ssd) = match (local_dir.is_dir(), ssd_dir.is_dir()) {
+        (true, true) => scanner::scan_pair(
+            &local_dir, &ssd_dir, ssd_rel, machine_name, &opts.scan, Some(&local_cache), Some(&ssd_cache))?,
+        (true, false) => (scan_one(&local_dir, &local_cache)?, Snapshot::new(ssd_rel, machine_name)),
+        (false, true) => (Snapshot::new(ssd_rel, machine_name), scan_one(&ssd_dir, &ssd_cache)?),
+        (false, false) => anyhow::bail!("'{}' is not a folder on either side of the mapping", sub),
+    };
+    let (mut local, mut ssd) = (outside_local.clone(), outside_ssd.clone());
+    local.graft(sub, sub_local);
+    ssd.graft(sub, sub_ssd);
+    Ok((local, ssd))
+}
+
+/// Leave out files that were still being written during the scan. All in-flux paths are
+/// returned (planned or not) so the base keeps their last synced state.
+fn defer_in_flux(plan: &mut SyncPlan, local: &Snapshot, ssd: &Snapshot) -> Vec<String> {
+    let in_flux: BTreeSet<&String> = local.in_flux.iter().chain(&ssd.in_flux).collect();
+    if in_flux.is_empty() {
+        return vec![];
+    }
+    let mut deferred = plan.defer_unless(|entry| !in_flux.contains(&entry.path));
+    deferred.extend(in_flux.into_iter().cloned());
+    deferred
+}
+
+/// Leave out changes to files over `[scan] max_file_size` on either side. As with in-flux
+/// files, all such paths are returned so the base keeps their last synced state.
+fn defer_oversized(plan: &mut SyncPlan, local: &Snapshot, ssd: &Snapshot) -> Vec<String> {
+    let oversized: BTreeSet<&String> = local.oversized.keys().chain(ssd.oversized.keys()).collect();
+    if oversized.is_empty() {
+        return vec![];
+    }
+    let mut deferred = plan.defer_unless(|entry| !oversized.contains(&entry.path));
+    deferred.extend(oversized.into_iter().cloned());
+    deferred
+}
+
+/// Files over `[scan] max_file_size` on either side, largest size when both sides have one.
+fn oversized_files(local: &Snapshot, ssd: &Snapshot) -> Vec<(String, u64)> {
+    let mut files = local.oversized.clone();
+    for (path, size) in &ssd.oversized {
+        let entry = files.entry(path.clone()).or_default();
+        *entry = (*entry).max(*size);
+    }
+    files.into_iter().collect()
+}
+
+/// `[safety] deferred_delete`: deletions the previous sync did not stage are held back and
+/// returned, to be staged in the new base. Deletions staged last time (file still gone) stay.
+/// 子目录被临时卸载、扫描中断等情况会让文件短暂“消失”，分两次确认可避免误删。
+fn stage_deletions(plan: &mut SyncPlan, pending: &BTreeSet<String>) -> Vec<String> {
+    plan.defer_unless(|entry| {
+        !matches!(entry.action, SyncAction::DeleteFromSsd | SyncAction::DeleteFromLocal)
+            || pending.contains(&entry.path)
+    })
+}
+
+/// Deletions to record in the new base: those staged now, plus earlier ones outside
+/// `--subpath` (not looked at in this run).
+fn pending_deletes(opts: &SyncOptions, base: &Snapshot, staged: &[String]) -> BTreeSet<String> {
+    let mut pending: BTreeSet<String> = staged.iter().cloned().collect();
+    if let Some(sub) = &opts.subpath {
+        let prefix = format!("{}/", sub);
+        pending.extend(base.pending_deletes.iter().filter(|p| !p.starts_with(&prefix)).cloned());
+    }
+    pending
+}
+
+pub fn preview_plan(
+    local_root: &Path,
+    ssd_data_root: &Path,
+    ssd_rel: &str,
+    machine_name: &str,
+    opts: &SyncOptions,
+) -> Result<SyncPlan> {
+    preview_plan_with_sides(local_root, ssd_data_root, ssd_rel, machine_name, opts).map(|(plan, _, _)| plan)
+}
+
+/// Like [`preview_plan`], also returning the scanned local and SSD snapshots
+/// (e.g. for the sizes and mtimes `diff` shows).
+pub fn preview_plan_with_sides(
+    local_root: &Path,
+    ssd_data_root: &Path,
+    ssd_rel: &str,
+    machine_name: &str,
+    opts: &SyncOptions,
+) -> Result<(SyncPlan, Snapshot, Snapshot)> {
+    let ssd_folder = ssd_data_root.join(ssd_rel);
+    let snapshot_dir = AppConfig::ssd_snapshots_dir(ssd_data_root, machine_name);
+    let base = Snapshot::load_or_empty(
+        &snapshot_dir.join(Snapshot::snapshot_filename(ssd_rel)), ssd_rel, machine_name)?;
+    let ssd_cache = Snapshot::load_or_empty(
+        &snapshot_dir.join(Snapshot::ssd_cache_filename(ssd_rel)), ssd_rel, machine_name)?;
+
+    // SSD 文件夹尚未创建（首次同步前）：按空目录计算，不创建任何目录
+    let (local_snap, mut ssd_snap) = if ssd_folder.exists() {
+        let outside = outside_subpath(opts, &base, &ssd_cache);
+        scan_sides(
+            local_root, &ssd_folder, ssd_rel, machine_name, opts,
+            (&base, &ssd_cache), outside.as_ref())?
+    } else {
+        let local = scanner::scan_directory(local_root, ssd_rel, machine_name, &opts.scan, Some(&base))?;
+        (local, Snapshot::new(ssd_rel, machine_name))
+    };
+    NameMap::load(ssd_data_root, ssd_rel)?.decode_snapshot(&mut ssd_snap);
+
+    let (mut plan, _, _) = crate::diff::plan_changes(
+        &base, &ssd_cache, &local_snap, &ssd_snap, opts.change_detection);
+    plan.defer_unless(|entry| crate::diff::empty_dir_allowed(opts.empty_dirs, entry));
+    defer_in_flux(&mut plan, &local_snap, &ssd_snap);
+    defer_oversized(&mut plan, &local_snap, &ssd_snap);
+    if opts.safety.deferred_delete {
+        stage_deletions(&mut plan, &base.pending_deletes);
+    }
+    if opts.version_counters {
+        VersionMap::load(ssd_data_root, ssd_rel)?.annotate_conflicts(&mut plan, &base, &ssd_snap);
+    }
+    Ok((plan, local_snap, ssd_snap))
+}
+
+/// Outcome of `verify`: the files on the SSD re-hashed and compared with the SSD cache
+/// snapshot written by this machine's last sync.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub checked: usize,
+    /// Files whose content no longer matches; the flag is set when size and mtime still
+    /// match the record, i.e. the content changed without a normal write (likely corruption)
+    pub mismatched: Vec<(String, bool)>,
+    pub missing: Vec<String>,
+}
+
+impl VerifyReport {
+    pub fn problems(&self) -> usize {
+        self.mismatched.len() + self.missing.len()
+    }
+}
+
+/// Re-hash every file recorded in the SSD cache of a mapping (`verify`). Hashes are always
+/// computed from the file contents; neither the scan cache nor the hash cache is used.
+pub fn verify_ssd(ssd_data_root: &Path, ssd_rel: &str, machine_name: &str) -> Result<VerifyReport> {
+    let ssd_folder = ssd_data_root.join(ssd_rel);
+    let cache_file = AppConfig::ssd_snapshots_dir(ssd_data_root, machine_name)
+        .join(Snapshot::ssd_cache_filename(ssd_rel));
+    if !cache_file.exists() {
+        anyhow::bail!("No SSD snapshot for '{}' from this machine yet; run `sync` first", ssd_rel);
+    }
+    let cache = Snapshot::load(&cache_file)?;
+    let names = NameMap::load(ssd_data_root, ssd_rel)?;
+
+    let mut report = VerifyReport::default();
+    for (rel, entry) in &cache.files {
+        let path = ssd_folder.join(names.encode(rel));
+        if entry.is_dir {
+            if !path.is_dir() {
+                report.missing.push(rel.clone());
+            }
+            continue;
+        }
+        if let Some(target) = entry.symlink_target() {
+            match std::fs::read_link(&path) {
+                Ok(found) => {
+                    report.checked += 1;
+                    if found.to_string_lossy() != target {
+                        report.mismatched.push((rel.clone(), false));
+                    }
+                }
+                Err(_) => report.missing.push(rel.clone()),
+            }
+            continue;
+        }
+        let metadata = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                report.missing.push(rel.clone());
+                continue;
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read metadata: {}", path.display())),
+        };
+        report.checked += 1;
+        let algorithm = HashAlgorithm::of(&entry.hash).unwrap_or_default();
+        if scanner::compute_file_hash(&path, algorithm)? != entry.hash {
+            let silent = metadata.len() == entry.size && scanner::mtime_secs(&metadata) == entry.mtime_secs;
+            report.mismatched.push((rel.clone(), silent));
+        }
+    }
+    Ok(report)
+}
+
+/// Replace the base snapshot of a mapping with the current state of one side (`reset-base`).
+/// The next sync then sees every difference on the other side as a change made there.
+/// Returns the number of entries in the new base.
+pub fn reset_base(
+    local_root: &Path,
+    ssd_data_root: &Path,
+    ssd_rel: &str,
+    machine_name: &str,
+    opts: &SyncOptions,
+    from_ssd: bool,
+) -> Result<usize> {
+    let ssd_folder = ssd_data_root.join(ssd_rel);
+    if !ssd_folder.exists() {
+        anyhow::bail!("SSD folder does not exist: {}", ssd_folder.display());
+    }
+    let snapshot_dir = AppConfig::ssd_snapshots_dir(ssd_data_root, machine_name);
+    let snapshot_file = snapshot_dir.join(Snapshot::snapshot_filename(ssd_rel));
+    let ssd_cache_file = snapshot_dir.join(Snapshot::ssd_cache_filename(ssd_rel));
+    let old_base = Snapshot::load_or_empty(&snapshot_file, ssd_rel, machine_name)?;
+    let ssd_cache = Snapshot::load_or_empty(&ssd_cache_file, ssd_rel, machine_name)?;
+
+    let (local_snap, mut ssd_snap) = scanner::scan_pair(
+        local_root, &ssd_folder, ssd_rel, machine_name, &opts.scan,
+        Some(&old_base), Some(&ssd_cache))?;
+    NameMap::load(ssd_data_root, ssd_rel)?.decode_snapshot(&mut ssd_snap);
+
+    // 基准快照同时用作本地扫描缓存：以 SSD 为准时，内容相同的文件换成本地的 size/mtime
+    let mut new_base = if from_ssd {
+        crate::diff::rebase_stats(&ssd_snap, &local_snap)
+    } else {
+        local_snap
+    };
+    // 内容未变的文件保留原有版本号
+    for (path, entry) in new_base.files.iter_mut() {
+        if let Some(old) = old_base.files.get(path).filter(|old| old.hash == entry.hash) {
+            entry.version = old.version;
+        }
+    }
+    new_base.synced_at = chrono::Utc::now();
+    new_base.empty_dirs = opts.empty_dirs;
+    new_base.save(&snapshot_file)?;
+    ssd_snap.synced_at = chrono::Utc::now();
+    ssd_snap.save(&ssd_cache_file)?;
+    // 中断续传记录基于旧的基准，一并丢弃
+    let resume_file = snapshot_dir.join(Snapshot::resume_filename(ssd_rel));
+    if resume_file.exists() {
+        std::fs::remove_file(&resume_file)?;
+    }
+    Ok(new_base.files.len())
+}
+
+/// Run a full sync for one mapping (从磁盘加载快照).
+pub fn sync_one_mapping(
+    local_root: &Path,
+    ssd_data_root: &Path,
+    ssd_rel: &str,
+    machine_name: &str,
+    opts: &SyncOptions,
+    progress: &mut ProgressFn,
 ) -> Result<(SyncPlan, SyncResult)> {
     let (plan, result, _, _) = sync_one_mapping_cached(
-        local_root, ssd_data_root, ssd_rel, machine_name,
-        ignore, conflict_strategy, dry_run, verbose, None,
+        local_root, ssd_data_root, ssd_rel, machine_name, opts, None, progress,
     )?;
     Ok((plan, result))
 }
 
+/// 首次同步时两侧都有数据却几乎没有重合，很可能是映射指错了 SSD 文件夹；
+/// 合并前先警告，需要 `--force` 或交互确认才继续。
+fn check_first_sync_overlap(
+    local_snap: &Snapshot,
+    ssd_snap: &Snapshot,
+    ssd_folder: &Path,
+    opts: &SyncOptions,
+) -> Result<()> {
+    let overlap = crate::diff::first_sync_overlap(local_snap, ssd_snap);
+    if overlap.percent() >= LOW_OVERLAP_PERCENT || opts.force {
+        return Ok(());
+    }
+    outln!("  ⚠ WARNING: first sync of two folders that look unrelated");
+    let numbers = format!(
+        "    Local has {} files, {} has {} files; only {:.0}% of them match",
+        overlap.local_files,
+        ssd_folder.display(),
+        overlap.ssd_files,
+        overlap.percent()
+    );
+    // 同步可能就此中止：即使 --quiet（如 cron 任务）也要在 stderr 说明原因
+    if opts.dry_run {
+        outln!("{}", numbers);
+    } else {
+        eprintln!("{}", numbers);
+    }
+    outln!("    Syncing will merge both sets of files into both folders.");
+    if opts.dry_run {
+        return Ok(());
+    }
+    if !crate::prompt::confirm("    Merge anyway?")? {
+        anyhow::bail!(
+            "First sync aborted: local and SSD folders barely overlap. \
+             Check the mapping, or re-run with --force to merge them"
+        );
+    }
+    Ok(())
+}
+
+/// Other machines that have a snapshot of `ssd_rel` on this SSD, by name.
+fn prior_machine_snapshots(ssd_data_root: &Path, ssd_rel: &str, machine_name: &str) -> Vec<String> {
+    let snapshots_root = AppConfig::ssd_syncer_dir(ssd_data_root).join("snapshots");
+    let file_name = Snapshot::snapshot_filename(ssd_rel);
+    let mut machines: Vec<String> = std::fs::read_dir(&snapshots_root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|e| e.path().join(&file_name).is_file())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| name != machine_name)
+        .collect();
+    machines.sort();
+    machines
+}
+
+/// Move the snapshots of `ssd_rel` from machine `from` to machine `to` (after a rename).
+fn adopt_snapshot(ssd_data_root: &Path, ssd_rel: &str, from: &str, to: &str) -> Result<()> {
+    let (from_dir, to_dir) = (
+        AppConfig::ssd_snapshots_dir(ssd_data_root, from),
+        AppConfig::ssd_snapshots_dir(ssd_data_root, to),
+    );
+    std::fs::create_dir_all(&to_dir)?;
+    for name in [
+        Snapshot::snapshot_filename(ssd_rel),
+        Snapshot::ssd_cache_filename(ssd_rel),
+        Snapshot::resume_filename(ssd_rel),
+    ] {
+        if from_dir.join(&name).exists() {
+            std::fs::rename(from_dir.join(&name), to_dir.join(&name))
+                .with_context(|| format!("Failed to move snapshot {}", from_dir.join(&name).display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// This machine has no snapshot of the folder, but other machine names do: most likely the
+/// machine was renamed (e.g. a fresh `init`), and without its old snapshot everything looks new.
+/// 也可能只是另一台电脑同步同一个文件夹，所以只在终端上询问，默认不接管。
+fn offer_prior_snapshot(ssd_data_root: &Path, ssd_rel: &str, machine_name: &str, dry_run: bool) -> Result<()> {
+    let machines = prior_machine_snapshots(ssd_data_root, ssd_rel, machine_name);
+    if machines.is_empty() {
+        return Ok(());
+    }
+    outln!(
+        "  ⚠ WARNING: this folder has never been synced as machine '{}', but it has been as: {}",
+        machine_name,
+        machines.join(", ")
+    );
+    outln!("    If this computer used one of those names before, adopt its snapshot; otherwise this sync");
+    outln!("    compares every file from scratch, like a first sync.");
+    if dry_run {
+        return Ok(());
+    }
+    for machine in &machines {
+        if crate::prompt::confirm(&format!("    Adopt the snapshot of '{}'?", machine))? {
+            adopt_snapshot(ssd_data_root, ssd_rel, machine, machine_name)?;
+            outln!("  ✓ Adopted the snapshot of '{}'", machine);
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// `[sync_settings] confirm`: print what the plan is about to do and ask before running it.
+fn confirm_plan(plan: &SyncPlan, opts: &SyncOptions) -> Result<()> {
+    let counts = plan.counts();
+    let deletes = counts.del_ssd + counts.del_local;
+    let ask = match opts.confirm {
+        ConfirmPolicy::Never => false,
+        ConfirmPolicy::OnDelete => deletes > 0,
+        ConfirmPolicy::Always => true,
+    };
+    if !ask || opts.dry_run {
+        return Ok(());
+    }
+
+    let mut parts = vec![];
+    if counts.copy_to_ssd > 0 {
+        parts.push(format!("copy {} → SSD", counts.copy_to_ssd));
+    }
+    if counts.copy_to_local > 0 {
+        parts.push(format!("{} ← local", counts.copy_to_local));
+    }
+    if deletes > 0 {
+        parts.push(format!("delete {}", deletes));
+    }
+    if counts.renames > 0 {
+        parts.push(format!("rename {}", counts.renames));
+    }
+    if counts.conflicts > 0 {
+        parts.push(format!("resolve {} conflict(s)", counts.conflicts));
+    }
+    outln!("  About to: {}", parts.join(", "));
+
+    let proceed = match opts.confirm_answer {
+        Some(answer) => answer,
+        None if !std::io::stdin().is_terminal() => {
+            eprintln!("    No terminal to confirm on; pass --yes to sync without asking");
+            false
+        }
+        None => crate::prompt::confirm("  Proceed?")?,
+    };
+    if !proceed {
+        return Err(SyncDeclined.into());
+    }
+    Ok(())
+}
+
 /// 同步一个映射（支持内存缓存快照）。
 /// 接受 `cached_snapshots`: Option<(base_snapshot, ssd_cache)>，如果有则跳过磁盘加载。
 /// 返回 (plan, result, 更新后的base_snapshot, 更新后的ssd_cache)。
@@ -480,12 +1672,11 @@ fn sync_one_mapping_cached(
     ssd_data_root: &Path,
     ssd_rel: &str,
     machine_name: &str,
-    ignore: &IgnoreMatcher,
-    conflict_strategy: &ConflictStrategy,
-    dry_run: bool,
-    verbose: bool,
+    opts: &SyncOptions,
     cached_snapshots: Option<(Snapshot, Snapshot)>,
+    progress: &mut ProgressFn,
 ) -> Result<(SyncPlan, SyncResult, Snapshot, Snapshot)> {
+    let dry_run = opts.dry_run;
     let ssd_folder = ssd_data_root.join(ssd_rel);
 
     // Ensure SSD folder exists
@@ -497,78 +1688,223 @@ fn sync_one_mapping_cached(
     let snapshot_dir =
         AppConfig::ssd_snapshots_dir(ssd_data_root, machine_name);
     let snapshot_file = snapshot_dir.join(Snapshot::snapshot_filename(ssd_rel));
-    let ssd_cache_filename = format!("{}_ssd_cache.json",
-        ssd_rel.replace('/', "_").replace('\\', "_").replace(':', "_"));
-    let ssd_cache_file = snapshot_dir.join(&ssd_cache_filename);
+    let ssd_cache_file = snapshot_dir.join(Snapshot::ssd_cache_filename(ssd_rel));
+    let resume_file = snapshot_dir.join(Snapshot::resume_filename(ssd_rel));
 
     // 使用内存缓存的快照（如果有），否则从磁盘加载
-    let (base_snapshot, ssd_cache) = match cached_snapshots {
+    let (base_snapshot, mut ssd_cache) = match cached_snapshots {
         Some((base, cache)) => {
             log::debug!("Using in-memory cached snapshots");
             (base, cache)
         }
         None => {
+            if !snapshot_file.exists() {
+                offer_prior_snapshot(ssd_data_root, ssd_rel, machine_name, dry_run)?;
+            }
             let base = Snapshot::load_or_empty(&snapshot_file, ssd_rel, machine_name)?;
             let cache = Snapshot::load_or_empty(&ssd_cache_file, ssd_rel, machine_name)?;
             (base, cache)
         }
     };
 
+    // 策略变更后，之前被挡下的空目录操作会在本次同步中照常执行
+    if !base_snapshot.files.is_empty() && base_snapshot.empty_dirs != opts.empty_dirs {
+        log::info!(
+            "[filters] empty_dirs changed from {:?} to {:?} for '{}'",
+            base_snapshot.empty_dirs, opts.empty_dirs, ssd_rel
+        );
+    }
+
+    // 上次同步被中断：已复制完成的文件并入扫描缓存，两侧哈希一致，不会再次进入计划
+    let resumed = ResumeLog::load(&resume_file)?;
+    let mut resume_cache = None;
+    if !resumed.is_empty() {
+        log::info!(
+            "Resuming interrupted sync: {} file(s) already copied",
+            resumed.len()
+        );
+        let mut local_cache = base_snapshot.clone();
+        for r in resumed {
+            let (src_cache, dst_cache) = if r.to_ssd {
+                (&mut local_cache, &mut ssd_cache)
+            } else {
+                (&mut ssd_cache, &mut local_cache)
+            };
+            src_cache.files.insert(r.path.clone(), r.source);
+            dst_cache.files.insert(r.path, r.dest);
+        }
+        resume_cache = Some(local_cache);
+    }
+    let local_cache = resume_cache.as_ref().unwrap_or(&base_snapshot);
+
     // Scan both directories (并行扫描，各自使用独立的缓存快照)
-    let (local_snap, ssd_snap) =
-        scanner::scan_pair(local_root, &ssd_folder, ssd_rel, machine_name, ignore,
-            Some(&base_snapshot), Some(&ssd_cache))?;
-
-    // Compute changes
-    let local_changes = crate::diff::compute_changes(&base_snapshot, &local_snap);
-    let ssd_changes = crate::diff::compute_changes(&base_snapshot, &ssd_snap);
-
-    log::info!(
-        "Changes: {} local, {} SSD",
-        local_changes.len(),
-        ssd_changes.len()
-    );
+    let outside = outside_subpath(opts, &base_snapshot, &ssd_cache);
+    let (local_snap, mut ssd_snap) = scan_sides(
+        local_root, &ssd_folder, ssd_rel, machine_name, opts, (local_cache, &ssd_cache), outside.as_ref())?;
+    // SSD 上被改名的文件按原始名字参与比较
+    let names = NameMap::load(ssd_data_root, ssd_rel)?;
+    names.decode_snapshot(&mut ssd_snap);
+
+    if base_snapshot.files.is_empty() {
+        check_first_sync_overlap(&local_snap, &ssd_snap, &ssd_folder, opts)?;
+    }
+
+    // Compute changes + build sync plan
+    let (mut plan, local_count, ssd_count) = crate::diff::plan_changes(
+        &base_snapshot, &ssd_cache, &local_snap, &ssd_snap, opts.change_detection);
+
+    log::info!("Changes: {} local, {} SSD", local_count, ssd_count);
 
-    // Build sync plan
-    let plan = crate::diff::build_sync_plan(&local_changes, &ssd_changes);
+    let mut versions = if opts.version_counters {
+        let versions = VersionMap::load(ssd_data_root, ssd_rel)?;
+        versions.annotate_conflicts(&mut plan, &base_snapshot, &ssd_snap);
+        Some(versions)
+    } else {
+        None
+    };
+
+    // [filters] empty_dirs 挡下的空目录操作不算作 "Deferred"：它们每次都会被跳过，基准保持原样
+    let mut deferred =
+        plan.defer_unless(|entry| crate::diff::empty_dir_allowed(opts.empty_dirs, entry));
+    let planned = plan.actions.len();
+    deferred.extend(defer_in_flux(&mut plan, &local_snap, &ssd_snap));
+    deferred.extend(defer_oversized(&mut plan, &local_snap, &ssd_snap));
+    let skipped = oversized_files(&local_snap, &ssd_snap);
+    let staged = if opts.safety.deferred_delete {
+        stage_deletions(&mut plan, &base_snapshot.pending_deletes)
+    } else {
+        vec![]
+    };
+    deferred.extend(staged.iter().cloned());
+    if opts.only_conflicts {
+        deferred.extend(plan.defer_unless(|entry| matches!(entry.action, SyncAction::Conflict(_))));
+    }
+    if let Some(direction) = opts.direction {
+        // 单向同步：另一侧的改动和冲突都留在基准之外，下次双向同步时照常处理
+        for entry in plan.actions.iter().filter(|e| matches!(e.action, SyncAction::Conflict(_))) {
+            outln!("  ⚠ Conflict skipped by {}: {}", direction.flag(), entry.display_path());
+        }
+        deferred.extend(plan.defer_unless(|entry| direction.allows(&entry.action)));
+    }
+
+    // strategy = "fail"：有任何冲突就在执行前中止（预览同样报错，结果与实际运行一致）
+    if opts.conflict_strategy == ConflictStrategy::Fail {
+        let conflicts: Vec<String> = plan
+            .actions
+            .iter()
+            .filter(|entry| matches!(entry.action, SyncAction::Conflict(_)))
+            .map(|entry| entry.path.clone())
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(ConflictsFound(conflicts).into());
+        }
+    }
+
+    if opts.explain && !plan.actions.is_empty() {
+        let reasons = crate::diff::explain_plan(
+            &plan, &base_snapshot, &ssd_cache, &local_snap, &ssd_snap, opts.change_detection);
+        outln!("  Why each action was chosen (changes since the last sync):");
+        for (entry, lines) in plan.actions.iter().zip(reasons) {
+            outln!("    {} {}", action_label(&entry.action), entry.display_path());
+            for line in lines {
+                outln!("        {}", line);
+            }
+        }
+        outln!();
+    }
 
     if plan.actions.is_empty() {
         log::info!("No changes to sync for '{}'", ssd_rel);
         // 即使无需同步，也更新缓存快照以加速后续扫描
         let mut updated_base = local_snap;
         let mut updated_ssd = ssd_snap;
+        updated_base.restore_paths(&base_snapshot, &deferred);
+        updated_ssd.restore_paths(&ssd_cache, &deferred);
+        updated_base.pending_deletes = pending_deletes(opts, &base_snapshot, &staged);
+        let mut result = SyncResult::new();
+        result.deferred = planned - staged.len();
+        result.staged_deletes = staged.len();
+        result.skipped = skipped;
         if !dry_run {
             updated_base.synced_at = chrono::Utc::now();
+            updated_base.empty_dirs = opts.empty_dirs;
             updated_base.save(&snapshot_file)?;
             updated_ssd.synced_at = chrono::Utc::now();
             updated_ssd.save(&ssd_cache_file)?;
+            ResumeLog::remove(&resume_file)?;
         }
-        return Ok((plan, SyncResult::new(), updated_base, updated_ssd));
+        return Ok((plan, result, updated_base, updated_ssd));
     }
 
+    confirm_plan(&plan, opts)?;
+    warn_if_ssd_too_small(&plan, ssd_data_root);
+
     // Execute
-    let engine = SyncEngine::new(machine_name, conflict_strategy.clone(), dry_run, verbose);
-    let mut result = engine.execute_plan(&plan, local_root, &ssd_folder)?;
+    let mut engine = SyncEngine::new(machine_name, opts.conflict_strategy.clone(), dry_run);
+    engine.copy_concurrency = opts.copy_concurrency;
+    engine.wait_for_ssd = opts.wait_for_ssd;
+    engine.retry = opts.retry;
+    engine.temp_suffix = opts.scan.temp_suffix.clone();
+    engine.conflict_marker = opts.conflict_marker.clone();
+    engine.trash = opts.trash;
+    engine.follow_symlinks = opts.scan.follow_symlinks;
+    engine.names = Mutex::new(names);
+    engine.illegal_char_policy =
+        compat::is_restrictive_fs(&ssd_folder).then_some(opts.compat.illegal_char_policy);
+    if !dry_run {
+        engine.resume_log = Some(ResumeLog::open(&resume_file)?);
+        if opts.safety.archive {
+            engine.archive = Some(Archive::new(ssd_data_root, &ssd_folder));
+        }
+    }
+    let mut result = engine.execute_plan(&plan, local_root, &ssd_folder, progress)?;
+    result.deferred = planned - plan.actions.len() - staged.len();
+    result.staged_deletes = staged.len();
+    result.skipped = skipped;
+    let mut names = std::mem::take(&mut *engine.names.lock().unwrap());
 
     // Update snapshots
     // 关键：基准快照 = 本地与SSD的交集（防止同步期间新增的本地文件被误判为"SSD删除"）
     let (updated_base, updated_ssd) = if !dry_run {
-        let (final_local, final_ssd) = scanner::scan_pair(
-            local_root, &ssd_folder, ssd_rel, machine_name, ignore,
-            Some(&local_snap), Some(&ssd_snap))?;
+        names.save()?;
+        let (final_local, mut final_ssd) = scan_sides(
+            local_root, &ssd_folder, ssd_rel, machine_name, opts, (&local_snap, &ssd_snap), outside.as_ref())?;
+        names.decode_snapshot(&mut final_ssd);
         result.total_files = final_local.files.len();
 
         // 基准快照 = 本地文件中同时存在于SSD的部分（保留本地mtime用于扫描缓存）
         let mut new_base = final_local;
         new_base.files.retain(|path, _| final_ssd.files.contains_key(path));
+        // 延后的变更：基准与缓存保持同步前的状态，下次同步会再次提出
+        new_base.restore_paths(&base_snapshot, &deferred);
+        new_base.pending_deletes = pending_deletes(opts, &base_snapshot, &staged);
+        if let Some(versions) = versions.as_mut() {
+            versions.update(&mut new_base, &base_snapshot, &ssd_snap, &final_ssd);
+            versions.save()?;
+        }
         new_base.synced_at = chrono::Utc::now();
+        new_base.empty_dirs = opts.empty_dirs;
         new_base.save(&snapshot_file)?;
 
         // SSD 侧缓存快照
         let mut new_ssd_cache = final_ssd;
+        new_ssd_cache.restore_paths(&ssd_cache, &deferred);
         new_ssd_cache.synced_at = chrono::Utc::now();
         new_ssd_cache.save(&ssd_cache_file)?;
 
+        // 快照已更新，续传记录不再需要
+        drop(engine);
+        ResumeLog::remove(&resume_file)?;
+
+        if opts.safety.archive {
+            let today = chrono::Local::now().date_naive();
+            let pruned = archive::prune(
+                &archive::archive_root(ssd_data_root), opts.safety.archive_retention_days, today)?;
+            if pruned > 0 {
+                log::info!("Pruned {} expired archive folder(s)", pruned);
+            }
+        }
+
         log::debug!("Snapshots updated: {}", snapshot_file.display());
         (new_base, new_ssd_cache)
     } else {
@@ -578,3 +1914,1035 @@ fn sync_one_mapping_cached(
 
     Ok((plan, result, updated_base, updated_ssd))
 }
+
+/// Bootstrap a local folder from the SSD: copy every SSD file that is missing locally and never
+/// delete anything. Local files that differ from the SSD are left alone unless `overwrite`.
+/// 完成后写入与 SSD 一致的基准快照，之后的普通双向同步即可正常工作。
+/// 返回 (plan, result, 因内容不同而跳过的本地文件)。
+pub fn restore_from_ssd(
+    local_root: &Path,
+    ssd_data_root: &Path,
+    ssd_rel: &str,
+    machine_name: &str,
+    opts: &SyncOptions,
+    overwrite: bool,
+    progress: &mut ProgressFn,
+) -> Result<(SyncPlan, SyncResult, Vec<String>)> {
+    let ssd_folder = ssd_data_root.join(ssd_rel);
+    if !ssd_folder.is_dir() {
+        anyhow::bail!("SSD folder does not exist: {}", ssd_folder.display());
+    }
+    if !local_root.exists() && !opts.dry_run {
+        std::fs::create_dir_all(local_root)
+            .with_context(|| format!("Failed to create local folder: {}", local_root.display()))?;
+    }
+
+    let snapshot_dir = AppConfig::ssd_snapshots_dir(ssd_data_root, machine_name);
+    let snapshot_file = snapshot_dir.join(Snapshot::snapshot_filename(ssd_rel));
+    let ssd_cache_file = snapshot_dir.join(Snapshot::ssd_cache_filename(ssd_rel));
+    let ssd_cache = Snapshot::load_or_empty(&ssd_cache_file, ssd_rel, machine_name)?;
+
+    let mut ssd_snap = scanner::scan_directory(&ssd_folder, ssd_rel, machine_name, &opts.scan, Some(&ssd_cache))?;
+    let names = NameMap::load(ssd_data_root, ssd_rel)?;
+    names.decode_snapshot(&mut ssd_snap);
+    let local_snap = if local_root.exists() {
+        scanner::scan_directory(local_root, ssd_rel, machine_name, &opts.scan, None)?
+    } else {
+        Snapshot::new(ssd_rel, machine_name)
+    };
+
+    let mut actions = Vec::new();
+    let mut skipped = Vec::new();
+    for (path, ssd_entry) in &ssd_snap.files {
+        match local_snap.files.get(path) {
+            Some(local_entry) if local_entry.hash == ssd_entry.hash => continue,
+            Some(_) if !overwrite => {
+                skipped.push(path.clone());
+                continue;
+            }
+            _ => actions.push(SyncPlanEntry {
+                path: path.clone(),
+                action: SyncAction::CopyToLocal,
+                is_dir: ssd_entry.is_dir,
+                entry: Some(ssd_entry.clone()),
+            }),
+        }
+    }
+    let plan = SyncPlan { actions };
+
+    let mut engine = SyncEngine::new(machine_name, opts.conflict_strategy.clone(), opts.dry_run);
+    engine.copy_concurrency = opts.copy_concurrency;
+    engine.wait_for_ssd = opts.wait_for_ssd;
+    engine.retry = opts.retry;
+    engine.temp_suffix = opts.scan.temp_suffix.clone();
+    engine.conflict_marker = opts.conflict_marker.clone();
+    engine.trash = opts.trash;
+    engine.follow_symlinks = opts.scan.follow_symlinks;
+    engine.names = Mutex::new(names);
+    let mut result = engine.execute_plan(&plan, local_root, &ssd_folder, progress)?;
+    let names = engine.names.into_inner().unwrap();
+
+    if opts.dry_run {
+        result.total_files = ssd_snap.files.len();
+        return Ok((plan, result, skipped));
+    }
+
+    // 基准快照只包含两侧内容一致的文件：跳过的本地文件留给下次同步按冲突策略处理
+    let (final_local, mut final_ssd) = scanner::scan_pair(
+        local_root, &ssd_folder, ssd_rel, machine_name, &opts.scan,
+        Some(&local_snap), Some(&ssd_snap))?;
+    names.decode_snapshot(&mut final_ssd);
+    result.total_files = final_local.files.len();
+
+    let mut new_base = final_local;
+    new_base
+        .files
+        .retain(|path, entry| final_ssd.files.get(path).is_some_and(|s| s.hash == entry.hash));
+    new_base.synced_at = chrono::Utc::now();
+    new_base.save(&snapshot_file)?;
+
+    let mut new_ssd_cache = final_ssd;
+    new_ssd_cache.synced_at = chrono::Utc::now();
+    new_ssd_cache.save(&ssd_cache_file)?;
+
+    Ok((plan, result, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn copy_entry(path: &str, action: SyncAction, is_dir: bool) -> SyncPlanEntry {
+        SyncPlanEntry {
+            path: path.to_string(),
+            action,
+            is_dir,
+            entry: None,
+        }
+    }
+
+    fn run(strategy: ConflictStrategy, entries: Vec<SyncPlanEntry>) -> (tempfile::TempDir, SyncResult) {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join("local")).unwrap();
+        std::fs::create_dir_all(tmp.path().join("ssd")).unwrap();
+        std::fs::write(tmp.path().join("ssd/report"), "file on ssd").unwrap();
+        std::fs::write(tmp.path().join("ssd/notes.txt"), "notes").unwrap();
+        // 本地同名路径是目录 / 上级路径是文件
+        std::fs::create_dir_all(tmp.path().join("local/report/old")).unwrap();
+        std::fs::write(tmp.path().join("local/report/old/x.txt"), "x").unwrap();
+        std::fs::write(tmp.path().join("local/photos"), "not a dir").unwrap();
+        std::fs::create_dir_all(tmp.path().join("ssd/photos")).unwrap();
+        std::fs::write(tmp.path().join("ssd/photos/a.jpg"), "jpg").unwrap();
+
+        let engine = SyncEngine::new("mac", strategy, false);
+        let plan = SyncPlan { actions: entries };
+        let result = engine
+            .execute_plan(&plan, &tmp.path().join("local"), &tmp.path().join("ssd"), &mut |_| {})
+            .unwrap();
+        (tmp, result)
+    }
+
+    #[test]
+    fn test_copy_preserves_mtime() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src.txt");
+        std::fs::write(&src, "content").unwrap();
+        let old = std::time::SystemTime::now() - Duration::from_secs(30 * 24 * 3600);
+        std::fs::File::options().write(true).open(&src).unwrap().set_modified(old).unwrap();
+
+        // dry run 不创建目标文件
+        let dst = tmp.path().join("out/dst.txt");
+        SyncEngine::new("mac", ConflictStrategy::Both, true).copy_into(&src, &dst).unwrap();
+        assert!(!dst.exists());
+
+        SyncEngine::new("mac", ConflictStrategy::Both, false).copy_into(&src, &dst).unwrap();
+        let copied = std::fs::metadata(&dst).unwrap().modified().unwrap();
+        let diff = copied.duration_since(old).unwrap_or_else(|e| e.duration());
+        assert!(diff < Duration::from_secs(1), "mtime off by {:?}", diff);
+    }
+
+    #[test]
+    fn test_copy_times_recorded() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join("local/sub")).unwrap();
+        std::fs::create_dir_all(tmp.path().join("ssd")).unwrap();
+        std::fs::write(tmp.path().join("local/a.bin"), vec![0u8; 4096]).unwrap();
+        std::fs::write(tmp.path().join("local/sub/b.txt"), "b").unwrap();
+        let copy = |path: &str, size: u64| SyncPlanEntry {
+            entry: Some(FileEntry { size, mtime_secs: 0, hash: String::new(), is_dir: false, version: 0, is_symlink: false }),
+            ..copy_entry(path, SyncAction::CopyToSsd, false)
+        };
+        let plan = SyncPlan {
+            actions: vec![
+                copy_entry("sub", SyncAction::CopyToSsd, true),
+                copy("a.bin", 4096),
+                copy("sub/b.txt", 1),
+                copy("missing.txt", 1),
+            ],
+        };
+
+        let mut engine = SyncEngine::new("mac", ConflictStrategy::Both, false);
+                engine.copy_concurrency = 2;
+        let mut result = engine.execute_plan(&plan, &tmp.path().join("local"), &tmp.path().join("ssd"), &mut |_| {}).unwrap();
+        // 目录和失败的复制不计时
+        let mut paths: Vec<&str> = result.copy_times.iter().map(|t| t.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths, vec!["a.bin", "sub/b.txt"]);
+        assert_eq!(result.bytes_copied, 4097);
+
+        result.copy_times[0].elapsed = Duration::from_secs(1);
+        result.copy_times[1].elapsed = Duration::from_secs(3);
+        let slowest = result.slowest_copies(1);
+        assert_eq!(slowest.len(), 1);
+        assert_eq!(slowest[0].elapsed, Duration::from_secs(3));
+
+        // dry run 不计时
+        let engine = SyncEngine::new("mac", ConflictStrategy::Both, true);
+        let result = engine.execute_plan(&plan, &tmp.path().join("local"), &tmp.path().join("ssd"), &mut |_| {}).unwrap();
+        assert!(result.copy_times.is_empty());
+    }
+
+    #[test]
+    fn test_in_flux_files_deferred() {
+        let entry = |hash: &str| FileEntry {
+            size: 10, mtime_secs: 1000, hash: hash.to_string(), is_dir: false, version: 0, is_symlink: false,
+        };
+        let mut base = Snapshot::new("docs", "mac");
+        base.files.insert("app.log".to_string(), entry("h1"));
+        base.files.insert("notes.txt".to_string(), entry("h2"));
+        let mut local = base.clone();
+        local.files.insert("app.log".to_string(), entry("h1-partial"));
+        local.files.insert("notes.txt".to_string(), entry("h2-new"));
+        local.in_flux.insert("app.log".to_string());
+
+        let (mut plan, _, _) =
+            crate::diff::plan_changes(&base, &base, &local, &base, ChangeDetection::Hash);
+        assert_eq!(plan.actions.len(), 2);
+        let deferred = defer_in_flux(&mut plan, &local, &base);
+        assert_eq!(deferred, vec!["app.log", "app.log"]);
+        assert_eq!(plan.actions.len(), 1);
+        assert_eq!(plan.actions[0].path, "notes.txt");
+
+        // 基准保留上次同步的条目，下次扫描到完整内容时再同步
+        local.restore_paths(&base, &deferred);
+        assert_eq!(local.files["app.log"].hash, "h1");
+    }
+
+    #[test]
+    fn test_temp_suffix_and_conflict_marker() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src.txt");
+        std::fs::write(&src, "new").unwrap();
+        let dst = tmp.path().join("out/dst.txt");
+        std::fs::create_dir_all(tmp.path().join("out")).unwrap();
+        std::fs::write(&dst, "old").unwrap();
+
+        let mut engine = SyncEngine::new("mac", ConflictStrategy::Both, false);
+        engine.temp_suffix = ".partial".to_string();
+        engine.conflict_marker = "sync-conflict".to_string();
+        engine.copy_file(&src, &dst).unwrap();
+        assert_eq!(std::fs::read_to_string(&dst).unwrap(), "new");
+        assert!(!tmp.path().join("out/dst.txt.partial").exists());
+
+        let name = engine.conflict_rel("docs/report.pdf", |_| false);
+        assert!(name.starts_with("docs/report.sync-conflict.mac."), "{}", name);
+        assert!(name.ends_with(".pdf"));
+    }
+
+    #[test]
+    fn test_conflict_names_never_collide() {
+        let engine = SyncEngine::new("mac", ConflictStrategy::Both, false);
+        let taken = ["docs/a.conflict.mac.20250101120000.txt", "docs/a.conflict.mac.20250101120000.1.txt"];
+        assert_eq!(
+            engine.conflict_rel_at("docs/a.txt", "20250101120000", |c| taken.contains(&c)),
+            "docs/a.conflict.mac.20250101120000.2.txt"
+        );
+        assert_eq!(
+            conflict_copy_time("a.conflict.mac.20250101120000.2.txt", "conflict").map(|t| t.to_rfc3339()),
+            Some("2025-01-01T12:00:00+00:00".to_string())
+        );
+        assert!(conflict_copy_time(".env.conflict.my.laptop.20250101120000", "conflict").is_some());
+        assert!(conflict_copy_time("a.conflict.txt", "conflict").is_none());
+        assert!(conflict_copy_time("a.txt", "conflict").is_none());
+
+        // 没有主干或扩展名的名字
+        assert_eq!(engine.conflict_rel_at(".env", "1", |_| false), ".env.conflict.mac.1");
+        assert_eq!(engine.conflict_rel_at("x/.env", "1", |c| c == "x/.env.conflict.mac.1"), "x/.env.conflict.mac.1.1");
+        assert_eq!(engine.conflict_rel_at("Makefile", "1", |_| false), "Makefile.conflict.mac.1");
+        assert_eq!(engine.conflict_rel_at("notes.", "1", |_| false), "notes..conflict.mac.1");
+
+        // 同一文件连续两次冲突（同一秒内）：两个冲突副本都保留
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        for round in ["one", "two"] {
+            std::fs::write(local.join(".env"), format!("local {}", round)).unwrap();
+            std::fs::write(ssd.join(".env"), "ssd").unwrap();
+            engine.resolve_both(".env", &local.join(".env"), &ssd.join(".env"), &local, &ssd).unwrap();
+        }
+        for root in [&local, &ssd] {
+            let mut copies: Vec<String> = std::fs::read_dir(root)
+                .unwrap()
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .filter(|n| n.starts_with(".env.conflict.mac."))
+                .collect();
+            copies.sort();
+            assert_eq!(copies.len(), 2, "{:?}", copies);
+            let contents: BTreeSet<String> =
+                copies.iter().map(|n| std::fs::read_to_string(root.join(n)).unwrap()).collect();
+            assert_eq!(contents, BTreeSet::from(["local one".to_string(), "local two".to_string()]));
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_failed_copy_leaves_no_partial_file() {
+        // /proc/self/mem 能打开，但从偏移 0 读取会出错：临时文件已创建，复制中途失败
+        let src = Path::new("/proc/self/mem");
+        let tmp = tempfile::tempdir().unwrap();
+        let (fresh, existing) = (tmp.path().join("new.bin"), tmp.path().join("old.bin"));
+        std::fs::write(&existing, "old content").unwrap();
+
+        let engine = SyncEngine::new("mac", ConflictStrategy::Both, false);
+        assert!(engine.copy_file(src, &fresh).is_err());
+        assert!(engine.copy_file(src, &existing).is_err());
+        assert!(!fresh.exists());
+        assert_eq!(std::fs::read_to_string(&existing).unwrap(), "old content");
+        let names: Vec<_> = std::fs::read_dir(tmp.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, vec!["old.bin"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_file_rename_moves_without_copying() {
+        use std::os::unix::fs::MetadataExt;
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(local.join("docs")).unwrap();
+        std::fs::create_dir_all(ssd.join("old")).unwrap();
+        std::fs::write(local.join("docs/bar.bin"), "big content").unwrap();
+        std::fs::write(ssd.join("old/foo.bin"), "big content").unwrap();
+        let inode = std::fs::metadata(ssd.join("old/foo.bin")).unwrap().ino();
+
+        let engine = SyncEngine::new("mac", ConflictStrategy::Both, false);
+        let plan = SyncPlan {
+            actions: vec![copy_entry("docs/bar.bin", SyncAction::RenameOnSsd { from: "old/foo.bin".to_string() }, false)],
+        };
+        let result = engine.execute_plan(&plan, &local, &ssd, &mut |_| {}).unwrap();
+        assert_eq!(result.renamed, 1);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        // 同一个 inode：SSD 上只是改名，没有重新写入内容
+        assert_eq!(std::fs::metadata(ssd.join("docs/bar.bin")).unwrap().ino(), inode);
+        assert!(!ssd.join("old").exists());
+    }
+
+    fn sync_opts() -> SyncOptions {
+        SyncOptions {
+            scan: ScanOptions::new(crate::ignore::IgnoreMatcher::new(&[])),
+            conflict_strategy: ConflictStrategy::Both,
+            change_detection: ChangeDetection::Hash,
+            version_counters: false,
+            empty_dirs: EmptyDirPolicy::Both,
+            copy_concurrency: 1,
+            safety: SafetyConfig::default(),
+            compat: CompatConfig::default(),
+            wait_for_ssd: None,
+            retry: RetryPolicy::default(),
+            conflict_marker: "conflict".to_string(),
+            trash: false,
+            dry_run: false,
+            verbose: false,
+            summary_only: true,
+            force: false,
+            only_conflicts: false,
+            direction: None,
+            explain: false,
+            subpath: None,
+            confirm: ConfirmPolicy::Never,
+            confirm_answer: None,
+        }
+    }
+
+    #[test]
+    fn test_subpath_sync_leaves_rest_untouched() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        for dir in ["app", "other"] {
+            std::fs::create_dir_all(local.join(dir)).unwrap();
+        }
+        std::fs::create_dir_all(&ssd).unwrap();
+        std::fs::write(local.join("app/a.txt"), "a").unwrap();
+        std::fs::write(local.join("other/b.txt"), "b").unwrap();
+        let mut opts = sync_opts();
+        sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert!(ssd.join("work/other/b.txt").exists());
+
+        std::fs::write(local.join("app/a.txt"), "a changed").unwrap();
+        std::fs::write(local.join("app/new.txt"), "new").unwrap();
+        std::fs::remove_file(local.join("other/b.txt")).unwrap();
+        std::fs::write(local.join("other/c.txt"), "c").unwrap();
+
+        opts.subpath = Some("app".to_string());
+        let (plan, _) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        let paths: Vec<&str> = plan.actions.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["app/a.txt", "app/new.txt"]);
+        assert_eq!(std::fs::read_to_string(ssd.join("work/app/a.txt")).unwrap(), "a changed");
+        assert!(ssd.join("work/other/b.txt").exists());
+        assert!(!ssd.join("work/other/c.txt").exists());
+
+        // 子目录外的条目仍留在基准中：完整同步时 b.txt 被识别为本地删除，而不是 SSD 新增
+        opts.subpath = None;
+        let (plan, _) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        let actions: Vec<(&str, &SyncAction)> = plan.actions.iter().map(|e| (e.path.as_str(), &e.action)).collect();
+        assert_eq!(
+            actions,
+            vec![("other/b.txt", &SyncAction::DeleteFromSsd), ("other/c.txt", &SyncAction::CopyToSsd)]
+        );
+        assert!(!local.join("other/b.txt").exists());
+    }
+
+    #[test]
+    fn test_one_way_sync_changes_one_side() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(local.join(name), name).unwrap();
+        }
+        let mut opts = sync_opts();
+        sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+
+        // 两侧各有改动，另有一个冲突
+        std::fs::write(local.join("local.txt"), "l").unwrap();
+        std::fs::remove_file(local.join("a.txt")).unwrap();
+        std::fs::write(ssd.join("work/ssd.txt"), "s").unwrap();
+        std::fs::remove_file(ssd.join("work/b.txt")).unwrap();
+        std::fs::write(local.join("c.txt"), "c local").unwrap();
+        std::fs::write(ssd.join("work/c.txt"), "c on ssd").unwrap();
+
+        opts.direction = Some(SyncDirection::Push);
+        let (plan, result) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        let paths: Vec<&str> = plan.actions.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "local.txt"]);
+        assert_eq!((result.copied_to_ssd, result.deleted_from_ssd, result.deferred), (1, 1, 3));
+        assert!(ssd.join("work/local.txt").exists() && !ssd.join("work/a.txt").exists());
+        // 本地一侧保持原样，冲突未处理
+        assert!(!local.join("ssd.txt").exists() && local.join("b.txt").exists());
+        assert_eq!(std::fs::read_to_string(local.join("c.txt")).unwrap(), "c local");
+        assert_eq!(std::fs::read_to_string(ssd.join("work/c.txt")).unwrap(), "c on ssd");
+
+        // 再次 push 无事可做；SSD 侧的改动仍待处理，不会变成冲突
+        let (plan, result) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert!(plan.actions.is_empty());
+        assert_eq!(result.deferred, 3);
+
+        opts.direction = Some(SyncDirection::Pull);
+        let (plan, result) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        let actions: Vec<(&str, &SyncAction)> = plan.actions.iter().map(|e| (e.path.as_str(), &e.action)).collect();
+        assert_eq!(
+            actions,
+            vec![("b.txt", &SyncAction::DeleteFromLocal), ("ssd.txt", &SyncAction::CopyToLocal)]
+        );
+        assert_eq!(result.deferred, 1);
+        assert!(local.join("ssd.txt").exists() && !local.join("b.txt").exists());
+        assert_eq!(std::fs::read_to_string(ssd.join("work/c.txt")).unwrap(), "c on ssd");
+
+        // 双向同步只剩冲突
+        opts.direction = None;
+        let (plan, _) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        let paths: Vec<&str> = plan.actions.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["c.txt"]);
+        assert!(matches!(plan.actions[0].action, SyncAction::Conflict(_)));
+    }
+
+    #[test]
+    fn test_deep_folder_removal_propagates_cleanly() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(local.join("a/b/c/d")).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        std::fs::write(local.join("a/b/c/x.txt"), "x").unwrap();
+        let opts = sync_opts();
+        sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert!(ssd.join("work/a/b/c/d").is_dir());
+
+        // 删掉深层目录后 a/b 成了空目录：SSD 上删除 c 及其内容，但保留空的 a/b
+        std::fs::remove_dir_all(local.join("a/b/c")).unwrap();
+        let (plan, result) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        let paths: Vec<&str> = plan.actions.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["a", "a/b", "a/b/c/d", "a/b/c/x.txt"]);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(!ssd.join("work/a/b/c").exists());
+        assert!(ssd.join("work/a/b").is_dir());
+
+        let (plan, _) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert!(plan.actions.is_empty(), "{:?}", plan.actions);
+
+        // 删空整个映射：SSD 上的映射目录本身保留
+        std::fs::remove_dir_all(local.join("a")).unwrap();
+        let (_, result) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert!(ssd.join("work").is_dir());
+        assert!(!ssd.join("work/a").exists());
+    }
+
+    #[test]
+    fn test_deferred_delete_takes_two_syncs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        std::fs::write(local.join("a.jpg"), "a").unwrap();
+        std::fs::write(local.join("b.jpg"), "b").unwrap();
+        let mut opts = sync_opts();
+        opts.safety.deferred_delete = true;
+        sync_one_mapping(&local, &ssd, "photos", "mac", &opts, &mut |_| {}).unwrap();
+
+        std::fs::remove_file(local.join("a.jpg")).unwrap();
+        std::fs::remove_file(local.join("b.jpg")).unwrap();
+        let (_, result) = sync_one_mapping(&local, &ssd, "photos", "mac", &opts, &mut |_| {}).unwrap();
+        assert_eq!((result.staged_deletes, result.deleted_from_ssd, result.deferred), (2, 0, 0));
+        assert!(ssd.join("photos/a.jpg").exists() && ssd.join("photos/b.jpg").exists());
+
+        // b.jpg 只是暂时消失：重新出现后不再删除，也不再处于待删除状态
+        std::fs::write(local.join("b.jpg"), "b").unwrap();
+        let (_, result) = sync_one_mapping(&local, &ssd, "photos", "mac", &opts, &mut |_| {}).unwrap();
+        assert_eq!((result.staged_deletes, result.deleted_from_ssd), (0, 1));
+        assert!(!ssd.join("photos/a.jpg").exists());
+        assert!(ssd.join("photos/b.jpg").exists());
+
+        std::fs::remove_file(local.join("b.jpg")).unwrap();
+        let (_, result) = sync_one_mapping(&local, &ssd, "photos", "mac", &opts, &mut |_| {}).unwrap();
+        assert_eq!((result.staged_deletes, result.deleted_from_ssd), (1, 0));
+        assert!(ssd.join("photos/b.jpg").exists());
+    }
+
+    #[test]
+    fn test_exclude_patterns_for_one_run() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(local.join("drafts")).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        std::fs::write(local.join("report.doc"), "r").unwrap();
+        std::fs::write(local.join("report.doc.bak"), "r").unwrap();
+        std::fs::write(local.join("drafts/old.bak"), "o").unwrap();
+        std::fs::write(local.join(".DS_Store"), "x").unwrap();
+
+        // --exclude "*.bak" 与配置中的忽略规则合并，配置本身不变
+        let config = crate::config::IgnoreConfig::default();
+        let mut opts = sync_opts();
+        opts.scan = ScanOptions::new(crate::ignore::IgnoreMatcher::new(
+            &config.patterns_with(&["*.bak".to_string()]),
+        ));
+        let plan = preview_plan(&local, &ssd, "work", "mac", &opts).unwrap();
+        let paths: Vec<&str> = plan.actions.iter().map(|e| e.path.as_str()).collect();
+        // drafts/ 只剩被排除的文件，按空目录同步
+        assert_eq!(paths, vec!["drafts", "report.doc"]);
+        assert!(!config.patterns.iter().any(|p| p == "*.bak"));
+
+        opts.scan = ScanOptions::new(crate::ignore::IgnoreMatcher::new(&config.patterns));
+        let plan = preview_plan(&local, &ssd, "work", "mac", &opts).unwrap();
+        assert!(plan.actions.iter().any(|e| e.path == "drafts/old.bak"));
+    }
+
+    #[test]
+    fn test_ssdignore_file_patterns() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(local.join("build")).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        std::fs::write(local.join("notes.txt"), "n").unwrap();
+        std::fs::write(local.join("scratch.tmp"), "s").unwrap();
+        std::fs::write(local.join("build/out.tmp"), "o").unwrap();
+        std::fs::write(local.join("build/keep.bin"), "k").unwrap();
+        std::fs::write(local.join(".ssdignore"), "# editor leftovers\n!build\n\n  *.tmp  \n").unwrap();
+
+        // 配置忽略 build，.ssdignore 在其后：!build 重新包含它，*.tmp 在任何位置都被排除
+        let ignore = crate::ignore::IgnoreMatcher::new(&["build".to_string()]).with_ignore_file(&local).unwrap();
+        let mut opts = sync_opts();
+        opts.scan = ScanOptions::new(ignore);
+        let plan = preview_plan(&local, &ssd, "work", "mac", &opts).unwrap();
+        let paths: Vec<&str> = plan.actions.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec![".ssdignore", "build/keep.bin", "notes.txt"]);
+
+        // 没有 .ssdignore：只有配置中的规则
+        let ignore = crate::ignore::IgnoreMatcher::new(&[]).with_ignore_file(&ssd).unwrap();
+        assert!(!ignore.is_ignored("scratch.tmp"));
+    }
+
+    #[test]
+    fn test_renamed_machine_finds_prior_snapshot() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        std::fs::write(local.join("a.txt"), "a").unwrap();
+        std::fs::write(local.join("b.txt"), "b").unwrap();
+        let opts = sync_opts();
+        sync_one_mapping(&local, &ssd, "work", "old-mac", &opts, &mut |_| {}).unwrap();
+
+        // 改名后的机器没有自己的快照：能找到旧名字下的快照
+        assert_eq!(prior_machine_snapshots(&ssd, "work", "new-mac"), vec!["old-mac"]);
+        assert!(prior_machine_snapshots(&ssd, "work", "old-mac").is_empty());
+        assert!(prior_machine_snapshots(&ssd, "other", "new-mac").is_empty());
+
+        // 接管后继续增量同步，而不是当作首次同步
+        adopt_snapshot(&ssd, "work", "old-mac", "new-mac").unwrap();
+        assert!(prior_machine_snapshots(&ssd, "work", "new-mac").is_empty());
+        std::fs::remove_file(local.join("a.txt")).unwrap();
+        let (plan, _) = sync_one_mapping(&local, &ssd, "work", "new-mac", &opts, &mut |_| {}).unwrap();
+        assert!(matches!(plan.actions[..], [SyncPlanEntry { action: SyncAction::DeleteFromSsd, .. }]), "{:?}", plan.actions);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_recreated_or_followed() {
+        use std::os::unix::fs::symlink;
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(local.join("v2")).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        std::fs::write(local.join("v2/app.bin"), "v2").unwrap();
+        symlink("v2", local.join("current")).unwrap();
+        symlink("nowhere.txt", local.join("broken")).unwrap();
+
+        let mut opts = sync_opts();
+        let (_, result) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(std::fs::read_link(ssd.join("work/current")).unwrap(), Path::new("v2"));
+        assert_eq!(std::fs::read_link(ssd.join("work/broken")).unwrap(), Path::new("nowhere.txt"));
+
+        // 在 SSD 上改指向：作为修改同步回本机；删除链接同样传播
+        std::fs::create_dir(ssd.join("work/v3")).unwrap();
+        std::fs::write(ssd.join("work/v3/app.bin"), "v3").unwrap();
+        std::fs::remove_file(ssd.join("work/current")).unwrap();
+        symlink("v3", ssd.join("work/current")).unwrap();
+        std::fs::remove_file(local.join("broken")).unwrap();
+        let (_, result) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(std::fs::read_link(local.join("current")).unwrap(), Path::new("v3"));
+        assert_eq!(std::fs::read_to_string(local.join("current/app.bin")).unwrap(), "v3");
+        assert!(std::fs::symlink_metadata(ssd.join("work/broken")).is_err());
+
+        // 跟随符号链接：复制目标内容，SSD 上是普通文件
+        let (local2, ssd2) = (tmp.path().join("local2"), tmp.path().join("ssd2"));
+        std::fs::create_dir_all(&local2).unwrap();
+        std::fs::create_dir_all(&ssd2).unwrap();
+        symlink(local.join("v2"), local2.join("release")).unwrap();
+        opts.scan.follow_symlinks = true;
+        let (_, result) = sync_one_mapping(&local2, &ssd2, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let copied = ssd2.join("work/release/app.bin");
+        assert!(!std::fs::symlink_metadata(ssd2.join("work/release")).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read_to_string(copied).unwrap(), "v2");
+    }
+
+    #[test]
+    fn test_progress_events_follow_plan() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(local.join("docs")).unwrap();
+        std::fs::create_dir_all(local.join("empty")).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        std::fs::write(local.join("docs/a.txt"), "aaa").unwrap();
+        std::fs::write(local.join("b.txt"), "bb").unwrap();
+
+        let mut events = vec![];
+        let (plan, result) = sync_one_mapping(&local, &ssd, "work", "mac", &sync_opts(), &mut |p| {
+            events.push((p.index, p.total, p.entry.path.clone(), p.entry.action.clone(), p.bytes, p.total_bytes, p.done));
+        })
+        .unwrap();
+        assert_eq!(result.copied_to_ssd, 3);
+
+        // 每个动作按执行顺序先报告开始、再报告完成
+        let mut expected = vec![];
+        for (i, entry) in plan.ordered().into_iter().enumerate() {
+            let bytes = if entry.is_dir { 0 } else { entry.entry.as_ref().unwrap().size };
+            for done in [false, true] {
+                expected.push((i + 1, 3, entry.path.clone(), entry.action.clone(), bytes, 5, done));
+            }
+        }
+        assert_eq!(events, expected);
+        assert_eq!(events[0].2, "empty");
+    }
+
+    #[test]
+    fn test_parallel_copies_complete() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(&ssd).unwrap();
+        for i in 0..400 {
+            let dir = local.join(format!("d{}", i % 20));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join(format!("f{}.txt", i)), i.to_string()).unwrap();
+        }
+        let mut opts = sync_opts();
+        opts.copy_concurrency = 8;
+        let (_, result) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        // 删除与复制在同一次同步中：删除串行执行，复制并行
+        std::fs::remove_dir_all(local.join("d0")).unwrap();
+        for i in 400..600 {
+            std::fs::write(local.join(format!("d{}/f{}.txt", i % 19 + 1, i)), i.to_string()).unwrap();
+        }
+        let (_, result) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!((result.copied_to_ssd, result.deleted_from_ssd), (200, 20));
+        assert!(!ssd.join("work/d0").exists());
+        for i in (1..600).filter(|i| i % 20 != 0 || *i >= 400) {
+            let dir = if i < 400 { i % 20 } else { i % 19 + 1 };
+            let copied = ssd.join(format!("work/d{}/f{}.txt", dir, i));
+            assert_eq!(std::fs::read_to_string(&copied).unwrap(), i.to_string());
+        }
+    }
+
+    #[test]
+    fn test_confirm_before_executing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        std::fs::write(local.join("a.txt"), "a").unwrap();
+        std::fs::write(local.join("b.txt"), "b").unwrap();
+        let mut opts = sync_opts();
+        opts.confirm = ConfirmPolicy::OnDelete;
+        opts.confirm_answer = Some(false);
+        // 只有复制，on-delete 不询问
+        sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert!(ssd.join("work/a.txt").exists());
+
+        std::fs::remove_file(local.join("a.txt")).unwrap();
+        let Err(err) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}) else {
+            panic!("sync ran without confirmation");
+        };
+        assert!(err.downcast_ref::<SyncDeclined>().is_some());
+        assert!(ssd.join("work/a.txt").exists());
+
+        opts.confirm_answer = Some(true);
+        let (_, result) = sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert_eq!(result.deleted_from_ssd, 1);
+        assert!(!ssd.join("work/a.txt").exists());
+    }
+
+    #[test]
+    fn test_verify_detects_corruption_and_missing_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        for (name, content) in [("a.txt", "aaaa"), ("b.txt", "bbbb"), ("c.txt", "cccc")] {
+            std::fs::write(local.join(name), content).unwrap();
+        }
+        sync_one_mapping(&local, &ssd, "work", "mac", &sync_opts(), &mut |_| {}).unwrap();
+        let report = verify_ssd(&ssd, "work", "mac").unwrap();
+        assert_eq!((report.checked, report.problems()), (3, 0));
+
+        // 同样大小、同样 mtime 的内容变化：典型的静默损坏
+        let path = ssd.join("work/a.txt");
+        let mtime = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::fs::write(&path, "aaab").unwrap();
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(mtime).unwrap();
+        std::fs::remove_file(ssd.join("work/c.txt")).unwrap();
+
+        let report = verify_ssd(&ssd, "work", "mac").unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.mismatched, vec![("a.txt".to_string(), true)]);
+        assert_eq!(report.missing, vec!["c.txt"]);
+        assert!(verify_ssd(&ssd, "other", "mac").is_err());
+    }
+
+    #[test]
+    fn test_copy_file_where_dir_expected_keeps_both() {
+        let (tmp, result) = run(
+            ConflictStrategy::Both,
+            vec![
+                copy_entry("photos/a.jpg", SyncAction::CopyToLocal, false),
+                copy_entry("report", SyncAction::CopyToLocal, false),
+            ],
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let local = tmp.path().join("local");
+        assert_eq!(std::fs::read_to_string(local.join("report")).unwrap(), "file on ssd");
+        assert_eq!(std::fs::read_to_string(local.join("photos/a.jpg")).unwrap(), "jpg");
+        // 挡路的目录和文件都以冲突副本保留
+        let names: Vec<String> = std::fs::read_dir(&local)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert!(names.iter().any(|n| n.starts_with("report.conflict.mac.")));
+        assert!(names.iter().any(|n| n.starts_with("photos.conflict.mac.")));
+    }
+
+    #[test]
+    fn test_dir_where_file_expected_replaced() {
+        let (tmp, result) = run(
+            ConflictStrategy::SsdWins,
+            vec![
+                copy_entry("notes.txt", SyncAction::CopyToLocal, false),
+                copy_entry("photos", SyncAction::CopyToLocal, true),
+                copy_entry("report", SyncAction::CopyToLocal, false),
+            ],
+        );
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        let local = tmp.path().join("local");
+        assert!(local.join("photos").is_dir());
+        assert!(local.join("report").is_file());
+        assert_eq!(std::fs::read_dir(&local).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_illegal_char_policy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(local.join("a:b")).unwrap();
+        std::fs::write(local.join("a:b/c?.txt"), "data").unwrap();
+        let plan = SyncPlan { actions: vec![copy_entry("a:b/c?.txt", SyncAction::CopyToSsd, false)] };
+
+        let mut engine = SyncEngine::new("mac", ConflictStrategy::Both, false);
+        engine.illegal_char_policy = Some(IllegalCharPolicy::Error);
+        let result = engine.execute_plan(&plan, &local, &ssd, &mut |_| {}).unwrap();
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].contains("illegal filename"), "{:?}", result.errors);
+
+        engine.illegal_char_policy = Some(IllegalCharPolicy::Sanitize);
+        let result = engine.execute_plan(&plan, &local, &ssd, &mut |_| {}).unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(std::fs::read_to_string(ssd.join("a：b/c？.txt")).unwrap(), "data");
+        assert_eq!(engine.names.lock().unwrap().decode("a：b/c？.txt"), "a:b/c?.txt");
+    }
+
+    #[test]
+    fn test_dry_run_reports_unwritable_targets() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(ssd.join("docs")).unwrap();
+        std::fs::write(local.join("a.txt"), "new").unwrap();
+        std::fs::write(ssd.join("a.txt"), "old").unwrap();
+        let mut perms = std::fs::metadata(ssd.join("a.txt")).unwrap().permissions();
+        perms.set_readonly(true);
+        std::fs::set_permissions(ssd.join("a.txt"), perms).unwrap();
+        std::fs::write(local.join("b.txt"), "b").unwrap();
+
+        let engine = SyncEngine::new("mac", ConflictStrategy::Both, true);
+        let plan = SyncPlan {
+            actions: vec![
+                copy_entry("a.txt", SyncAction::CopyToSsd, false),
+                copy_entry("docs/new/b.txt", SyncAction::CopyToSsd, false),
+            ],
+        };
+        let result = engine.execute_plan(&plan, &local, &ssd, &mut |_| {}).unwrap();
+        assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+        assert!(result.errors[0].contains("read-only"), "{:?}", result.errors);
+        // 探测用的临时文件不会残留
+        assert_eq!(std::fs::read_dir(ssd.join("docs")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_retry_transient_errors() {
+        use std::io::{Error, ErrorKind};
+        let policy = RetryPolicy { retries: 2, delay: Duration::from_millis(1) };
+        // 前两次失败、第三次成功
+        let mut calls = 0;
+        let outcome = policy.run("op", || {
+            calls += 1;
+            if calls <= 2 { Err(Error::from(ErrorKind::Interrupted)) } else { Ok(calls) }
+        });
+        assert_eq!(outcome.unwrap(), 3);
+
+        // 重试次数用完
+        let mut calls = 0;
+        let outcome: std::io::Result<()> = policy.run("op", || {
+            calls += 1;
+            Err(Error::from(ErrorKind::TimedOut))
+        });
+        assert!(outcome.is_err());
+        assert_eq!(calls, 3);
+
+        // 非暂时性错误不重试
+        for kind in [ErrorKind::NotFound, ErrorKind::PermissionDenied] {
+            let mut calls = 0;
+            let outcome: std::io::Result<()> = policy.run("op", || {
+                calls += 1;
+                Err(Error::from(kind))
+            });
+            assert_eq!(outcome.unwrap_err().kind(), kind);
+            assert_eq!(calls, 1);
+        }
+    }
+
+    #[test]
+    fn test_wait_for_ssd_resumes_or_aborts() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::write(local.join("a.txt"), "a").unwrap();
+        std::fs::write(local.join("b.txt"), "b").unwrap();
+        let plan = SyncPlan {
+            actions: vec![
+                copy_entry("a.txt", SyncAction::CopyToSsd, false),
+                copy_entry("b.txt", SyncAction::CopyToSsd, false),
+            ],
+        };
+        let mut engine = SyncEngine::new("mac", ConflictStrategy::Both, false);
+                engine.wait_for_ssd = Some(Duration::from_secs(1));
+
+        // SSD 根目录在等待期间重新出现：继续执行剩余动作
+        let remount = std::thread::spawn({
+            let ssd = ssd.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(200));
+                std::fs::create_dir_all(&ssd).unwrap();
+            }
+        });
+        let result = engine.execute_plan(&plan, &local, &ssd, &mut |_| {}).unwrap();
+        remount.join().unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.copied_to_ssd, 2);
+
+        // 超时：干净地中止，不会在挂载点下创建目录
+        std::fs::remove_dir_all(&ssd).unwrap();
+        let Err(err) = engine.execute_plan(&plan, &local, &ssd, &mut |_| {}) else {
+            panic!("expected the sync to abort");
+        };
+        assert!(err.to_string().contains("stopped after 0 of 2"), "{}", err);
+        assert!(!ssd.exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_ssd_full_stops_copies() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        let suffix = crate::config::DEFAULT_TEMP_SUFFIX;
+        let mut actions = vec![copy_entry("first.txt", SyncAction::CopyToSsd, false)];
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(local.join(name), name).unwrap();
+            // 写入 /dev/full 总是返回 ENOSPC：模拟 SSD 写满
+            std::os::unix::fs::symlink("/dev/full", ssd.join(format!("{}{}", name, suffix))).unwrap();
+            actions.push(copy_entry(name, SyncAction::CopyToSsd, false));
+        }
+        std::fs::write(local.join("first.txt"), "first").unwrap();
+        std::fs::write(ssd.join("old.txt"), "old").unwrap();
+        actions.push(copy_entry("old.txt", SyncAction::DeleteFromSsd, false));
+        actions.push(copy_entry("new.txt", SyncAction::CopyToLocal, false));
+        std::fs::write(ssd.join("new.txt"), "new").unwrap();
+
+        let engine = SyncEngine::new("mac", ConflictStrategy::Both, false);
+                let result = engine.execute_plan(&SyncPlan { actions }, &local, &ssd, &mut |_| {}).unwrap();
+
+        // 第一个失败后不再尝试其余的复制（它们的临时文件链接仍在），只报告一条错误
+        assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+        assert!(result.errors[0].starts_with("SSD full, stopping: 3 copy(ies)"), "{:?}", result.errors);
+        assert!(ssd.join(format!("b.txt{}", suffix)).is_symlink());
+        assert!(ssd.join(format!("c.txt{}", suffix)).is_symlink());
+        // 之前完成的复制以及删除、复制到本地照常执行
+        assert_eq!(result.copied_to_ssd, 1);
+        assert_eq!(std::fs::read_to_string(ssd.join("first.txt")).unwrap(), "first");
+        assert!(!ssd.join("old.txt").exists());
+        assert_eq!(std::fs::read_to_string(local.join("new.txt")).unwrap(), "new");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_ssd_full_during_conflict_copy() {
+        let suffix = crate::config::DEFAULT_TEMP_SUFFIX;
+        for strategy in [ConflictStrategy::Both, ConflictStrategy::NewerWins] {
+            let tmp = tempfile::tempdir().unwrap();
+            let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+            std::fs::create_dir_all(&local).unwrap();
+            std::fs::create_dir_all(&ssd).unwrap();
+            std::fs::write(local.join("notes.txt"), "local").unwrap();
+            std::fs::write(local.join("later.txt"), "later").unwrap();
+            std::fs::write(ssd.join("notes.txt"), "ssd").unwrap();
+            let old = std::time::SystemTime::now() - Duration::from_secs(3600);
+            std::fs::File::options().write(true).open(ssd.join("notes.txt")).unwrap().set_modified(old).unwrap();
+            // 本地版本写到 SSD 时（NewerWins 覆盖、Both 的冲突副本）SSD 已满
+            std::os::unix::fs::symlink("/dev/full", ssd.join(format!("notes.txt{}", suffix))).unwrap();
+            let now = chrono::Utc::now();
+            for secs in 0..5 {
+                let timestamp = (now + chrono::Duration::seconds(secs)).format("%Y%m%d%H%M%S");
+                let temp = ssd.join(format!("notes.conflict.mac.{}.txt{}", timestamp, suffix));
+                std::os::unix::fs::symlink("/dev/full", temp).unwrap();
+            }
+            let conflict = SyncPlanEntry {
+                path: "notes.txt".to_string(),
+                action: SyncAction::Conflict(ConflictInfo {
+                    local_change: crate::diff::ChangeType::Modified,
+                    ssd_change: crate::diff::ChangeType::Modified,
+                    versions: None,
+                    base: None,
+                    local: None,
+                    ssd: None,
+                }),
+                is_dir: false,
+                entry: None,
+            };
+            let plan = SyncPlan { actions: vec![conflict, copy_entry("later.txt", SyncAction::CopyToSsd, false)] };
+
+            let engine = SyncEngine::new("mac", strategy, false);
+            let mut events = vec![];
+            let result = engine.execute_plan(&plan, &local, &ssd, &mut |p| events.push((p.index, p.done))).unwrap();
+
+            // 冲突的写入失败后按 SSD 已满处理：之后的复制不再尝试，只报告一条错误
+            assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+            assert!(result.errors[0].starts_with("SSD full, stopping: 2 copy(ies)"), "{:?}", result.errors);
+            assert!(!ssd.join("later.txt").exists());
+            assert_eq!(std::fs::read_to_string(ssd.join("notes.txt")).unwrap(), "ssd");
+            // 开始的动作也报告了完成
+            assert_eq!(events, vec![(1, false), (1, true)]);
+        }
+    }
+
+    #[test]
+    fn test_resume_after_interrupted_sync() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        std::fs::write(local.join("a.txt"), "a").unwrap();
+        std::fs::write(local.join("b.txt"), "b").unwrap();
+        let opts = sync_opts();
+        sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+
+        std::fs::write(local.join("a.txt"), "a changed").unwrap();
+        std::fs::write(local.join("b.txt"), "b changed").unwrap();
+        std::fs::write(local.join("c.txt"), "c new").unwrap();
+
+        // 中断的同步：a.txt 和 c.txt 已复制完成，快照尚未更新
+        let resume_file = AppConfig::ssd_snapshots_dir(&ssd, "mac").join(Snapshot::resume_filename("work"));
+        let mut engine = SyncEngine::new("mac", ConflictStrategy::Both, false);
+        engine.resume_log = Some(ResumeLog::open(&resume_file).unwrap());
+        let copied = ["a.txt", "c.txt"].map(|path| SyncPlanEntry {
+            entry: Some(scanner::file_entry(&local.join(path), HashAlgorithm::default()).unwrap()),
+            ..copy_entry(path, SyncAction::CopyToSsd, false)
+        });
+        let plan = SyncPlan { actions: copied.to_vec() };
+        engine.execute_plan(&plan, &local, &ssd.join("work"), &mut |_| {}).unwrap();
+        drop(engine);
+        assert_eq!(ResumeLog::load(&resume_file).unwrap().len(), 2);
+
+        // 中断之后 SSD 上的 c.txt 又被改动：需要重新处理
+        std::fs::write(ssd.join("work/c.txt"), "c edited on ssd").unwrap();
+
+        let mut dry = sync_opts();
+        dry.dry_run = true;
+        let (plan, _) = sync_one_mapping(&local, &ssd, "work", "mac", &dry, &mut |_| {}).unwrap();
+        let paths: Vec<&str> = plan.actions.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["b.txt", "c.txt"]);
+        assert_eq!(plan.actions[0].action, SyncAction::CopyToSsd);
+        assert!(matches!(plan.actions[1].action, SyncAction::Conflict(_)));
+        assert!(resume_file.exists());
+
+        // 同步成功后续传记录被删除
+        sync_one_mapping(&local, &ssd, "work", "mac", &opts, &mut |_| {}).unwrap();
+        assert!(!resume_file.exists());
+        assert_eq!(std::fs::read_to_string(ssd.join("work/b.txt")).unwrap(), "b changed");
+    }
+}
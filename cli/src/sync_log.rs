@@ -0,0 +1,318 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+use crate::config::AppConfig;
+use crate::diff::SyncPlan;
+use crate::sync_engine::SyncResult;
+
+const LOG_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S UTC";
+
+pub fn log_path(ssd_mount: &Path) -> std::path::PathBuf {
+    AppConfig::ssd_syncer_dir(ssd_mount).join("sync.log")
+}
+
+/// `sync.jsonl` next to `sync.log`: the same entries with every changed path (`[sync_settings] json_log`)
+pub fn json_log_path(ssd_mount: &Path) -> std::path::PathBuf {
+    AppConfig::ssd_syncer_dir(ssd_mount).join("sync.jsonl")
+}
+
+/// What one sync did to one mapping; one line of `sync.log`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub machine: String,
+    pub mapping: String,
+    pub to_ssd: usize,
+    pub to_local: usize,
+    pub deleted: usize,
+    pub renamed: usize,
+    pub conflicts: usize,
+    pub errors: usize,
+    pub bytes: u64,
+    /// Planned actions with their paths (only in `sync.jsonl`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<LoggedChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoggedChange {
+    pub action: String,
+    pub path: String,
+}
+
+impl LogEntry {
+    pub fn new(machine: &str, mapping: &str, result: &SyncResult) -> Self {
+        Self {
+            // 日志只精确到秒：与解析回来的时间戳保持一致
+            timestamp: DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap_or_default(),
+            machine: machine.to_string(),
+            mapping: mapping.to_string(),
+            to_ssd: result.copied_to_ssd,
+            to_local: result.copied_to_local,
+            deleted: result.deleted_from_ssd + result.deleted_from_local,
+            renamed: result.renamed,
+            conflicts: result.conflicts,
+            errors: result.errors.len(),
+            bytes: result.bytes_copied,
+            changes: vec![],
+        }
+    }
+
+    pub fn actions(&self) -> usize {
+        self.to_ssd + self.to_local + self.deleted + self.renamed + self.conflicts
+    }
+
+    /// `[2024-03-10 11:45:00 UTC] machine=mac mapping=WORK actions=3 to_ssd=2 ...`. `actions`
+    /// 仍紧跟在 machine 之后，与旧格式（`machine=X actions=N`）兼容；含空格等字符的值加引号。
+    pub fn to_line(&self) -> String {
+        let quote = |s: &str| {
+            if s.is_empty() || s.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') {
+                serde_json::to_string(s).unwrap_or_default()
+            } else {
+                s.to_string()
+            }
+        };
+        format!(
+            "[{}] machine={} mapping={} actions={} to_ssd={} to_local={} deleted={} renamed={} conflicts={} errors={} bytes={}",
+            self.timestamp.format(LOG_TIMESTAMP_FORMAT),
+            quote(&self.machine),
+            quote(&self.mapping),
+            self.actions(),
+            self.to_ssd,
+            self.to_local,
+            self.deleted,
+            self.renamed,
+            self.conflicts,
+            self.errors,
+            self.bytes
+        )
+    }
+
+    /// Parse a line written by `to_line`. Older lines without `mapping=` give `None`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let timestamp = parse_log_timestamp(line)?;
+        let mut rest = &line[line.find(']')? + 1..];
+        let mut fields = std::collections::HashMap::new();
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+            let (key, value) = rest.split_once('=')?;
+            let value = if value.starts_with('"') {
+                let mut stream = serde_json::Deserializer::from_str(value).into_iter::<String>();
+                let parsed = stream.next()?.ok()?;
+                rest = &value[stream.byte_offset()..];
+                parsed
+            } else {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                rest = &value[end..];
+                value[..end].to_string()
+            };
+            fields.insert(key, value);
+        }
+        let count = |key: &str| fields.get(key).and_then(|v| v.parse().ok());
+        Some(Self {
+            timestamp,
+            machine: fields.get("machine")?.clone(),
+            mapping: fields.get("mapping")?.clone(),
+            to_ssd: count("to_ssd")?,
+            to_local: count("to_local")?,
+            deleted: count("deleted")?,
+            renamed: count("renamed")?,
+            conflicts: count("conflicts")?,
+            errors: count("errors")?,
+            bytes: fields.get("bytes")?.parse().ok()?,
+            changes: vec![],
+        })
+    }
+
+    /// Record the planned actions' paths (for `sync.jsonl`).
+    pub fn with_changes(mut self, plan: &SyncPlan) -> Self {
+        self.changes = plan
+            .actions
+            .iter()
+            .map(|entry| LoggedChange { action: entry.action.kind().to_string(), path: entry.path.clone() })
+            .collect();
+        self
+    }
+}
+
+/// Append `entry` to `sync.log`, and with `json` also to `sync.jsonl` including its changes.
+pub fn append_sync_log(ssd_mount: &Path, entry: &LogEntry, json: bool) -> Result<()> {
+    let append = |path: std::path::PathBuf, line: String| -> Result<()> {
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    };
+    append(log_path(ssd_mount), format!("{}\n", entry.to_line()))?;
+    if json {
+        append(json_log_path(ssd_mount), format!("{}\n", serde_json::to_string(entry)?))?;
+    }
+    Ok(())
+}
+
+/// Parse the `[YYYY-MM-DD HH:MM:SS UTC]` prefix of a log line.
+pub fn parse_log_timestamp(line: &str) -> Option<DateTime<Utc>> {
+    let rest = line.strip_prefix('[')?;
+    let end = rest.find(']')?;
+    NaiveDateTime::parse_from_str(&rest[..end], LOG_TIMESTAMP_FORMAT)
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+/// Parse a user-supplied date: `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS` (UTC) or RFC 3339.
+pub fn parse_date_arg(s: &str) -> Result<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    if let Ok(t) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
+        return Ok(t.and_utc());
+    }
+    if let Ok(d) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Ok(d.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+    anyhow::bail!(
+        "Invalid date '{}'. Expected YYYY-MM-DD, \"YYYY-MM-DD HH:MM:SS\" (UTC) or RFC 3339",
+        s
+    )
+}
+
+/// Start of the window for `log --since 7d`: `now` minus a duration such as `24h` or `30m`.
+pub fn parse_since(s: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let secs = crate::units::parse_duration(s)?;
+    i64::try_from(secs)
+        .ok()
+        .and_then(chrono::TimeDelta::try_seconds)
+        .and_then(|d| now.checked_sub_signed(d))
+        .ok_or_else(|| anyhow::anyhow!("Duration '{}' is too large", s))
+}
+
+/// Time window filter for log entries: `newer_than` is inclusive, `older_than` exclusive.
+/// 设置了任一边界时，无法解析时间戳的行会被排除。
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    pub newer_than: Option<DateTime<Utc>>,
+    pub older_than: Option<DateTime<Utc>>,
+}
+
+impl LogFilter {
+    pub fn is_empty(&self) -> bool {
+        self.newer_than.is_none() && self.older_than.is_none()
+    }
+
+    pub fn matches(&self, line: &str) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Some(ts) = parse_log_timestamp(line) else {
+            return false;
+        };
+        if let Some(newer) = self.newer_than {
+            if ts < newer {
+                return false;
+            }
+        }
+        if let Some(older) = self.older_than {
+            if ts >= older {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Lines of the log within `filter`, at most the last `limit` of them.
+pub fn recent_entries<'a>(content: &'a str, filter: &LogFilter, limit: usize) -> Vec<&'a str> {
+    // 先按时间窗口过滤，再按 --limit 截取最近的条目
+    let lines: Vec<&str> = content.lines().filter(|l| filter.matches(l)).collect();
+    let start = lines.len().saturating_sub(limit);
+    lines[start..].to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+[2024-01-01 10:00:00 UTC] machine=mac actions=3
+[2024-02-15 08:30:00 UTC] machine=win actions=1
+garbage line
+[2024-03-01 23:59:59 UTC] machine=mac actions=7";
+
+    #[test]
+    fn test_parse_log_timestamp() {
+        let ts = parse_log_timestamp("[2024-02-15 08:30:00 UTC] machine=win actions=1").unwrap();
+        assert_eq!(ts, parse_date_arg("2024-02-15 08:30:00").unwrap());
+        assert!(parse_log_timestamp("machine=win actions=1").is_none());
+        assert!(parse_log_timestamp("[not a date] x").is_none());
+    }
+
+    #[test]
+    fn test_parse_date_arg() {
+        assert_eq!(
+            parse_date_arg("2024-02-15").unwrap(),
+            parse_date_arg("2024-02-15T00:00:00Z").unwrap()
+        );
+        assert!(parse_date_arg("15/02/2024").is_err());
+    }
+
+    #[test]
+    fn test_filter_window() {
+        let filter = LogFilter {
+            newer_than: Some(parse_date_arg("2024-02-01").unwrap()),
+            older_than: Some(parse_date_arg("2024-03-01").unwrap()),
+        };
+        let kept: Vec<&str> = LOG.lines().filter(|l| filter.matches(l)).collect();
+        assert_eq!(kept, vec!["[2024-02-15 08:30:00 UTC] machine=win actions=1"]);
+
+        // 无过滤条件时保留所有行（包括无法解析的行）
+        let all: Vec<&str> = LOG.lines().filter(|l| LogFilter::default().matches(l)).collect();
+        assert_eq!(all.len(), 4);
+    }
+
+    #[test]
+    fn test_log_entry_round_trip() {
+        let result = SyncResult { copied_to_ssd: 2, copied_to_local: 1, deleted_from_local: 1, conflicts: 1, bytes_copied: 2048, ..Default::default() };
+        let entry = LogEntry::new("mac", "My Docs", &result);
+        let line = entry.to_line();
+        assert!(line.contains("machine=mac mapping=\"My Docs\" actions=5 to_ssd=2 to_local=1 deleted=1"), "{}", line);
+        assert_eq!(LogEntry::parse(&line), Some(entry.clone()));
+        assert_eq!(parse_log_timestamp(&line), Some(entry.timestamp));
+
+        // 旧格式的行仍可按时间过滤，但不是结构化条目
+        assert_eq!(LogEntry::parse("[2024-01-01 10:00:00 UTC] machine=mac actions=3"), None);
+        assert_eq!(LogEntry::parse("garbage line"), None);
+
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(serde_json::from_str::<LogEntry>(&json).unwrap(), entry);
+    }
+
+    #[test]
+    fn test_since_then_limit() {
+        let now = parse_date_arg("2024-03-10 12:00:00").unwrap();
+        assert_eq!(parse_since("7d", now).unwrap(), parse_date_arg("2024-03-03 12:00:00").unwrap());
+        assert_eq!(parse_since("30m", now).unwrap(), parse_date_arg("2024-03-10 11:30:00").unwrap());
+        assert!(parse_since("7 days", now).is_err());
+        assert!(parse_since("99999999999w", now).is_err());
+
+        let log = "\
+[2024-03-01 09:00:00 UTC] machine=mac actions=1
+[2024-03-04 09:00:00 UTC] machine=mac actions=2
+[2024-03-09 09:00:00 UTC] machine=win actions=3
+old format line
+[2024-03-10 11:45:00 UTC] machine=mac actions=4";
+        let filter = LogFilter { newer_than: Some(parse_since("7d", now).unwrap()), older_than: None };
+        assert_eq!(recent_entries(log, &filter, 20).len(), 3);
+        // --since 先过滤，--limit 再截取最近的条目
+        assert_eq!(
+            recent_entries(log, &filter, 2),
+            vec!["[2024-03-09 09:00:00 UTC] machine=win actions=3", "[2024-03-10 11:45:00 UTC] machine=mac actions=4"]
+        );
+        assert_eq!(recent_entries(log, &LogFilter::default(), 2)[0], "old format line");
+    }
+}
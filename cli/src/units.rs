@@ -0,0 +1,114 @@
+use anyhow::Result;
+
+/// Parse a duration like `30s`, `10m`, `12h`, `90d` or `2w` into seconds.
+/// 不带单位的数字按秒处理。
+pub fn parse_duration(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let Ok(value) = num.parse::<u64>() else {
+        anyhow::bail!("Invalid duration '{}'. Expected e.g. 30s, 10m, 12h, 90d or 2w", s);
+    };
+    let factor = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        other => anyhow::bail!(
+            "Invalid duration unit '{}' in '{}'. Use s, m, h, d or w",
+            other,
+            s
+        ),
+    };
+    value
+        .checked_mul(factor)
+        .ok_or_else(|| anyhow::anyhow!("Duration '{}' is too large", s))
+}
+
+/// Parse a size like `500M`, `1G` or `2TB` into bytes (binary units: 1K = 1024).
+/// 不带单位的数字按字节处理，单位不区分大小写。
+pub fn parse_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let Ok(value) = num.parse::<u64>() else {
+        anyhow::bail!("Invalid size '{}'. Expected e.g. 500M, 1G or 2T", s);
+    };
+    let power = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" => 1,
+        "M" | "MB" => 2,
+        "G" | "GB" => 3,
+        "T" | "TB" => 4,
+        other => anyhow::bail!("Invalid size unit '{}' in '{}'. Use K, M, G or T", other, s),
+    };
+    value
+        .checked_mul(1024u64.pow(power))
+        .ok_or_else(|| anyhow::anyhow!("Size '{}' is too large", s))
+}
+
+/// Human-readable byte count in binary units, e.g. `1.5 GiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    // 保留一位小数后会显示为 1024.0 的值（如 1 MiB - 1 字节）进到下一个单位
+    while unit < UNITS.len() - 1 && (value >= 1024.0 || (unit > 0 && (value * 10.0).round() >= 10240.0)) {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("45").unwrap(), 45);
+        assert_eq!(parse_duration("30s").unwrap(), 30);
+        assert_eq!(parse_duration("10m").unwrap(), 600);
+        assert_eq!(parse_duration("12h").unwrap(), 43_200);
+        assert_eq!(parse_duration(" 90d ").unwrap(), 7_776_000);
+        assert_eq!(parse_duration("2w").unwrap(), 1_209_600);
+        for bad in ["", "d", "10x", "1.5h", "-3d"] {
+            assert!(parse_duration(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("10K").unwrap(), 10 * 1024);
+        assert_eq!(parse_size("500M").unwrap(), 500 * 1024 * 1024);
+        assert_eq!(parse_size(" 1g ").unwrap(), 1024 * 1024 * 1024);
+        assert_eq!(parse_size("2TB").unwrap(), 2 * 1024u64.pow(4));
+        assert_eq!(parse_size("12 mb").unwrap(), 12 * 1024 * 1024);
+        for bad in ["", "G", "1.5G", "10X", "-1M", "99999999999T"] {
+            assert!(parse_size(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1024), "1.0 KiB");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(1024 * 1024 - 1), "1.0 MiB");
+        assert_eq!(format_bytes(1024 * 1024 - 52 * 1024), "972.0 KiB");
+        assert_eq!(format_bytes(1024 * 1024 - 60), "1023.9 KiB");
+        assert_eq!(format_bytes(1024 * 1024), "1.0 MiB");
+        assert_eq!(format_bytes(1024 * 1024 * 1024 - 1), "1.0 GiB");
+        assert_eq!(format_bytes(1024 * 1024 * 1024), "1.0 GiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024 * 1024), "5.0 GiB");
+        assert_eq!(format_bytes(1024u64.pow(4) - 1), "1.0 TiB");
+        assert_eq!(format_bytes(2048 * 1024u64.pow(4)), "2048.0 TiB");
+    }
+}
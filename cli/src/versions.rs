@@ -0,0 +1,160 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::AppConfig;
+use crate::diff::{SyncAction, SyncPlan};
+use crate::snapshot::Snapshot;
+
+/// Per-file version counters shared by all machines (`[sync_settings] version_counters`).
+/// 每台机器把本地修改推到 SSD 时版本号加一；基准快照的 `FileEntry::version` 记录上次同步时的版本，
+/// 两者相减即可知道 SSD 一侧自上次同步以来前进了多少。
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VersionMap {
+    #[serde(skip)]
+    path: PathBuf,
+    versions: BTreeMap<String, u64>,
+}
+
+impl VersionMap {
+    pub fn file_path(ssd_data_root: &Path, ssd_rel: &str) -> PathBuf {
+        AppConfig::ssd_syncer_dir(ssd_data_root)
+            .join("versions")
+            .join(Snapshot::snapshot_filename(ssd_rel))
+    }
+
+    pub fn load(ssd_data_root: &Path, ssd_rel: &str) -> Result<Self> {
+        let path = Self::file_path(ssd_data_root, ssd_rel);
+        let mut map: VersionMap = if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", path.display()))?
+        } else {
+            VersionMap::default()
+        };
+        map.path = path;
+        Ok(map)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(&self.path, content)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Version of the SSD copy of `path`. A file changed on the SSD without going through
+    /// ssd-syncer still counts as one step ahead of the base.
+    fn ssd_version(&self, path: &str, base: &Snapshot, ssd: &Snapshot) -> u64 {
+        let recorded = self.versions.get(path).copied().unwrap_or(0);
+        let Some(base_entry) = base.files.get(path) else {
+            return if ssd.files.contains_key(path) { recorded.max(1) } else { recorded };
+        };
+        match ssd.files.get(path) {
+            Some(entry) if entry.hash != base_entry.hash => recorded.max(base_entry.version + 1),
+            _ => recorded.max(base_entry.version),
+        }
+    }
+
+    /// Fill in `(local, ssd)` versions for every conflict in `plan`.
+    pub fn annotate_conflicts(&self, plan: &mut SyncPlan, base: &Snapshot, ssd: &Snapshot) {
+        for entry in &mut plan.actions {
+            if let SyncAction::Conflict(info) = &mut entry.action {
+                // 本地每次同步最多前进一步：有改动即为基准版本 + 1
+                let local = base.files.get(&entry.path).map_or(1, |b| b.version + 1);
+                info.versions = Some((local, self.ssd_version(&entry.path, base, ssd)));
+            }
+        }
+    }
+
+    /// Assign versions to the new base snapshot and publish the ones this machine advanced.
+    /// Content that came from the SSD keeps the SSD version; content pushed from this
+    /// machine gets one more than either side had.
+    pub fn update(&mut self, new_base: &mut Snapshot, base: &Snapshot, ssd: &Snapshot, final_ssd: &Snapshot) {
+        for (path, entry) in new_base.files.iter_mut() {
+            let ssd_version = self.ssd_version(path, base, ssd);
+            entry.version = match (base.files.get(path), ssd.files.get(path)) {
+                (Some(b), _) if b.hash == entry.hash => b.version,
+                (_, Some(s)) if s.hash == entry.hash => ssd_version,
+                (b, _) => b.map_or(0, |b| b.version).max(ssd_version) + 1,
+            };
+            let recorded = self.versions.entry(path.clone()).or_insert(0);
+            *recorded = (*recorded).max(entry.version);
+        }
+        self.versions.retain(|path, _| final_ssd.files.contains_key(path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::{ChangeType, ConflictInfo, SyncPlanEntry};
+    use crate::snapshot::FileEntry;
+
+    fn snap(files: &[(&str, &str, u64)]) -> Snapshot {
+        let mut s = Snapshot::new("work", "mac");
+        for (path, hash, version) in files {
+            s.files.insert(
+                path.to_string(),
+                FileEntry {
+                    size: 1,
+                    mtime_secs: 1,
+                    hash: hash.to_string(),
+                    is_dir: false,
+                    version: *version,
+                    is_symlink: false,
+                },
+            );
+        }
+        s
+    }
+
+    #[test]
+    fn test_versions_track_which_side_advanced() {
+        let mut map = VersionMap::default();
+        let base = snap(&[("a.txt", "h1", 3), ("b.txt", "h1", 3)]);
+        // 另一台机器已推送两次 a.txt；b.txt 在 SSD 上被直接修改
+        map.versions.insert("a.txt".to_string(), 5);
+        map.versions.insert("b.txt".to_string(), 3);
+        let ssd = snap(&[("a.txt", "h5", 0), ("b.txt", "h2", 0)]);
+
+        let conflict = |path: &str| SyncPlanEntry {
+            path: path.to_string(),
+            action: SyncAction::Conflict(ConflictInfo {
+                local_change: ChangeType::Modified,
+                ssd_change: ChangeType::Modified,
+                versions: None,
+                base: None,
+                local: None,
+                ssd: None,
+            }),
+            is_dir: false,
+            entry: None,
+        };
+        let mut plan = SyncPlan { actions: vec![conflict("a.txt"), conflict("b.txt")] };
+        map.annotate_conflicts(&mut plan, &base, &ssd);
+        let versions: Vec<_> = plan
+            .actions
+            .iter()
+            .map(|e| match &e.action {
+                SyncAction::Conflict(info) => info.versions,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(versions, vec![Some((4, 5)), Some((4, 4))]);
+
+        // 同步后：a.txt 取 SSD 版本，b.txt 由本机推送
+        let mut new_base = snap(&[("a.txt", "h5", 0), ("b.txt", "local", 0), ("c.txt", "new", 0)]);
+        let final_ssd = new_base.clone();
+        map.update(&mut new_base, &base, &ssd, &final_ssd);
+        assert_eq!(new_base.files["a.txt"].version, 5);
+        assert_eq!(new_base.files["b.txt"].version, 5);
+        assert_eq!(new_base.files["c.txt"].version, 1);
+        assert_eq!(map.versions["b.txt"], 5);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::config::SYNCER_DIR_NAME;
+use crate::scanner::ScanOptions;
+
+/// How long to wait before trying again when a mapping's SSD is not connected.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A batch of changes is synced at the latest this many delays after its first change, even
+/// if files keep changing, so one busy folder (e.g. a running build) can't hold back the rest.
+const MAX_WAIT_FACTOR: u32 = 5;
+
+/// Turns file events into syncs. Changes in all mappings are collected into one batch that is
+/// due once no watched folder has changed for `delay`, so a burst of saves (or a large copy)
+/// leads to a single sync run covering every mapping that changed.
+pub struct SyncScheduler {
+    delay: Duration,
+    /// Watched local folder and scan options of each mapping, by mapping index
+    roots: Vec<(PathBuf, ScanOptions)>,
+    /// Mappings with changes in the current batch
+    pending: BTreeSet<usize>,
+    /// 当前这批变化：(第一个事件的时间, 计划同步的时间)
+    batch: Option<(Instant, Instant)>,
+    /// 映射索引 → 单独安排的同步时间（启动时、SSD 未连接时的重试）
+    deadlines: BTreeMap<usize, Instant>,
+}
+
+impl SyncScheduler {
+    pub fn new(roots: Vec<(PathBuf, ScanOptions)>, delay: Duration) -> Self {
+        Self { delay, roots, pending: BTreeSet::new(), batch: None, deadlines: BTreeMap::new() }
+    }
+
+    /// Add every mapping whose folder holds one of `paths` to the batch and restart the quiet
+    /// period. Paths the sync would not look at are left out. Returns whether any was scheduled.
+    pub fn on_event(&mut self, paths: &[PathBuf], now: Instant) -> bool {
+        let mut scheduled = false;
+        for (index, (root, opts)) in self.roots.iter().enumerate() {
+            if paths.iter().any(|path| watched(root, path, opts)) {
+                self.pending.insert(index);
+                scheduled = true;
+            }
+        }
+        if scheduled {
+            let first = self.batch.map_or(now, |(first, _)| first);
+            let due = (now + self.delay).min(first + self.delay * MAX_WAIT_FACTOR);
+            self.batch = Some((first, due));
+        }
+        scheduled
+    }
+
+    /// Sync a mapping at `at`, whether or not anything changed (e.g. when watching starts).
+    pub fn schedule_at(&mut self, index: usize, at: Instant) {
+        self.deadlines.insert(index, at);
+    }
+
+    /// Try a mapping again after [`RETRY_INTERVAL`] (e.g. its SSD is unplugged).
+    pub fn retry_later(&mut self, index: usize, now: Instant) {
+        self.schedule_at(index, now + RETRY_INTERVAL);
+    }
+
+    /// Mappings whose sync is due, to be synced in one run; they are no longer scheduled
+    /// afterwards.
+    pub fn take_due(&mut self, now: Instant) -> Vec<usize> {
+        let mut due: BTreeSet<usize> = self
+            .deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(index, _)| *index)
+            .collect();
+        for index in &due {
+            self.deadlines.remove(index);
+        }
+        if self.batch.is_some_and(|(_, batch_due)| batch_due <= now) {
+            due.append(&mut self.pending);
+            self.batch = None;
+        }
+        due.into_iter().collect()
+    }
+
+    /// When the next scheduled sync is due, if any.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.values().copied().chain(self.batch.map(|(_, due)| due)).min()
+    }
+}
+
+/// Whether a change at `path` can matter to the sync of `root`: it is inside the folder and
+/// not ignored, an in-progress copy, or the tool's own metadata.
+fn watched(root: &Path, path: &Path, opts: &ScanOptions) -> bool {
+    let Ok(rel) = path.strip_prefix(root) else {
+        return false;
+    };
+    let components: Vec<String> = rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if components.iter().any(|c| c == SYNCER_DIR_NAME) {
+        return false;
+    }
+    if components.last().is_some_and(|name| name.ends_with(&opts.temp_suffix)) {
+        return false;
+    }
+    // 被忽略目录下的任何变动都不触发同步（与扫描时跳过整个子树一致）
+    (1..=components.len()).all(|n| !opts.ignore.is_ignored(&components[..n].join("/")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ignore::IgnoreMatcher;
+
+    #[test]
+    fn test_event_schedules_debounced_sync() {
+        let root = PathBuf::from("/work/docs");
+        let opts = ScanOptions::new(IgnoreMatcher::new(&["node_modules".to_string(), "*.log".to_string()]));
+        let delay = Duration::from_secs(2);
+        let mut scheduler = SyncScheduler::new(vec![(root.clone(), opts)], delay);
+        let start = Instant::now();
+
+        // 忽略的目录、临时文件、元数据目录以及映射之外的路径都不触发同步
+        for path in [
+            "/work/docs/node_modules/pkg/index.js",
+            "/work/docs/app.log",
+            "/work/docs/a.txt.ssd-syncer.tmp",
+            "/work/docs/.ssd-syncer/sync.log",
+            "/work/other/a.txt",
+        ] {
+            assert!(!scheduler.on_event(&[PathBuf::from(path)], start), "{}", path);
+        }
+        assert_eq!(scheduler.next_deadline(), None);
+
+        assert!(scheduler.on_event(&[root.join("report/a.txt")], start));
+        assert!(scheduler.take_due(start + Duration::from_secs(1)).is_empty());
+        // 新的事件重新计时
+        scheduler.on_event(&[root.join("report/b.txt")], start + Duration::from_secs(1));
+        assert!(scheduler.take_due(start + delay).is_empty());
+        assert_eq!(scheduler.take_due(start + Duration::from_secs(3)), vec![0]);
+        assert!(scheduler.take_due(start + Duration::from_secs(10)).is_empty());
+
+        // SSD 未连接：稍后重试
+        scheduler.retry_later(0, start);
+        assert_eq!(scheduler.next_deadline(), Some(start + RETRY_INTERVAL));
+    }
+
+    #[test]
+    fn test_quick_events_across_mappings_sync_once() {
+        let roots: Vec<(PathBuf, ScanOptions)> = ["/work/docs", "/work/code", "/work/other"]
+            .iter()
+            .map(|root| (PathBuf::from(root), ScanOptions::new(IgnoreMatcher::new(&[]))))
+            .collect();
+        let delay = Duration::from_secs(2);
+        let mut scheduler = SyncScheduler::new(roots, delay);
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        // 编辑器保存：写临时文件、截断、重命名，两个映射几乎同时变化
+        for (ms, path) in [
+            (0, "/work/docs/a.txt"),
+            (300, "/work/code/main.rs"),
+            (600, "/work/docs/.a.txt.swp"),
+            (900, "/work/docs/a.txt"),
+            (1500, "/work/code/main.rs"),
+        ] {
+            scheduler.on_event(&[PathBuf::from(path)], at(ms));
+        }
+        assert!(scheduler.take_due(at(3000)).is_empty());
+        assert_eq!(scheduler.next_deadline(), Some(at(3500)));
+        // 一次同步覆盖两个映射，之后不再重复
+        assert_eq!(scheduler.take_due(at(3500)), vec![0, 1]);
+        assert!(scheduler.take_due(at(60_000)).is_empty());
+        assert_eq!(scheduler.next_deadline(), None);
+
+        // 持续变化的目录最多推迟 MAX_WAIT_FACTOR 个 delay，不会一直拖住其他映射
+        scheduler.on_event(&[PathBuf::from("/work/other/x")], at(60_000));
+        for ms in (60_500..70_000).step_by(500) {
+            scheduler.on_event(&[PathBuf::from("/work/docs/build.log")], at(ms));
+        }
+        assert_eq!(scheduler.take_due(at(70_000)), vec![0, 2]);
+    }
+}
@@ -0,0 +1,49 @@
+use std::process::Command;
+
+/// Run the binary against an isolated config, with stdout piped.
+fn ssd_syncer(config: &std::path::Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ssd-syncer"))
+        .arg("--config")
+        .arg(config)
+        .args(args)
+        .env("HOME", config.parent().unwrap())
+        .env_remove("NO_COLOR")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_no_ansi_escapes_unless_forced() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd/WORK"));
+    std::fs::create_dir_all(&local).unwrap();
+    std::fs::create_dir_all(&ssd).unwrap();
+    std::fs::write(local.join("a.txt"), "a").unwrap();
+    std::fs::write(ssd.join("b.txt"), "b").unwrap();
+    let config = tmp.path().join("config.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[machine]\nname = \"mac\"\n\n[[sync]]\nname = \"WORK\"\nlocal = {:?}\nssd = {:?}\n",
+            local.to_string_lossy(),
+            ssd.to_string_lossy()
+        ),
+    )
+    .unwrap();
+
+    // 管道输出（auto）和 never 都不含 ANSI 转义序列
+    for command in ["status", "diff"] {
+        for args in [&[command, "WORK"][..], &["--color", "never", command, "WORK"][..]] {
+            let stdout = ssd_syncer(&config, args);
+            assert!(stdout.contains("SSD"), "{}", stdout);
+            assert!(!stdout.contains('\x1b'), "{:?}: {}", args, stdout);
+        }
+        let stdout = ssd_syncer(&config, &["--color", "always", command, "WORK"]);
+        assert!(stdout.contains("\x1b[32m"), "{}", stdout);
+    }
+
+    let stdout = ssd_syncer(&config, &["sync", "WORK", "--force", "--color", "never"]);
+    assert!(stdout.contains("Copied to local") && !stdout.contains('\x1b'), "{}", stdout);
+}
@@ -0,0 +1,79 @@
+use std::process::Command;
+
+/// Run the binary against an isolated config (state and hash cache live next to it).
+fn ssd_syncer(config: &std::path::Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_ssd-syncer"))
+        .arg("--config")
+        .arg(config)
+        .args(args)
+        .env("HOME", config.parent().unwrap())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_quiet_no_op_sync_prints_nothing() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd/WORK"));
+    std::fs::create_dir_all(&local).unwrap();
+    std::fs::create_dir_all(&ssd).unwrap();
+    std::fs::write(local.join("a.txt"), "a").unwrap();
+    let config = tmp.path().join("config.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[machine]\nname = \"mac\"\n\n[[sync]]\nname = \"WORK\"\nlocal = {:?}\nssd = {:?}\n",
+            local.to_string_lossy(),
+            ssd.to_string_lossy()
+        ),
+    )
+    .unwrap();
+
+    let first = ssd_syncer(&config, &["sync", "WORK"]);
+    assert!(first.status.success(), "{}", String::from_utf8_lossy(&first.stderr));
+    assert!(!first.stdout.is_empty());
+
+    // 没有变化：--quiet 时 stdout 和 stderr 都没有输出
+    for args in [&["--quiet", "sync", "WORK"][..], &["status", "WORK", "-q"][..]] {
+        let output = ssd_syncer(&config, args);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    }
+
+    // 出错时仍然在 stderr 报告，并以非零状态退出
+    let failed = ssd_syncer(&config, &["--quiet", "sync", "MISSING"]);
+    assert!(!failed.status.success());
+    assert!(failed.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&failed.stderr).contains("No mapping found"));
+}
+
+#[test]
+fn test_quiet_first_sync_overlap_abort_explains_on_stderr() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd/WORK"));
+    std::fs::create_dir_all(&local).unwrap();
+    std::fs::create_dir_all(&ssd).unwrap();
+    // 两侧都有文件但毫不相干：首次同步需要确认，没有终端时中止
+    std::fs::write(local.join("a.txt"), "a").unwrap();
+    std::fs::write(ssd.join("b.txt"), "b").unwrap();
+    let config = tmp.path().join("config.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[machine]\nname = \"mac\"\n\n[[sync]]\nname = \"WORK\"\nlocal = {:?}\nssd = {:?}\n",
+            local.to_string_lossy(),
+            ssd.to_string_lossy()
+        ),
+    )
+    .unwrap();
+
+    let output = ssd_syncer(&config, &["--quiet", "sync", "WORK"]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("only 0% of them match"), "{}", stderr);
+    assert!(stderr.contains("First sync aborted"), "{}", stderr);
+    assert!(!stderr.contains("Syncing will merge"), "{}", stderr);
+    assert!(!ssd.join("a.txt").exists());
+}
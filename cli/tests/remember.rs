@@ -0,0 +1,93 @@
+use std::process::Command;
+
+/// Run the binary against an isolated config (state lives next to it).
+fn ssd_syncer(config: &std::path::Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ssd-syncer"))
+        .arg("--config")
+        .arg(config)
+        .args(args)
+        .env("HOME", config.parent().unwrap())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_add_falls_back_to_last_ssd_directory() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd/WORK"));
+    std::fs::create_dir_all(&local).unwrap();
+    std::fs::create_dir_all(&ssd).unwrap();
+    for client in ["acme", "globex"] {
+        std::fs::create_dir_all(tmp.path().join("clients").join(client)).unwrap();
+    }
+    std::fs::write(local.join("a.txt"), "a").unwrap();
+    // 没有 [machine] ssd_mount
+    let config = tmp.path().join("config.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[machine]\nname = \"mac\"\n\n[[sync]]\nname = \"WORK\"\nlocal = {:?}\nssd = {:?}\n",
+            local.to_string_lossy(),
+            ssd.to_string_lossy()
+        ),
+    )
+    .unwrap();
+    let pattern = format!("{}/*", tmp.path().join("clients").display());
+
+    // 还没有记录：相对模板需要 --ssd
+    let output = Command::new(env!("CARGO_BIN_EXE_ssd-syncer"))
+        .arg("--config")
+        .arg(&config)
+        .args(["add", "--local", &pattern, "--ssd-template", "{name}_SYNC"])
+        .env("HOME", tmp.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    // --no-remember 的同步不记录 SSD 目录
+    ssd_syncer(&config, &["sync", "WORK", "--no-remember"]);
+    assert!(!std::fs::read_to_string(tmp.path().join("state.toml")).unwrap_or_default().contains("last_ssd_mount"));
+
+    ssd_syncer(&config, &["sync", "WORK"]);
+    assert!(std::fs::read_to_string(tmp.path().join("state.toml")).unwrap().contains("last_ssd_mount"));
+    let stdout = ssd_syncer(&config, &["add", "--local", &pattern, "--ssd-template", "{name}_SYNC"]);
+    assert!(stdout.contains("Using last used SSD directory"), "{}", stdout);
+    let content = std::fs::read_to_string(&config).unwrap();
+    let expected = tmp.path().join("ssd/acme_SYNC");
+    assert!(content.contains(&format!("{:?}", expected.to_string_lossy())), "{}", content);
+}
+
+#[test]
+fn test_bare_sync_with_several_mappings_still_errors() {
+    let tmp = tempfile::tempdir().unwrap();
+    let mut config_text = "[machine]\nname = \"mac\"\n".to_string();
+    for name in ["WORK", "DOCS"] {
+        let (local, ssd) = (tmp.path().join("local").join(name), tmp.path().join("ssd").join(name));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&ssd).unwrap();
+        config_text.push_str(&format!(
+            "\n[[sync]]\nname = \"{}\"\nlocal = {:?}\nssd = {:?}\n",
+            name,
+            local.to_string_lossy(),
+            ssd.to_string_lossy()
+        ));
+    }
+    let config = tmp.path().join("config.toml");
+    std::fs::write(&config, config_text).unwrap();
+
+    // 同步过 WORK 之后，不带名称的命令仍然要求指定映射，而不是回退到上次同步的映射
+    ssd_syncer(&config, &["sync", "WORK"]);
+    for args in [&["sync"][..], &["status"][..]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_ssd-syncer"))
+            .arg("--config")
+            .arg(&config)
+            .args(args)
+            .env("HOME", tmp.path())
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Multiple mappings configured"), "{:?}", args);
+    }
+}
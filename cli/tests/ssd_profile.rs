@@ -0,0 +1,63 @@
+use std::process::Command;
+
+/// Run the binary against an isolated config.
+fn ssd_syncer(config: &std::path::Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_ssd-syncer"))
+        .arg("--config")
+        .arg(config)
+        .args(args)
+        .env("HOME", config.parent().unwrap())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_drive_ids_per_ssd_profile() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (work, backup) = (tmp.path().join("WORK"), tmp.path().join("BACKUP"));
+    let local = tmp.path().join("local");
+    for dir in [&local, &work.join("DOCS"), &backup.join("DOCS")] {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    std::fs::write(local.join("a.txt"), "a").unwrap();
+    let config = tmp.path().join("config.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[machine]\nname = \"mac\"\nssd_mount = {:?}\n\n[ssd.backup]\nmount = {:?}\n\n\
+             [[sync]]\nname = \"DOCS\"\nlocal = {:?}\nssd = {:?}\n",
+            work.to_string_lossy(),
+            backup.to_string_lossy(),
+            local.to_string_lossy(),
+            work.join("DOCS").to_string_lossy()
+        ),
+    )
+    .unwrap();
+
+    // 默认 SSD 和备用 SSD 交替同步，都不需要 --force
+    for args in [
+        &["sync", "DOCS"][..],
+        &["--ssd-profile", "backup", "sync", "DOCS"][..],
+        &["sync", "DOCS"][..],
+        &["--ssd-profile", "backup", "sync", "DOCS"][..],
+    ] {
+        let output = ssd_syncer(&config, args);
+        assert!(output.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    }
+    assert_eq!(std::fs::read_to_string(backup.join("DOCS/a.txt")).unwrap(), "a");
+
+    let read_id = |ssd: &std::path::Path| std::fs::read_to_string(ssd.join("DOCS/.ssd-syncer/drive-id")).unwrap();
+    let (work_id, backup_id) = (read_id(&work), read_id(&backup));
+    assert_ne!(work_id, backup_id);
+    let content = std::fs::read_to_string(&config).unwrap();
+    assert!(content.contains(&format!("drive_id = \"{}\"", work_id.trim())), "{}", content);
+    assert!(content.contains(&format!("backup = \"{}\"", backup_id.trim())), "{}", content);
+    // 映射路径仍指向默认 SSD
+    assert!(content.contains(&format!("{:?}", work.join("DOCS").to_string_lossy())), "{}", content);
+
+    // 备用 SSD 的挂载点上换了一块硬盘：仍然拒绝
+    std::fs::write(backup.join("DOCS/.ssd-syncer/drive-id"), "other\n").unwrap();
+    let output = ssd_syncer(&config, &["--ssd-profile", "backup", "sync", "DOCS"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected drive ID"));
+}
@@ -0,0 +1,58 @@
+// 回收站位置由 XDG_DATA_HOME 决定：在单独的进程里设置，不影响并行运行的其他测试。
+#![cfg(target_os = "linux")]
+
+use std::path::Path;
+use std::process::Command;
+
+/// Run the binary against an isolated config, with the trash under `data_home`.
+fn ssd_syncer(config: &Path, data_home: &Path, args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_ssd-syncer"))
+        .arg("--config")
+        .arg(config)
+        .args(args)
+        .env("HOME", config.parent().unwrap())
+        .env("XDG_DATA_HOME", data_home)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_delete_to_trash() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (local, ssd) = (tmp.path().join("local"), tmp.path().join("ssd/WORK"));
+    for dir in [&local, &ssd] {
+        std::fs::create_dir_all(dir.join("empty")).unwrap();
+        std::fs::write(dir.join("a.txt"), "a").unwrap();
+        std::fs::write(dir.join("b.txt"), "b").unwrap();
+    }
+    let config = tmp.path().join("config.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "[machine]\nname = \"mac\"\n\n[[sync]]\nname = \"WORK\"\nlocal = {:?}\nssd = {:?}\n",
+            local.to_string_lossy(),
+            ssd.to_string_lossy()
+        ),
+    )
+    .unwrap();
+    let data = tmp.path().join("data");
+    ssd_syncer(&config, &data, &["sync", "WORK"]);
+
+    // 本地删除的文件和空目录：SSD 上的副本移到回收站
+    std::fs::remove_file(local.join("a.txt")).unwrap();
+    std::fs::remove_dir(local.join("empty")).unwrap();
+    ssd_syncer(&config, &data, &["sync", "WORK", "--delete-to-trash"]);
+    let trashed = data.join("Trash/files");
+    assert!(!ssd.join("a.txt").exists() && !ssd.join("empty").exists());
+    assert_eq!(std::fs::read_to_string(trashed.join("a.txt")).unwrap(), "a");
+    assert!(trashed.join("empty").is_dir());
+
+    // 回收站不可用：退回到直接删除
+    let not_a_dir = tmp.path().join("not-a-dir");
+    std::fs::write(&not_a_dir, "").unwrap();
+    std::fs::remove_file(local.join("b.txt")).unwrap();
+    ssd_syncer(&config, &not_a_dir, &["sync", "WORK", "--delete-to-trash"]);
+    assert!(!ssd.join("b.txt").exists());
+    assert!(!trashed.join("b.txt").exists());
+}